@@ -0,0 +1,153 @@
+use chrono::NaiveDateTime;
+use ctfdb::htb::structs::RankHistoryPoint;
+use failure::Error;
+use image::{DynamicImage, ImageOutputFormat, RgbImage};
+use plotters::{coord::Shift, prelude::*};
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 600;
+const FONT: &str = "sans-serif";
+
+/// Renders the team's rank (top) and points (bottom) over time as a PNG.
+pub fn render_rank_chart(history: &[RankHistoryPoint]) -> Result<Vec<u8>, Error> {
+    if history.len() < 2 {
+        return Err(format_err!("Need at least two points to draw a chart"));
+    }
+
+    let mut buffer = vec![0u8; (WIDTH * HEIGHT * 3) as usize];
+
+    {
+        let root = BitMapBackend::with_buffer(&mut buffer, (WIDTH, HEIGHT)).into_drawing_area();
+        root.fill(&WHITE)?;
+
+        let (rank_area, points_area) = root.split_vertically(HEIGHT / 2);
+
+        // A lower rank is better, so flip it to have the line go up as the team climbs
+        draw_line(
+            &rank_area,
+            "Rank",
+            history,
+            |point| -point.rank,
+            |rank| format!("#{}", -rank),
+            &RED,
+        )?;
+        draw_line(
+            &points_area,
+            "Points",
+            history,
+            |point| point.points,
+            |points| points.to_string(),
+            &BLUE,
+        )?;
+
+        root.present()?;
+    }
+
+    let image = RgbImage::from_raw(WIDTH, HEIGHT, buffer)
+        .ok_or_else(|| format_err!("Chart buffer was the wrong size"))?;
+
+    let mut png = vec![];
+    DynamicImage::ImageRgb8(image).write_to(&mut png, ImageOutputFormat::Png)?;
+
+    Ok(png)
+}
+
+/// Labels the time axis with dates, or times too when the chart covers a couple of days or less.
+fn time_label(timestamp: i64, span: i64) -> String {
+    let format = if span > 2 * 24 * 60 * 60 {
+        "%d %b"
+    } else {
+        "%d %b %H:%M"
+    };
+
+    NaiveDateTime::from_timestamp(timestamp, 0)
+        .format(format)
+        .to_string()
+}
+
+fn draw_line(
+    area: &DrawingArea<BitMapBackend, Shift>,
+    caption: &str,
+    history: &[RankHistoryPoint],
+    value: impl Fn(&RankHistoryPoint) -> i32,
+    label: impl Fn(i32) -> String,
+    colour: &RGBColor,
+) -> Result<(), Error> {
+    let start = history[0].timestamp.timestamp();
+    let end = history[history.len() - 1]
+        .timestamp
+        .timestamp()
+        .max(start + 1);
+
+    let values: Vec<i32> = history.iter().map(&value).collect();
+    let min = *values.iter().min().unwrap_or(&0);
+    let max = (*values.iter().max().unwrap_or(&0)).max(min + 1);
+
+    let mut chart = ChartBuilder::on(area)
+        .caption(caption, (FONT, 20))
+        .margin(10)
+        .x_label_area_size(30)
+        .y_label_area_size(60)
+        .build_cartesian_2d(start..end, min..max)?;
+
+    chart
+        .configure_mesh()
+        .x_labels(6)
+        .y_labels(6)
+        .x_label_formatter(&|timestamp| time_label(*timestamp, end - start))
+        .y_label_formatter(&|value| label(*value))
+        .label_style((FONT, 14))
+        .draw()?;
+
+    chart.draw_series(LineSeries::new(
+        history
+            .iter()
+            .zip(values)
+            .map(|(point, value)| (point.timestamp.timestamp(), value)),
+        colour.stroke_width(3),
+    ))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn test_render_rank_chart() {
+        let history = vec![
+            RankHistoryPoint {
+                timestamp: NaiveDate::from_ymd(2021, 10, 14).and_hms(0, 0, 0),
+                rank: 120,
+                points: 300,
+            },
+            RankHistoryPoint {
+                timestamp: NaiveDate::from_ymd(2021, 10, 15).and_hms(0, 0, 0),
+                rank: 110,
+                points: 320,
+            },
+        ];
+
+        assert!(render_rank_chart(&history[..1]).is_err());
+
+        match render_rank_chart(&history) {
+            Ok(png) => assert_eq!(&png[1..4], b"PNG"),
+            // Hosts without any fonts installed can't draw the labels, which is the only way
+            // rendering should fail
+            Err(why) => assert!(why.to_string().to_lowercase().contains("font"), "{}", why),
+        }
+    }
+
+    #[test]
+    fn test_time_label() {
+        let timestamp = NaiveDate::from_ymd(2021, 10, 14)
+            .and_hms(9, 30, 0)
+            .timestamp();
+
+        assert_eq!(time_label(timestamp, 7 * 24 * 60 * 60), "14 Oct");
+        assert_eq!(time_label(timestamp, 24 * 60 * 60), "14 Oct 09:30");
+    }
+}
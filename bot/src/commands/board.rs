@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+
+use failure::Error;
+use serenity::{
+    builder::{CreateActionRow, CreateEmbed},
+    client::Context,
+    model::interactions::{
+        message_component::{ButtonStyle, MessageComponentInteraction},
+        InteractionResponseType,
+    },
+};
+
+use ctfdb::{
+    ctfs::db::{
+        get_challenges_for_ctfid, get_ctf_for_channel, get_ctf_from_id, get_ctf_id_from_name,
+    },
+    models::{Challenge, Ctf},
+};
+
+use crate::commands::{
+    buttons::respond_privately,
+    core::{add_action_rows, build_embed, has_role, Invocation, Reply},
+};
+use crate::discord_name_provider::ctf_working;
+use crate::status_board::join_lines;
+
+/// How many challenges are shown on each page of the board.
+pub const CHALLENGES_PER_PAGE: usize = 10;
+
+/// Every board button's custom id starts with this.
+pub const BOARD_BUTTON_PREFIX: &str = "board:";
+
+/// Which challenges the board shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoardFilter {
+    All,
+    Todo,
+    InProgress,
+    Solved,
+}
+
+impl BoardFilter {
+    const FILTERS: [BoardFilter; 4] = [
+        BoardFilter::All,
+        BoardFilter::Todo,
+        BoardFilter::InProgress,
+        BoardFilter::Solved,
+    ];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            BoardFilter::All => "all",
+            BoardFilter::Todo => "todo",
+            BoardFilter::InProgress => "progress",
+            BoardFilter::Solved => "solved",
+        }
+    }
+
+    fn parse(filter: &str) -> Option<Self> {
+        BoardFilter::FILTERS
+            .iter()
+            .copied()
+            .find(|candidate| candidate.as_str() == filter)
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            BoardFilter::All => "All",
+            BoardFilter::Todo => "Todo",
+            BoardFilter::InProgress => "In progress",
+            BoardFilter::Solved => "Solved",
+        }
+    }
+
+    fn matches(&self, challenge: &Challenge, working: Option<&str>) -> bool {
+        match self {
+            BoardFilter::All => true,
+            BoardFilter::Todo => !challenge.solved && working.is_none(),
+            BoardFilter::InProgress => !challenge.solved && working.is_some(),
+            BoardFilter::Solved => challenge.solved,
+        }
+    }
+}
+
+/// What the board is showing, carried in its buttons' custom ids so it can be redrawn after a
+/// restart, e.g. ``board:3:todo:1:next``.
+#[derive(Debug, PartialEq)]
+pub struct BoardState {
+    pub ctf_id: i32,
+    pub filter: BoardFilter,
+    pub page: usize,
+}
+
+impl BoardState {
+    pub fn new(ctf_id: i32) -> Self {
+        BoardState {
+            ctf_id,
+            filter: BoardFilter::All,
+            page: 0,
+        }
+    }
+
+    /// The trailing tag keeps ids unique within the message when two buttons lead to the same page.
+    fn button_id(&self, tag: &str) -> String {
+        format!(
+            "{}{}:{}:{}:{}",
+            BOARD_BUTTON_PREFIX,
+            self.ctf_id,
+            self.filter.as_str(),
+            self.page,
+            tag
+        )
+    }
+}
+
+pub fn parse_board_button_id(custom_id: &str) -> Option<BoardState> {
+    let mut parts = custom_id.strip_prefix(BOARD_BUTTON_PREFIX)?.split(':');
+
+    let ctf_id = parts.next()?.parse::<i32>().ok()?;
+    let filter = BoardFilter::parse(parts.next()?)?;
+    let page = parts.next()?.parse::<usize>().ok()?;
+
+    Some(BoardState {
+        ctf_id,
+        filter,
+        page,
+    })
+}
+
+fn challenge_ids(challenges: &[Challenge]) -> Vec<i32> {
+    challenges.iter().map(|challenge| challenge.id).collect()
+}
+
+fn page_count(challenges: usize) -> usize {
+    ((challenges + CHALLENGES_PER_PAGE - 1) / CHALLENGES_PER_PAGE).max(1)
+}
+
+fn challenge_line(challenge: &Challenge, working: Option<&str>) -> String {
+    let (status, who) = if challenge.solved {
+        ("✅", challenge.solver.as_deref())
+    } else if working.is_some() {
+        ("🧰", working)
+    } else {
+        ("❓", None)
+    };
+
+    match who {
+        Some(who) => format!(
+            "{} {} ({}) - {}",
+            status, challenge.name, challenge.points, who
+        ),
+        None => format!("{} {} ({})", status, challenge.name, challenge.points),
+    }
+}
+
+/// Draws a page of the board, with challenges grouped by category.
+pub fn render_board(
+    ctf: &Ctf,
+    challenges: Vec<Challenge>,
+    working: &HashMap<i32, String>,
+    state: &BoardState,
+) -> (CreateEmbed, Vec<CreateActionRow>) {
+    let working_on = |challenge: &Challenge| working.get(&challenge.id).map(String::as_str);
+
+    let total = challenges.len();
+    let solved = challenges
+        .iter()
+        .filter(|challenge| challenge.solved)
+        .count();
+
+    let mut shown: Vec<Challenge> = challenges
+        .into_iter()
+        .filter(|challenge| state.filter.matches(challenge, working_on(challenge)))
+        .collect();
+    shown.sort_by(|a, b| (&a.category, &a.name).cmp(&(&b.category, &b.name)));
+
+    let pages = page_count(shown.len());
+    let page = state.page.min(pages - 1);
+
+    let mut categories: Vec<(String, Vec<String>)> = vec![];
+    for challenge in shown
+        .iter()
+        .skip(page * CHALLENGES_PER_PAGE)
+        .take(CHALLENGES_PER_PAGE)
+    {
+        match categories.last_mut() {
+            Some((category, lines)) if *category == challenge.category => {
+                lines.push(challenge_line(challenge, working_on(challenge)))
+            }
+            _ => categories.push((
+                challenge.category.clone(),
+                vec![challenge_line(challenge, working_on(challenge))],
+            )),
+        }
+    }
+
+    let embed = build_embed(|e| {
+        e.title(format!("📋 {}", ctf.name));
+
+        let mut description = format!(
+            "🏴‍ {}/{} solved, showing {}",
+            solved,
+            total,
+            state.filter.label().to_lowercase()
+        );
+        if categories.is_empty() {
+            description.push_str("\n\nNothing to show here!");
+        }
+        e.description(description);
+
+        for (category, lines) in categories {
+            e.field(format!("📚 {}", category), join_lines(lines), false);
+        }
+
+        e.footer(|f| f.text(format!("Page {} of {}", page + 1, pages)));
+    });
+
+    let current = BoardState {
+        ctf_id: state.ctf_id,
+        filter: state.filter,
+        page,
+    };
+
+    let mut navigation = CreateActionRow::default();
+    navigation
+        .create_button(|b| {
+            b.style(ButtonStyle::Secondary)
+                .label("◀ Previous")
+                .disabled(page == 0)
+                .custom_id(
+                    BoardState {
+                        page: page.saturating_sub(1),
+                        ..current
+                    }
+                    .button_id("previous"),
+                )
+        })
+        .create_button(|b| {
+            b.style(ButtonStyle::Secondary)
+                .label("Next ▶")
+                .disabled(page + 1 >= pages)
+                .custom_id(
+                    BoardState {
+                        page: (page + 1).min(pages - 1),
+                        ..current
+                    }
+                    .button_id("next"),
+                )
+        });
+
+    let mut filters = CreateActionRow::default();
+    for filter in BoardFilter::FILTERS.iter().copied() {
+        filters.create_button(|b| {
+            let style = if filter == state.filter {
+                ButtonStyle::Primary
+            } else {
+                ButtonStyle::Secondary
+            };
+
+            b.style(style).label(filter.label()).custom_id(
+                BoardState {
+                    ctf_id: state.ctf_id,
+                    filter,
+                    page: 0,
+                }
+                .button_id("filter"),
+            )
+        });
+    }
+
+    (embed, vec![navigation, filters])
+}
+
+/// Shows the board for the given CTF, or the CTF for the channel the command was used in.
+pub async fn show_board(invocation: &Invocation, ctf_name: Option<&str>) -> Result<Reply, Error> {
+    let ctf = match ctf_name {
+        Some(ctf_name) => match get_ctf_id_from_name(ctf_name).await {
+            Some(ctf_id) => get_ctf_from_id(ctf_id).await?,
+            None => return Ok(Reply::error("No CTF exists for that name!")),
+        },
+        None => match get_ctf_for_channel(invocation.channel_id.0 as i64).await {
+            Ok(ctf) => ctf,
+            Err(_) => {
+                return Ok(Reply::error(
+                    "No challenges found, is there an active CTF for this channel?",
+                ))
+            }
+        },
+    };
+
+    let challenges = get_challenges_for_ctfid(ctf.id).await?;
+    let working = ctf_working(
+        &invocation.http,
+        invocation.guild_id,
+        &challenge_ids(&challenges),
+    )
+    .await?;
+    let (embed, rows) = render_board(&ctf, challenges, &working, &BoardState::new(ctf.id));
+
+    Ok(Reply::embeds(vec![embed]).with_components(vec![rows]))
+}
+
+pub async fn handle_board_button(ctx: &Context, component: &MessageComponentInteraction) {
+    if let Err(why) = update_board(ctx, component).await {
+        eprintln!(
+            "Error when handling board button '{}'... {}",
+            component.data.custom_id, why
+        );
+
+        if let Err(why) = respond_privately(
+            ctx,
+            component,
+            build_embed(|e| {
+                e.description(format!("Something went wrong... {}", why));
+            }),
+        )
+        .await
+        {
+            eprintln!("Error when responding to board button... {}", why);
+        }
+    }
+}
+
+async fn update_board(ctx: &Context, component: &MessageComponentInteraction) -> Result<(), Error> {
+    let state = match parse_board_button_id(&component.data.custom_id) {
+        Some(state) => state,
+        None => return Err(format_err!("Unknown button!")),
+    };
+
+    // Boards in DMs were only sent to CTFers, and there's no guild to check roles in
+    if component.guild_id.is_some() && !has_role(ctx, component.member.as_ref(), "CTFer").await {
+        return Err(format_err!("You need the CTFer role to use this button."));
+    }
+
+    let ctf = get_ctf_from_id(state.ctf_id).await?;
+    let challenges = get_challenges_for_ctfid(ctf.id).await?;
+    let working = ctf_working(&ctx.http, component.guild_id, &challenge_ids(&challenges)).await?;
+    let (embed, rows) = render_board(&ctf, challenges, &working, &state);
+
+    component
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|message| {
+                    message
+                        .add_embed(embed)
+                        .components(|c| add_action_rows(c, rows))
+                })
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_board_button_id() {
+        let state = BoardState {
+            ctf_id: 3,
+            filter: BoardFilter::InProgress,
+            page: 2,
+        };
+
+        assert_eq!(state.button_id("next"), "board:3:progress:2:next");
+        assert_eq!(parse_board_button_id(&state.button_id("next")), Some(state));
+        assert_eq!(parse_board_button_id("board:3:everything:0:filter"), None);
+        assert_eq!(parse_board_button_id("ctf:working:3"), None);
+    }
+
+    #[test]
+    fn test_board_filter() {
+        let todo = Challenge::sample("todo", "web");
+        let working = Challenge::sample("working", "web");
+        let solved = Challenge::sample("solved", "web").solved_by("user");
+
+        assert!(BoardFilter::Todo.matches(&todo, None));
+        assert!(!BoardFilter::Todo.matches(&working, Some("user")));
+        assert!(BoardFilter::InProgress.matches(&working, Some("user")));
+        assert!(BoardFilter::Solved.matches(&solved, None));
+        assert!(!BoardFilter::Solved.matches(&todo, None));
+    }
+
+    #[test]
+    fn test_page_count() {
+        assert_eq!(page_count(0), 1);
+        assert_eq!(page_count(CHALLENGES_PER_PAGE), 1);
+        assert_eq!(page_count(CHALLENGES_PER_PAGE + 1), 2);
+    }
+
+    #[test]
+    fn test_challenge_line() {
+        assert_eq!(
+            challenge_line(&Challenge::sample("Baby RSA", "web"), Some("a, b")),
+            "🧰 Baby RSA (100) - a, b"
+        );
+        assert_eq!(
+            challenge_line(
+                &Challenge::sample("Baby RSA", "web").solved_by("user"),
+                None
+            ),
+            "✅ Baby RSA (100) - user"
+        );
+    }
+}
@@ -0,0 +1,327 @@
+use failure::Error;
+use serenity::{
+    builder::{CreateActionRow, CreateEmbed},
+    client::Context,
+    model::{
+        channel::{Embed, ReactionType},
+        interactions::{
+            message_component::{ButtonStyle, InteractionMessage, MessageComponentInteraction},
+            InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+        },
+    },
+};
+
+use ctfdb::ctfs::db::{add_working_by_id, get_challenge_from_id, remove_working_by_id};
+use ctfdb::models::Challenge;
+
+use crate::commands::core::{build_embed, has_role, Invocation};
+use crate::commands::ctf::already_solved;
+use crate::discord_name_provider::ctf_working;
+use crate::{populate_embed_from_challenge, WORKING_FIELD_NAME};
+
+/// What a button on a challenge embed does.
+#[derive(Debug, PartialEq)]
+pub enum ChallengeAction {
+    Working,
+    GiveUp,
+    Details,
+}
+
+impl ChallengeAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ChallengeAction::Working => "working",
+            ChallengeAction::GiveUp => "giveup",
+            ChallengeAction::Details => "details",
+        }
+    }
+
+    fn parse(action: &str) -> Option<Self> {
+        match action {
+            "working" => Some(ChallengeAction::Working),
+            "giveup" => Some(ChallengeAction::GiveUp),
+            "details" => Some(ChallengeAction::Details),
+            _ => None,
+        }
+    }
+}
+
+/// Buttons carry what they do and the challenge they're for, e.g. ``ctf:working:12``, so they keep
+/// working after a restart.
+pub fn challenge_button_id(action: &ChallengeAction, challenge_id: i32) -> String {
+    format!("ctf:{}:{}", action.as_str(), challenge_id)
+}
+
+pub fn parse_challenge_button_id(custom_id: &str) -> Option<(ChallengeAction, i32)> {
+    let mut parts = custom_id.splitn(3, ':');
+
+    if parts.next()? != "ctf" {
+        return None;
+    }
+
+    let action = ChallengeAction::parse(parts.next()?)?;
+    let challenge_id = parts.next()?.parse::<i32>().ok()?;
+
+    Some((action, challenge_id))
+}
+
+/// Discord won't show a longer label on a button than this.
+const MAX_LABEL_LENGTH: usize = 80;
+
+/// The row of buttons sent along with a challenge's embed.
+pub fn challenge_buttons(challenge_id: i32) -> CreateActionRow {
+    buttons_with_details_label(challenge_id, "Show details".to_string())
+}
+
+/// The row of buttons for one of several challenges in the same message, with the challenge's name
+/// on it so it's clear which row is which.
+pub fn named_challenge_buttons(challenge: &Challenge) -> CreateActionRow {
+    buttons_with_details_label(
+        challenge.id,
+        challenge.name.chars().take(MAX_LABEL_LENGTH).collect(),
+    )
+}
+
+fn buttons_with_details_label(challenge_id: i32, details_label: String) -> CreateActionRow {
+    let mut row = CreateActionRow::default();
+
+    row.create_button(|b| {
+        b.style(ButtonStyle::Primary)
+            .emoji(ReactionType::Unicode("🧰".to_string()))
+            .label("I'm working on this")
+            .custom_id(challenge_button_id(&ChallengeAction::Working, challenge_id))
+    })
+    .create_button(|b| {
+        b.style(ButtonStyle::Secondary)
+            .emoji(ReactionType::Unicode("🏳️".to_string()))
+            .label("Give up")
+            .custom_id(challenge_button_id(&ChallengeAction::GiveUp, challenge_id))
+    })
+    .create_button(|b| {
+        b.style(ButtonStyle::Secondary)
+            .emoji(ReactionType::Unicode("🔎".to_string()))
+            .label(details_label)
+            .custom_id(challenge_button_id(&ChallengeAction::Details, challenge_id))
+    });
+
+    row
+}
+
+/// Rebuilds an embed that's already been sent with an up to date list of who's working on it,
+/// keeping everything else about it as it was.
+fn refresh_working_field(embed: &Embed, working: Option<&str>) -> CreateEmbed {
+    let mut refreshed = CreateEmbed::default();
+    refreshed.colour(embed.colour);
+
+    if let Some(title) = &embed.title {
+        refreshed.title(title);
+    }
+
+    if let Some(description) = &embed.description {
+        refreshed.description(description);
+    }
+
+    if let Some(url) = &embed.url {
+        refreshed.url(url);
+    }
+
+    if let Some(timestamp) = &embed.timestamp {
+        refreshed.timestamp(timestamp.clone());
+    }
+
+    if let Some(author) = &embed.author {
+        refreshed.author(|a| {
+            a.name(&author.name);
+            if let Some(url) = &author.url {
+                a.url(url);
+            }
+            if let Some(icon_url) = &author.icon_url {
+                a.icon_url(icon_url);
+            }
+            a
+        });
+    }
+
+    if let Some(thumbnail) = &embed.thumbnail {
+        refreshed.thumbnail(&thumbnail.url);
+    }
+
+    if let Some(image) = &embed.image {
+        refreshed.image(&image.url);
+    }
+
+    if let Some(footer) = &embed.footer {
+        refreshed.footer(|f| {
+            f.text(&footer.text);
+            if let Some(icon_url) = &footer.icon_url {
+                f.icon_url(icon_url);
+            }
+            f
+        });
+    }
+
+    for field in embed
+        .fields
+        .iter()
+        .filter(|field| field.name != WORKING_FIELD_NAME)
+    {
+        refreshed.field(&field.name, &field.value, field.inline);
+    }
+
+    if let Some(working) = working {
+        refreshed.field(WORKING_FIELD_NAME, working, true);
+    }
+
+    refreshed
+}
+
+pub async fn handle_challenge_button(ctx: &Context, component: &MessageComponentInteraction) {
+    if let Err(why) = run_challenge_button(ctx, component).await {
+        eprintln!(
+            "Error when handling button '{}'... {}",
+            component.data.custom_id, why
+        );
+
+        if let Err(why) = respond_privately(
+            ctx,
+            component,
+            build_embed(|e| {
+                e.description(format!("Something went wrong... {}", why));
+            }),
+        )
+        .await
+        {
+            eprintln!("Error when responding to button... {}", why);
+        }
+    }
+}
+
+async fn run_challenge_button(
+    ctx: &Context,
+    component: &MessageComponentInteraction,
+) -> Result<(), Error> {
+    let (action, challenge_id) = match parse_challenge_button_id(&component.data.custom_id) {
+        Some(parsed) => parsed,
+        None => return Err(format_err!("Unknown button!")),
+    };
+
+    // Buttons in DMs are on lists only sent to CTFers, and there's no guild to check roles in
+    if component.guild_id.is_some() && !has_role(ctx, component.member.as_ref(), "CTFer").await {
+        return Err(format_err!("You need the CTFer role to use this button."));
+    }
+
+    let challenge = get_challenge_from_id(challenge_id).await?;
+    let invocation = Invocation::from_component(ctx, component);
+
+    match action {
+        ChallengeAction::Details => {
+            let working = ctf_working(&ctx.http, component.guild_id, &[challenge.id]).await?;
+
+            return respond_privately(
+                ctx,
+                component,
+                build_embed(|e| {
+                    populate_embed_from_challenge(
+                        challenge,
+                        working.get(&challenge_id).map(String::as_str),
+                        e,
+                    )
+                }),
+            )
+            .await;
+        }
+        ChallengeAction::Working if challenge.solved => {
+            return respond_privately(
+                ctx,
+                component,
+                build_embed(|e| {
+                    e.description(already_solved(&challenge));
+                }),
+            )
+            .await;
+        }
+        ChallengeAction::Working => {
+            add_working_by_id(
+                invocation.user_id.0 as i64,
+                &invocation.username,
+                challenge.id,
+            )
+            .await?
+        }
+        ChallengeAction::GiveUp => {
+            remove_working_by_id(invocation.user_id.0 as i64, &challenge).await?
+        }
+    }
+
+    let working = ctf_working(&ctx.http, component.guild_id, &[challenge.id]).await?;
+    let working = working.get(&challenge_id).map(String::as_str);
+
+    let existing = match &component.message {
+        // Several challenges share an embed, so there's no one working field to update
+        InteractionMessage::Regular(message) if message.components.len() > 1 => {
+            return respond_privately(
+                ctx,
+                component,
+                build_embed(|e| populate_embed_from_challenge(challenge, working, e)),
+            )
+            .await;
+        }
+        InteractionMessage::Regular(message) => message.embeds.first(),
+        _ => None,
+    };
+
+    let embed = match existing {
+        Some(existing) => refresh_working_field(existing, working),
+        None => build_embed(|e| populate_embed_from_challenge(challenge, working, e)),
+    };
+
+    component
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|message| message.add_embed(embed))
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Sends an embed only whoever pressed the button can see.
+pub async fn respond_privately(
+    ctx: &Context,
+    component: &MessageComponentInteraction,
+    embed: CreateEmbed,
+) -> Result<(), Error> {
+    component
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|message| {
+                    message
+                        .add_embed(embed)
+                        .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                })
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_challenge_button_id() {
+        let custom_id = challenge_button_id(&ChallengeAction::GiveUp, 42);
+
+        assert_eq!(custom_id, "ctf:giveup:42");
+        assert_eq!(
+            parse_challenge_button_id(&custom_id),
+            Some((ChallengeAction::GiveUp, 42))
+        );
+        assert_eq!(parse_challenge_button_id("htb:working:42"), None);
+        assert_eq!(parse_challenge_button_id("ctf:solve:42"), None);
+        assert_eq!(parse_challenge_button_id("ctf:working:"), None);
+    }
+}
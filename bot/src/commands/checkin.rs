@@ -0,0 +1,164 @@
+use failure::Error;
+use serenity::{
+    builder::CreateActionRow,
+    client::Context,
+    model::{
+        channel::ReactionType,
+        interactions::{
+            message_component::{ButtonStyle, MessageComponentInteraction},
+            InteractionResponseType,
+        },
+    },
+};
+
+use ctfdb::ctfs::db::{confirm_worker, get_challenge_from_id, get_worker_from_id, remove_worker};
+
+use crate::commands::{buttons::respond_privately, core::build_embed};
+
+/// Every check in button's custom id starts with this.
+pub const CHECKIN_BUTTON_PREFIX: &str = "checkin:";
+
+/// How someone answers being asked if they're still working on a challenge.
+#[derive(Debug, PartialEq)]
+pub enum CheckinAnswer {
+    StillOn,
+    Done,
+}
+
+impl CheckinAnswer {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CheckinAnswer::StillOn => "yes",
+            CheckinAnswer::Done => "no",
+        }
+    }
+
+    fn parse(answer: &str) -> Option<Self> {
+        match answer {
+            "yes" => Some(CheckinAnswer::StillOn),
+            "no" => Some(CheckinAnswer::Done),
+            _ => None,
+        }
+    }
+}
+
+/// Check in buttons carry the answer and the worker they're for, e.g. ``checkin:yes:12``.
+pub fn checkin_button_id(answer: &CheckinAnswer, worker_id: i32) -> String {
+    format!("{}{}:{}", CHECKIN_BUTTON_PREFIX, answer.as_str(), worker_id)
+}
+
+pub fn parse_checkin_button_id(custom_id: &str) -> Option<(CheckinAnswer, i32)> {
+    let mut parts = custom_id.strip_prefix(CHECKIN_BUTTON_PREFIX)?.split(':');
+
+    let answer = CheckinAnswer::parse(parts.next()?)?;
+    let worker_id = parts.next()?.parse::<i32>().ok()?;
+
+    Some((answer, worker_id))
+}
+
+/// The row of buttons sent with a check in.
+pub fn checkin_buttons(worker_id: i32) -> CreateActionRow {
+    let mut row = CreateActionRow::default();
+
+    row.create_button(|b| {
+        b.style(ButtonStyle::Success)
+            .emoji(ReactionType::Unicode("🧰".to_string()))
+            .label("Still on it")
+            .custom_id(checkin_button_id(&CheckinAnswer::StillOn, worker_id))
+    })
+    .create_button(|b| {
+        b.style(ButtonStyle::Secondary)
+            .emoji(ReactionType::Unicode("🏳️".to_string()))
+            .label("Give up")
+            .custom_id(checkin_button_id(&CheckinAnswer::Done, worker_id))
+    });
+
+    row
+}
+
+pub async fn handle_checkin_button(ctx: &Context, component: &MessageComponentInteraction) {
+    if let Err(why) = answer_checkin(ctx, component).await {
+        eprintln!(
+            "Error when handling check in button '{}'... {}",
+            component.data.custom_id, why
+        );
+
+        if let Err(why) = respond_privately(
+            ctx,
+            component,
+            build_embed(|e| {
+                e.description(format!("Something went wrong... {}", why));
+            }),
+        )
+        .await
+        {
+            eprintln!("Error when responding to check in button... {}", why);
+        }
+    }
+}
+
+async fn answer_checkin(
+    ctx: &Context,
+    component: &MessageComponentInteraction,
+) -> Result<(), Error> {
+    let (answer, worker_id) = match parse_checkin_button_id(&component.data.custom_id) {
+        Some(parsed) => parsed,
+        None => return Err(format_err!("Unknown button!")),
+    };
+
+    let worker = get_worker_from_id(worker_id).await?;
+    if worker.discord_id != component.user.id.0 as i64 {
+        return Err(format_err!("That check in isn't for you!"));
+    }
+
+    let challenge = get_challenge_from_id(worker.challenge_id).await?;
+
+    let reply = match answer {
+        CheckinAnswer::StillOn => {
+            confirm_worker(worker.id).await?;
+            format!(
+                "Great, you're still down as working on '{}'",
+                challenge.name
+            )
+        }
+        CheckinAnswer::Done => {
+            remove_worker(worker.id).await?;
+            format!("Removed you from working on '{}'", challenge.name)
+        }
+    };
+
+    // Swap the buttons out so the check in can't be answered twice
+    component
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|message| {
+                    message
+                        .add_embed(build_embed(|e| {
+                            e.description(reply);
+                        }))
+                        .components(|c| c)
+                })
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkin_button_id() {
+        let custom_id = checkin_button_id(&CheckinAnswer::Done, 12);
+
+        assert_eq!(custom_id, "checkin:no:12");
+        assert_eq!(
+            parse_checkin_button_id(&custom_id),
+            Some((CheckinAnswer::Done, 12))
+        );
+        assert_eq!(parse_checkin_button_id("checkin:maybe:12"), None);
+        assert_eq!(parse_checkin_button_id("ctf:working:12"), None);
+    }
+}
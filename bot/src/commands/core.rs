@@ -0,0 +1,259 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use failure::Error;
+use serenity::{
+    builder::{CreateActionRow, CreateComponents, CreateEmbed, CreateMessage},
+    client::Context,
+    framework::standard::CommandResult,
+    http::{AttachmentType, Http},
+    model::{
+        channel::Message,
+        guild::Member,
+        id::{ChannelId, GuildId, UserId},
+        interactions::{
+            application_command::ApplicationCommandInteraction,
+            message_component::MessageComponentInteraction,
+        },
+        user::User,
+    },
+};
+
+/// Who ran a command and where, whether it came in as a prefix or a slash command.
+pub struct Invocation {
+    pub http: Arc<Http>,
+    pub user_id: UserId,
+    pub username: String,
+    pub channel_id: ChannelId,
+    pub guild_id: Option<GuildId>,
+}
+
+impl Invocation {
+    pub async fn from_message(ctx: &Context, msg: &Message) -> Self {
+        let username = msg
+            .author_nick(&ctx.http)
+            .await
+            .unwrap_or_else(|| msg.author.name.clone());
+
+        Invocation {
+            http: ctx.http.clone(),
+            user_id: msg.author.id,
+            username,
+            channel_id: msg.channel_id,
+            guild_id: msg.guild_id,
+        }
+    }
+
+    pub fn from_interaction(ctx: &Context, command: &ApplicationCommandInteraction) -> Self {
+        Self::from_parts(
+            ctx,
+            &command.user,
+            command.member.as_ref(),
+            command.channel_id,
+            command.guild_id,
+        )
+    }
+
+    pub fn from_component(ctx: &Context, component: &MessageComponentInteraction) -> Self {
+        Self::from_parts(
+            ctx,
+            &component.user,
+            component.member.as_ref(),
+            component.channel_id,
+            component.guild_id,
+        )
+    }
+
+    fn from_parts(
+        ctx: &Context,
+        user: &User,
+        member: Option<&Member>,
+        channel_id: ChannelId,
+        guild_id: Option<GuildId>,
+    ) -> Self {
+        let username = member
+            .and_then(|member| member.nick.clone())
+            .unwrap_or_else(|| user.name.clone());
+
+        Invocation {
+            http: ctx.http.clone(),
+            user_id: user.id,
+            username,
+            channel_id,
+            guild_id,
+        }
+    }
+}
+
+/// What a command sends back, rendered by either the prefix or the slash command frontend.
+#[derive(Default)]
+pub struct Reply {
+    pub content: Option<String>,
+    pub embeds: Vec<CreateEmbed>,
+    /// Rows of buttons for the embed at the same index, embeds with buttons are sent in their own
+    /// message.
+    pub components: Vec<Vec<CreateActionRow>>,
+    pub attachment: Option<(String, Vec<u8>)>,
+    /// Errors and usage hints, slash commands only show these to whoever ran the command.
+    pub ephemeral: bool,
+    /// Prefix commands DM these to whoever ran the command rather than posting them in the channel.
+    pub private: bool,
+}
+
+impl Reply {
+    pub fn text(content: impl Into<String>) -> Self {
+        Reply {
+            content: Some(content.into()),
+            ..Default::default()
+        }
+    }
+
+    pub fn error(content: impl Into<String>) -> Self {
+        Reply {
+            content: Some(content.into()),
+            ephemeral: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn embeds(embeds: Vec<CreateEmbed>) -> Self {
+        Reply {
+            embeds,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_content(mut self, content: impl Into<String>) -> Self {
+        self.content = Some(content.into());
+        self
+    }
+
+    pub fn with_components(mut self, components: Vec<Vec<CreateActionRow>>) -> Self {
+        self.components = components;
+        self
+    }
+
+    pub fn with_attachment(mut self, filename: &str, data: Vec<u8>) -> Self {
+        self.attachment = Some((filename.to_string(), data));
+        self
+    }
+
+    pub fn private(mut self) -> Self {
+        self.private = true;
+        self
+    }
+}
+
+/// Builds an embed on its own, for commands that hand them back in a ``Reply``.
+pub fn build_embed(f: impl FnOnce(&mut CreateEmbed)) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    f(&mut embed);
+    embed
+}
+
+pub fn add_action_rows(
+    components: &mut CreateComponents,
+    rows: Vec<CreateActionRow>,
+) -> &mut CreateComponents {
+    for row in rows {
+        components.add_action_row(row);
+    }
+
+    components
+}
+
+/// Checks the member has a role with the given name, interactions don't go through the framework's
+/// ``allowed_roles`` check.
+pub async fn has_role(ctx: &Context, member: Option<&Member>, role_name: &str) -> bool {
+    match member {
+        Some(member) => member
+            .roles(&ctx.cache)
+            .await
+            .map(|roles| roles.iter().any(|role| role.name == role_name))
+            .unwrap_or(false),
+        None => false,
+    }
+}
+
+/// Turns a failed command into a reply, so both frontends report errors the same way.
+pub fn reply_or_error(result: Result<Reply, Error>) -> Reply {
+    match result {
+        Ok(reply) => reply,
+        Err(why) => {
+            eprintln!("Error when running command... {}", why);
+            Reply::error(format!("Something went wrong... {}", why))
+        }
+    }
+}
+
+/// Sends a command's reply in response to a prefix command.
+pub async fn send_reply(
+    ctx: &Context,
+    msg: &Message,
+    result: Result<Reply, Error>,
+) -> CommandResult {
+    let Reply {
+        content,
+        embeds,
+        components,
+        attachment,
+        private,
+        ..
+    } = reply_or_error(result);
+
+    if let Some((filename, data)) = attachment {
+        msg.channel_id
+            .send_message(&ctx.http, |m| {
+                m.add_file(AttachmentType::Bytes {
+                    data: Cow::from(data),
+                    filename,
+                });
+                if let Some(content) = &content {
+                    m.content(content);
+                }
+                if let Some(embed) = embeds.into_iter().next() {
+                    m.set_embed(embed);
+                }
+                m
+            })
+            .await?;
+
+        return Ok(());
+    }
+
+    if let Some(content) = content {
+        msg.reply(&ctx.http, content).await?;
+    }
+
+    let mut components = components.into_iter();
+
+    for embed in embeds {
+        let rows = components.next().unwrap_or_default();
+
+        if private {
+            msg.author
+                .dm(&ctx.http, |m| embed_message(m, embed, rows))
+                .await?;
+        } else {
+            msg.channel_id
+                .send_message(&ctx.http, |m| embed_message(m, embed, rows))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn embed_message<'a, 'b>(
+    message: &'b mut CreateMessage<'a>,
+    embed: CreateEmbed,
+    rows: Vec<CreateActionRow>,
+) -> &'b mut CreateMessage<'a> {
+    message.set_embed(embed);
+
+    if !rows.is_empty() {
+        message.components(|c| add_action_rows(c, rows));
+    }
+
+    message
+}
@@ -1,16 +1,42 @@
+use std::collections::HashMap;
+
+use std::env;
+use std::path::PathBuf;
+
+use chrono::NaiveDateTime;
+use failure::Error;
 use serenity::client::Context;
 use serenity::framework::standard::{macros::*, Args, CommandResult};
 use serenity::model::channel::Message;
+use serenity::model::id::{ChannelId, GuildId, RoleId};
 
+use ctfdb::announcements::{
+    add_announcement_route, get_announcement_routes, remove_announcement_route,
+    remove_announcement_template, set_announcement_template, RouteSource, TemplateKind,
+};
 use ctfdb::ctfs::db::{
-    add_active_ctf, add_working, get_active_ctfs, get_challenges_for_ctfname,
-    get_latest_scoreboard_status, remove_active_ctf, remove_working, search_for_challenge_by_name,
+    add_active_ctf, add_working_by_id, find_active_challenges_by_name,
+    find_challenges_by_name_in_any_ctf, get_active_ctfs, get_ctf_from_id, get_ctf_id_from_name,
+    get_latest_scoreboard_status, remove_active_ctf, remove_working_by_id,
+    search_for_challenge_by_name, set_ctf_end_time, set_ctf_stale_minutes,
 };
+use ctfdb::models::{Challenge, ChallengeNote, Ctf};
+use ctfdb::notes::{add_challenge_note, get_challenge_notes};
+use ctfdb::writeups::{export_ctf_writeups, set_challenge_writeup, WriteupBody, MAX_WRITEUP_BYTES};
 
+use crate::commands::board::show_board;
+use crate::commands::buttons::challenge_buttons;
+use crate::commands::core::{build_embed, send_reply, Invocation, Reply};
+use crate::discord_name_provider::ctf_working;
 use crate::populate_embed_from_challenge;
+use crate::teams::{select_team, HTBTeam, HTBTeamsContainer};
+use crate::templates::{check_template, render_announcement, TemplateValues, PLACEHOLDERS};
 
 #[group]
-#[commands(active, working, giveup, start, end, list, search, stats)]
+#[commands(
+    active, working, giveup, start, end, endtime, stale, list, board, search, note, notes, writeup,
+    export, stats, template, route
+)]
 #[prefixes("ctf", "c")]
 pub struct CTFer;
 
@@ -20,48 +46,47 @@ pub struct CTFer;
 #[example("\"CTF name\" <ctf url> <api key> <channel id to post updates to>")]
 #[description = "Starts a CTF, and will begin polling for challenge status changes"]
 async fn start(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    if args.len() >= 3 {
+    let reply = if args.len() >= 3 {
         let name = args.single_quoted::<String>()?;
         let base_url = args.single::<String>()?;
         let api_key = args.single::<String>()?;
         let channel_id = args.single::<i64>().unwrap_or(0);
 
-        let api_url = match base_url.ends_with('/') {
-            true => {
-                format!("{}api/v1", base_url)
-            }
-            false => {
-                format!("{}/api/v1", base_url)
-            }
-        };
-
-        match add_active_ctf(&name, &base_url, &api_url, &api_key, channel_id).await {
-            Ok(_) => {
-                let started_ctf_msg = format!("Started CTF '{}'", name);
-                msg.reply(&ctx.http, started_ctf_msg).await?;
-            }
-            Err(why) => {
-                msg.channel_id
-                    .send_message(&ctx.http, |m| {
-                        m.content(&format!(
-                            "Error occurred when adding new active CTF... try again?: {}",
-                            why
-                        ));
-                        m
-                    })
-                    .await?;
-                eprintln!("Error occurred when adding new active ctf: {}", why);
-            }
-        }
+        start_ctf(&name, &base_url, &api_key, channel_id).await
     } else {
-        msg.reply(
-            &ctx.http,
+        Ok(Reply::error(
             "Usage: ``!ctf start \"CTF Name\" <ctf url> <ctf api key> <id of channel to post updates in>``",
-        )
-            .await?;
-    }
+        ))
+    };
 
-    Ok(())
+    send_reply(ctx, msg, reply).await
+}
+
+pub async fn start_ctf(
+    name: &str,
+    base_url: &str,
+    api_key: &str,
+    channel_id: i64,
+) -> Result<Reply, Error> {
+    let api_url = match base_url.ends_with('/') {
+        true => {
+            format!("{}api/v1", base_url)
+        }
+        false => {
+            format!("{}/api/v1", base_url)
+        }
+    };
+
+    match add_active_ctf(name, base_url, &api_url, api_key, channel_id).await {
+        Ok(_) => Ok(Reply::text(format!("Started CTF '{}'", name))),
+        Err(why) => {
+            eprintln!("Error occurred when adding new active ctf: {}", why);
+            Ok(Reply::error(format!(
+                "Error occurred when adding new active CTF... try again?: {}",
+                why
+            )))
+        }
+    }
 }
 
 #[command]
@@ -69,47 +94,161 @@ async fn start(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
 #[example("\"CTF name\"")]
 #[description = "Ends a CTF with the provided name"]
 async fn end(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    if args.len() == 1 {
+    let reply = if args.len() == 1 {
         let name = args.single_quoted::<String>()?;
+        end_ctf(&name).await
+    } else {
+        Ok(Reply::error("Usage: ``!ctf end \"CTF name\"``"))
+    };
 
-        match remove_active_ctf(&name).await {
-            Ok(_) => {
-                msg.reply(&ctx.http, &format!("CTF ended '{}'", name))
-                    .await?;
-                // TODO show results
-            }
-            Err(why) => {
-                eprintln!("Error occurred when ending active ctf: {}", why);
-            }
+    send_reply(ctx, msg, reply).await
+}
+
+pub async fn end_ctf(name: &str) -> Result<Reply, Error> {
+    match remove_active_ctf(name).await {
+        // TODO show results
+        Ok(_) => Ok(Reply::text(format!("CTF ended '{}'", name))),
+        Err(why) => {
+            eprintln!("Error occurred when ending active ctf: {}", why);
+            Ok(Reply::error(format!(
+                "Unable to end CTF '{}'... {}",
+                name, why
+            )))
         }
+    }
+}
+
+#[command]
+#[allowed_roles("Organiser")]
+#[example("\"CTF name\" \"2026-10-20 18:00\"")]
+#[description = "Sets when a CTF ends (in the bot's local time), shown on its status board"]
+async fn endtime(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let reply = if args.len() == 2 {
+        let name = args.single_quoted::<String>()?;
+        let end_time = args.single_quoted::<String>()?;
+        set_end_time(&name, &end_time).await
+    } else {
+        Ok(Reply::error(
+            "Usage: ``!ctf endtime \"CTF name\" \"YYYY-MM-DD HH:MM\"``",
+        ))
+    };
+
+    send_reply(ctx, msg, reply).await
+}
+
+#[command]
+#[allowed_roles("Organiser")]
+#[example("\"CTF name\" 120")]
+#[example("\"CTF name\" off")]
+#[description = "Sets how many minutes someone can work on a challenge before they're asked if they're still on it, and taken off it if they don't answer"]
+async fn stale(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let reply = if args.len() == 2 {
+        let name = args.single_quoted::<String>()?;
+        let minutes = args.single::<String>()?;
+        set_stale_minutes(&name, &minutes).await
     } else {
-        msg.reply(&ctx.http, "Usage: ``!ctf end \"CTF name\"``")
-            .await?;
+        Ok(Reply::error(
+            "Usage: ``!ctf stale \"CTF name\" <minutes/off>``",
+        ))
+    };
+
+    send_reply(ctx, msg, reply).await
+}
+
+/// Reads a staleness window, where ``off`` (or 0) turns check ins off.
+pub fn parse_stale_minutes(minutes: &str) -> Option<Option<i32>> {
+    match minutes.trim().to_lowercase().as_str() {
+        "off" | "0" => Some(None),
+        minutes => minutes
+            .parse::<i32>()
+            .ok()
+            .filter(|minutes| *minutes > 0)
+            .map(Some),
     }
+}
 
-    Ok(())
+pub async fn set_stale_minutes(name: &str, minutes: &str) -> Result<Reply, Error> {
+    let stale_minutes = match parse_stale_minutes(minutes) {
+        Some(stale_minutes) => stale_minutes,
+        None => {
+            return Ok(Reply::error(
+                "That should be a number of minutes, or ``off``",
+            ))
+        }
+    };
+
+    if let Err(why) = set_ctf_stale_minutes(name, stale_minutes).await {
+        return Ok(Reply::error(format!(
+            "Unable to set check ins for '{}'... {}",
+            name, why
+        )));
+    }
+
+    Ok(Reply::text(match stale_minutes {
+        Some(minutes) => format!(
+            "People working on a challenge in '{}' for {} minutes will be asked if they're still on it",
+            name, minutes
+        ),
+        None => format!("Turned check ins off for '{}'", name),
+    }))
+}
+
+pub fn parse_end_time(end_time: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(end_time.trim(), "%Y-%m-%d %H:%M").ok()
+}
+
+pub async fn set_end_time(name: &str, end_time: &str) -> Result<Reply, Error> {
+    let end_time = match parse_end_time(end_time) {
+        Some(end_time) => end_time,
+        None => {
+            return Ok(Reply::error(
+                "Unable to read that end time, it should look like ``2026-10-20 18:00``",
+            ))
+        }
+    };
+
+    match set_ctf_end_time(name, end_time).await {
+        Ok(_) => Ok(Reply::text(format!(
+            "'{}' ends at {}",
+            name,
+            end_time.format("%a %b %e %H:%M")
+        ))),
+        Err(why) => {
+            eprintln!("Error occurred when setting ctf end time: {}", why);
+            Ok(Reply::error(format!(
+                "Unable to set the end time for '{}'... {}",
+                name, why
+            )))
+        }
+    }
 }
 
 #[command]
 #[allowed_roles("CTFer")]
 #[description = "Shows all active CTFs"]
 async fn active(ctx: &Context, msg: &Message, mut _args: Args) -> CommandResult {
+    send_reply(ctx, msg, list_active_ctfs().await).await
+}
+
+pub async fn list_active_ctfs() -> Result<Reply, Error> {
     match get_active_ctfs().await {
-        Ok(active_ctfs) => {
-            for ctf in active_ctfs {
-                msg.channel_id
-                    .send_message(&ctx.http, |message| message.embed(|e| e.title(ctf.name)))
-                    .await?;
-            }
-        }
+        Ok(active_ctfs) if active_ctfs.is_empty() => Ok(Reply::text("No CTFs are active!")),
+        Ok(active_ctfs) => Ok(Reply::embeds(
+            active_ctfs
+                .into_iter()
+                .map(|ctf| {
+                    build_embed(|e| {
+                        e.title(ctf.name);
+                    })
+                })
+                .collect(),
+        )),
         Err(why) => {
             let err_str = format!("Error occurred when listing active CTFs! {}", why);
-            msg.reply(&ctx.http, &err_str).await?;
             eprintln!("{}", err_str);
+            Ok(Reply::error(err_str))
         }
     }
-
-    Ok(())
 }
 
 #[command]
@@ -118,43 +257,57 @@ async fn active(ctx: &Context, msg: &Message, mut _args: Args) -> CommandResult
 #[example("\"Challenge name\"")]
 #[description = "Marks you as working on the provided challenge"]
 async fn working(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    if args.len() == 1 {
+    let reply = if args.len() == 1 {
         let challenge_name = args.single_quoted::<String>()?;
-
-        let username = msg
-            .author_nick(&ctx.http)
-            .await
-            .unwrap_or_else(|| msg.author.name.clone());
-
-        match add_working(username, &challenge_name).await {
-            Ok(_) => {
-                msg.reply(
-                    &ctx.http,
-                    &format!("Marked you as working on '{}'", &challenge_name),
-                )
-                .await?;
-            }
-            Err(why) => {
-                msg.reply(
-                    &ctx.http,
-                    format!(
-                        "Error when adding to working for '{}'... {}",
-                        &challenge_name, why
-                    ),
-                )
-                .await?;
-                eprintln!(
-                    "Error on adding to working for '{}' ... '{}'",
-                    &msg.author.name, why
-                );
-            }
-        }
+        mark_working(&Invocation::from_message(ctx, msg).await, &challenge_name).await
     } else {
-        msg.reply(&ctx.http, "Usage: ``!ctf working \"Challenge name\"``")
-            .await?;
+        Ok(Reply::error("Usage: ``!ctf working \"Challenge name\"``"))
+    };
+
+    send_reply(ctx, msg, reply).await
+}
+
+/// Warns anyone trying to work on a challenge that's already been solved.
+pub fn already_solved(challenge: &Challenge) -> String {
+    format!(
+        "'{}' has already been solved by {}, pick something else to work on!",
+        challenge.name,
+        challenge.solver.as_deref().unwrap_or("someone")
+    )
+}
+
+pub async fn mark_working(invocation: &Invocation, challenge_name: &str) -> Result<Reply, Error> {
+    let challenge = match running_challenge(invocation, challenge_name).await? {
+        Ok(challenge) => challenge,
+        Err(reply) => return Ok(reply),
+    };
+
+    if challenge.solved {
+        return Ok(Reply::error(already_solved(&challenge)));
     }
 
-    Ok(())
+    match add_working_by_id(
+        invocation.user_id.0 as i64,
+        &invocation.username,
+        challenge.id,
+    )
+    .await
+    {
+        Ok(_) => Ok(Reply::text(format!(
+            "Marked you as working on '{}'",
+            challenge.name
+        ))),
+        Err(why) => {
+            eprintln!(
+                "Error on adding to working for '{}' ... '{}'",
+                invocation.username, why
+            );
+            Ok(Reply::error(format!(
+                "Error when adding to working for '{}'... {}",
+                challenge.name, why
+            )))
+        }
+    }
 }
 
 // For lack of a better name...
@@ -164,55 +317,75 @@ async fn working(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult
 #[example("\"Challenge name\"")]
 #[description = "Removes you from working on the given challenge"]
 async fn giveup(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    if args.len() == 1 {
+    let reply = if args.len() == 1 {
         let challenge_name = args.single_quoted::<String>()?;
-        let username = msg
-            .author_nick(&ctx.http)
-            .await
-            .unwrap_or_else(|| msg.author.name.clone());
+        give_up(&Invocation::from_message(ctx, msg).await, &challenge_name).await
+    } else {
+        Ok(Reply::error("Usage: ``!ctf giveup \"Challenge name\"``"))
+    };
 
-        remove_working(username, &challenge_name).await?;
+    send_reply(ctx, msg, reply).await
+}
 
-        msg.reply(
-            &ctx.http,
-            &format!("Removed you from working on '{}'", &challenge_name),
-        )
-        .await?;
-    } else {
-        msg.reply(&ctx.http, "Usage: ``!ctf giveup \"Challenge name\"``")
-            .await?;
-    }
+pub async fn give_up(invocation: &Invocation, challenge_name: &str) -> Result<Reply, Error> {
+    let challenge = match running_challenge(invocation, challenge_name).await? {
+        Ok(challenge) => challenge,
+        Err(reply) => return Ok(reply),
+    };
 
-    Ok(())
+    remove_working_by_id(invocation.user_id.0 as i64, &challenge).await?;
+
+    Ok(Reply::text(format!(
+        "Removed you from working on '{}'",
+        challenge.name
+    )))
 }
 
 #[command]
 #[allowed_roles("CTFer")]
 #[aliases("l")]
 #[example("\"CTF Name\"")]
-#[description = "Lists all challenges for given CTF, or defaults to checking for the active CTF in the current channel"]
+#[description = "DMs you a board of all challenges for given CTF, or defaults to checking for the active CTF in the current channel"]
 async fn list(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    if args.len() == 1 {
-        let ctf_name = args.single_quoted::<String>()?;
-        for challenge in get_challenges_for_ctfname(ctf_name).await? {
-            msg.author
-                .dm(&ctx.http, |m| {
-                    m.embed(|e| {
-                        populate_embed_from_challenge(challenge, e);
-                        e
-                    })
-                })
-                .await?;
+    let reply = match args.len() {
+        0 => list_challenges(&Invocation::from_message(ctx, msg).await, None).await,
+        1 => {
+            let ctf_name = args.single_quoted::<String>()?;
+            list_challenges(&Invocation::from_message(ctx, msg).await, Some(&ctf_name)).await
         }
-    } else {
-        msg.reply(
-            &ctx.http,
+        _ => Ok(Reply::error(
             "Usage: ``!ctf list \"CTF name\"`` or use ``!list`` in the correct channel",
-        )
-        .await?;
-    }
+        )),
+    };
+
+    send_reply(ctx, msg, reply).await
+}
 
-    Ok(())
+pub async fn list_challenges(
+    invocation: &Invocation,
+    ctf_name: Option<&str>,
+) -> Result<Reply, Error> {
+    Ok(show_board(invocation, ctf_name).await?.private())
+}
+
+#[command]
+#[allowed_roles("CTFer")]
+#[aliases("b")]
+#[example("\"CTF Name\"")]
+#[description = "Posts a board of all challenges for given CTF in the channel, or defaults to the active CTF in the current channel"]
+async fn board(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let reply = match args.len() {
+        0 => show_board(&Invocation::from_message(ctx, msg).await, None).await,
+        1 => {
+            let ctf_name = args.single_quoted::<String>()?;
+            show_board(&Invocation::from_message(ctx, msg).await, Some(&ctf_name)).await
+        }
+        _ => Ok(Reply::error(
+            "Usage: ``!ctf board \"CTF name\"`` or use ``!ctf board`` in the correct channel",
+        )),
+    };
+
+    send_reply(ctx, msg, reply).await
 }
 
 #[command]
@@ -220,56 +393,860 @@ async fn list(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
 #[example("\"Challenge name\"")]
 #[description = "Searches for the status of the given challenge"]
 async fn search(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    if args.len() == 1 {
+    let reply = if args.len() == 1 {
         let challenge_name = args.single_quoted::<String>()?;
+        search_challenge(&Invocation::from_message(ctx, msg).await, &challenge_name).await
+    } else {
+        Ok(Reply::error("Usage: ``!ctf search \"Challenge name\"``"))
+    };
+
+    send_reply(ctx, msg, reply).await
+}
 
-        let challenges = search_for_challenge_by_name(&challenge_name).await?;
+pub async fn search_challenge(
+    invocation: &Invocation,
+    challenge_name: &str,
+) -> Result<Reply, Error> {
+    let challenges = search_for_challenge_by_name(challenge_name).await?;
 
-        if !challenges.is_empty() {
-            for challenge in challenges {
-                msg.channel_id
-                    .send_message(&ctx.http, |m| {
-                        m.embed(|e| {
-                            populate_embed_from_challenge(challenge, e);
-                            e
-                        })
-                    })
-                    .await?;
+    if challenges.is_empty() {
+        return Ok(Reply::error("No challenge found by that name!"));
+    }
+
+    let challenge_ids: Vec<i32> = challenges.iter().map(|challenge| challenge.id).collect();
+    let working = ctf_working(&invocation.http, invocation.guild_id, &challenge_ids).await?;
+
+    Ok(challenge_reply(challenges, &working))
+}
+
+/// Embeds each challenge with buttons to claim it, give up on it or see its details.
+fn challenge_reply(challenges: Vec<Challenge>, working: &HashMap<i32, String>) -> Reply {
+    let components = challenges
+        .iter()
+        .map(|challenge| vec![challenge_buttons(challenge.id)])
+        .collect();
+
+    Reply::embeds(
+        challenges
+            .into_iter()
+            .map(|challenge| {
+                let working = working.get(&challenge.id).map(String::as_str);
+                build_embed(|e| populate_embed_from_challenge(challenge, working, e))
+            })
+            .collect(),
+    )
+    .with_components(components)
+}
+
+#[command]
+#[allowed_roles("CTFer")]
+#[example("\"Challenge name\" The key is in the EXIF data")]
+#[description = "Adds a note to the given challenge for the rest of the team"]
+async fn note(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let reply = if args.len() >= 2 {
+        let challenge_name = args.single_quoted::<String>()?;
+        add_note(
+            &Invocation::from_message(ctx, msg).await,
+            &challenge_name,
+            args.rest(),
+        )
+        .await
+    } else {
+        Ok(Reply::error(
+            "Usage: ``!ctf note \"Challenge name\" <note>``",
+        ))
+    };
+
+    send_reply(ctx, msg, reply).await
+}
+
+/// Finds the challenge a command is about in the running CTFs. When more than one has a challenge
+/// by that name, the CTF whose channel or thread the command was used in is the one meant.
+async fn running_challenge(
+    invocation: &Invocation,
+    challenge_name: &str,
+) -> Result<Result<Challenge, Reply>, Error> {
+    let mut matches = find_active_challenges_by_name(challenge_name).await?;
+    let channel_id = invocation.channel_id.0 as i64;
+
+    if matches.len() > 1 {
+        matches.retain(|(challenge, ctf)| {
+            ctf.channel_id == channel_id || challenge.thread_id == Some(channel_id)
+        });
+    }
+
+    match matches.len() {
+        0 => Ok(Err(Reply::error(
+            "No challenge found by that name in a running CTF!",
+        ))),
+        1 => Ok(Ok(matches.remove(0).0)),
+        _ => Ok(Err(Reply::error(format!(
+            "'{}' is in more than one running CTF, use this in the channel for the one you mean",
+            challenge_name
+        )))),
+    }
+}
+
+pub async fn add_note(
+    invocation: &Invocation,
+    challenge_name: &str,
+    content: &str,
+) -> Result<Reply, Error> {
+    let challenge = match running_challenge(invocation, challenge_name).await? {
+        Ok(challenge) => challenge,
+        Err(reply) => return Ok(reply),
+    };
+
+    if let Err(why) = add_challenge_note(
+        challenge.id,
+        Some(invocation.user_id.0 as i64),
+        &invocation.username,
+        content,
+    )
+    .await
+    {
+        return Ok(Reply::error(why.to_string()));
+    }
+
+    Ok(Reply::text(format!(
+        "Added your note to '{}', see them all with ``!ctf notes \"{}\"``",
+        challenge.name, challenge.name
+    )))
+}
+
+#[command]
+#[allowed_roles("CTFer")]
+#[example("\"Challenge name\"")]
+#[description = "Lists the notes on the given challenge"]
+async fn notes(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let reply = if args.len() == 1 {
+        let challenge_name = args.single_quoted::<String>()?;
+        list_notes(&Invocation::from_message(ctx, msg).await, &challenge_name).await
+    } else {
+        Ok(Reply::error("Usage: ``!ctf notes \"Challenge name\"``"))
+    };
+
+    send_reply(ctx, msg, reply).await
+}
+
+/// Discord won't take an embed description longer than this.
+const MAX_DESCRIPTION_LENGTH: usize = 4096;
+
+/// Lists notes oldest first, dropping the oldest ones if they don't all fit.
+fn format_notes(notes: &[ChallengeNote]) -> String {
+    let lines: Vec<String> = notes
+        .iter()
+        .map(|note| {
+            let author = match note.discord_id {
+                Some(discord_id) => format!("<@{}>", discord_id),
+                None => format!("**{}**", note.author),
+            };
+
+            format!(
+                "``{}`` {}: {}",
+                note.created_at.format("%e %b %H:%M"),
+                author,
+                note.content
+            )
+        })
+        .collect();
+
+    let mut shown = lines.len();
+    loop {
+        let hidden = lines.len() - shown;
+        let mut description = lines[hidden..].join("\n");
+
+        if hidden > 0 {
+            description = format!("...and {} older\n{}", hidden, description);
+        }
+
+        if shown == 0 || description.chars().count() <= MAX_DESCRIPTION_LENGTH {
+            return description;
+        }
+
+        shown -= 1;
+    }
+}
+
+pub async fn list_notes(invocation: &Invocation, challenge_name: &str) -> Result<Reply, Error> {
+    let challenge = match running_challenge(invocation, challenge_name).await? {
+        Ok(challenge) => challenge,
+        Err(reply) => return Ok(reply),
+    };
+
+    let notes = get_challenge_notes(challenge.id).await?;
+    if notes.is_empty() {
+        return Ok(Reply::text(format!(
+            "No notes on '{}' yet, add one with ``!ctf note \"{}\" <note>``",
+            challenge.name, challenge.name
+        )));
+    }
+
+    Ok(Reply::embeds(vec![build_embed(|e| {
+        e.title(format!("📝 Notes on {}", challenge.name));
+        e.description(format_notes(&notes));
+    })]))
+}
+
+#[command]
+#[allowed_roles("CTFer")]
+#[example("\"Challenge name\" https://blog.example/writeup")]
+#[example("\"Challenge name\" https://blog.example/writeup \"CTF name\"")]
+#[description = "Stores your writeup for the given challenge, a link or an attached markdown file. Name the CTF if the challenge's name isn't unique"]
+async fn writeup(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+
+    let reply = match (args.len(), msg.attachments.first()) {
+        (1, Some(attachment)) | (2, Some(attachment)) => {
+            let challenge_name = args.single_quoted::<String>()?;
+            let ctf_name = args.single_quoted::<String>().ok();
+
+            if !attachment.filename.to_lowercase().ends_with(".md") {
+                Ok(Reply::error("Attached writeups need to be a ``.md`` file!"))
+            } else if attachment.size as usize > MAX_WRITEUP_BYTES {
+                Ok(Reply::error(format!(
+                    "Writeups can't be bigger than {}KB!",
+                    MAX_WRITEUP_BYTES / 1024
+                )))
+            } else {
+                let body = WriteupBody::markdown(attachment.download().await?);
+                submit_writeup(&invocation, &challenge_name, body, ctf_name.as_deref()).await
             }
-        } else {
-            msg.reply(&ctx.http, "No challenge found by that name!")
-                .await?;
         }
-    } else {
-        msg.reply(&ctx.http, "Usage: ``!ctf search \"Challenge name\"``")
-            .await?;
+        (2, None) | (3, None) => {
+            let challenge_name = args.single_quoted::<String>()?;
+            let body = WriteupBody::url(&args.single::<String>()?);
+            let ctf_name = args.single_quoted::<String>().ok();
+            submit_writeup(&invocation, &challenge_name, body, ctf_name.as_deref()).await
+        }
+        _ => Ok(Reply::error(
+            "Usage: ``!ctf writeup \"Challenge name\" <link> \"CTF name\"`` or attach a ``.md`` file with ``!ctf writeup \"Challenge name\" \"CTF name\"``, the CTF name is optional",
+        )),
+    };
+
+    send_reply(ctx, msg, reply).await
+}
+
+/// Picks the challenge a writeup is for. Writeups often come in after the CTF has ended, so
+/// ended CTFs count too. A named CTF settles it, otherwise the CTF for the channel it was sent in,
+/// then whichever is still running.
+fn pick_writeup_challenge(
+    mut matches: Vec<(Challenge, Ctf)>,
+    channel_id: i64,
+    ctf_name: Option<&str>,
+) -> Result<Challenge, Reply> {
+    if let Some(ctf_name) = ctf_name {
+        matches.retain(|(_, ctf)| ctf.name.eq_ignore_ascii_case(ctf_name));
     }
 
-    Ok(())
+    let in_channel = |(challenge, ctf): &(Challenge, Ctf)| {
+        ctf.channel_id == channel_id || challenge.thread_id == Some(channel_id)
+    };
+    if matches.len() > 1 && matches.iter().any(in_channel) {
+        matches.retain(in_channel);
+    }
+
+    if matches.len() > 1 && matches.iter().any(|(_, ctf)| ctf.active) {
+        matches.retain(|(_, ctf)| ctf.active);
+    }
+
+    match matches.len() {
+        0 => Err(Reply::error("No challenge found by that name!")),
+        1 => Ok(matches.remove(0).0),
+        _ => Err(Reply::error(format!(
+            "'{}' is in more than one CTF, add the CTF's name to say which",
+            matches[0].0.name
+        ))),
+    }
+}
+
+pub async fn submit_writeup(
+    invocation: &Invocation,
+    challenge_name: &str,
+    body: Result<WriteupBody, Error>,
+    ctf_name: Option<&str>,
+) -> Result<Reply, Error> {
+    let body = match body {
+        Ok(body) => body,
+        Err(why) => return Ok(Reply::error(why.to_string())),
+    };
+
+    let challenge = match pick_writeup_challenge(
+        find_challenges_by_name_in_any_ctf(challenge_name).await?,
+        invocation.channel_id.0 as i64,
+        ctf_name,
+    ) {
+        Ok(challenge) => challenge,
+        Err(reply) => return Ok(reply),
+    };
+
+    set_challenge_writeup(
+        challenge.id,
+        invocation.user_id.0 as i64,
+        &invocation.username,
+        &body,
+    )
+    .await?;
+
+    Ok(Reply::text(format!(
+        "Thanks! Stored your writeup for '{}'",
+        challenge.name
+    )))
+}
+
+#[command]
+#[allowed_roles("Organiser")]
+#[example("\"CTF name\"")]
+#[description = "Exports the CTF's challenges, notes and writeups as a folder per challenge"]
+async fn export(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let reply = if args.len() == 1 {
+        let ctf_name = args.single_quoted::<String>()?;
+        export_writeups(&ctf_name).await
+    } else {
+        Ok(Reply::error("Usage: ``!ctf export \"CTF name\"``"))
+    };
+
+    send_reply(ctx, msg, reply).await
+}
+
+pub async fn export_writeups(ctf_name: &str) -> Result<Reply, Error> {
+    let ctf = match get_ctf_id_from_name(ctf_name).await {
+        Some(ctf_id) => get_ctf_from_id(ctf_id).await?,
+        None => return Ok(Reply::error("No CTF exists for that name!")),
+    };
+
+    let root = env::var("WRITEUPS_DIR")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .unwrap_or_else(|| "writeups".to_string());
+    let folder = export_ctf_writeups(&ctf, &PathBuf::from(root)).await?;
+
+    Ok(Reply::text(format!(
+        "Exported '{}' to ``{}``, replacing any earlier export of it, ready to commit to the writeups repository",
+        ctf.name,
+        folder.display()
+    )))
 }
 
 #[command]
 #[allowed_roles("CTFer")]
 #[description = "Displays the stats for all active ctfs"]
 async fn stats(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
-    let active_ctfs = get_active_ctfs().await?;
+    send_reply(ctx, msg, show_stats().await).await
+}
+
+pub async fn show_stats() -> Result<Reply, Error> {
+    let mut embeds = vec![];
 
-    for ctf in active_ctfs {
+    for ctf in get_active_ctfs().await? {
         let stats = get_latest_scoreboard_status(ctf.id).await?;
 
-        msg.channel_id
-            .send_message(&ctx.http, |message| {
-                message.embed(|e| {
-                    e.title(ctf.name);
-                    e.description(format!(
-                        "📈 Team position: {}, Total score: {}",
-                        stats.position, stats.points
-                    ));
-                    e
+        embeds.push(build_embed(|e| {
+            e.title(ctf.name);
+            e.description(format!(
+                "📈 Team position: {}, Total score: {}",
+                stats.position, stats.points
+            ));
+        }));
+    }
+
+    Ok(Reply::embeds(embeds))
+}
+
+#[command]
+#[allowed_roles("Organiser")]
+#[example("set ctf_solve \"{flag} {solver} popped {challenge}\" \"Worth {points} points\"")]
+#[example("set htb_root \"{flag} {solver} rooted {challenge}\" none")]
+#[example("reset ctf_solve \"CTF name\"")]
+#[example("preview htb_user")]
+#[description = "Sets, resets or previews the template for solve announcements, for the server or a single CTF"]
+async fn template(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let action = args.single::<String>().unwrap_or_default();
+    let kind = args.single::<String>().unwrap_or_default();
+
+    let reply = match (action.as_str(), args.remaining()) {
+        ("set", 2) | ("set", 3) => {
+            let title = args.single_quoted::<String>()?;
+            let description = args.single_quoted::<String>()?;
+            let ctf_name = args.single_quoted::<String>().ok();
+            set_template(
+                &invocation,
+                &kind,
+                &title,
+                &description,
+                ctf_name.as_deref(),
+            )
+            .await
+        }
+        ("reset", 0) | ("reset", 1) => {
+            let ctf_name = args.single_quoted::<String>().ok();
+            reset_template(&invocation, &kind, ctf_name.as_deref()).await
+        }
+        ("preview", 0) | ("preview", 1) => {
+            let ctf_name = args.single_quoted::<String>().ok();
+            preview_template(&invocation, &kind, ctf_name.as_deref()).await
+        }
+        _ => Ok(template_usage()),
+    };
+
+    send_reply(ctx, msg, reply).await
+}
+
+fn template_usage() -> Reply {
+    Reply::error(format!(
+        "Usage: ``!ctf template set <kind> \"Title\" \"Description or none\" \"CTF name\"``, ``!ctf template reset <kind> \"CTF name\"`` or ``!ctf template preview <kind> \"CTF name\"``, the CTF name is optional. Kinds are {}",
+        TemplateKind::KINDS
+            .iter()
+            .map(|kind| format!("``{}``", kind.as_str()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))
+}
+
+/// Works out which guild, and CTF if one was named, a server setting is for.
+async fn guild_and_ctf(
+    invocation: &Invocation,
+    ctf_name: Option<&str>,
+) -> Result<(i64, Option<i32>), Reply> {
+    let guild_id = match invocation.guild_id {
+        Some(guild_id) => guild_id.0 as i64,
+        None => return Err(Reply::error("This can only be used in a server!")),
+    };
+
+    let ctf_id = match ctf_name {
+        Some(ctf_name) => match get_ctf_id_from_name(ctf_name).await {
+            Some(ctf_id) => Some(ctf_id),
+            None => return Err(Reply::error("No CTF exists for that name!")),
+        },
+        None => None,
+    };
+
+    Ok((guild_id, ctf_id))
+}
+
+/// Works out which guild, CTF and kind of announcement a template command is for.
+async fn template_target(
+    invocation: &Invocation,
+    kind: &str,
+    ctf_name: Option<&str>,
+) -> Result<(i64, Option<i32>, TemplateKind), Reply> {
+    let kind = match TemplateKind::parse(kind) {
+        Some(kind) => kind,
+        None => return Err(template_usage()),
+    };
+
+    let (guild_id, ctf_id) = guild_and_ctf(invocation, ctf_name).await?;
+
+    Ok((guild_id, ctf_id, kind))
+}
+
+pub async fn set_template(
+    invocation: &Invocation,
+    kind: &str,
+    title: &str,
+    description: &str,
+    ctf_name: Option<&str>,
+) -> Result<Reply, Error> {
+    let (guild_id, ctf_id, kind) = match template_target(invocation, kind, ctf_name).await {
+        Ok(target) => target,
+        Err(reply) => return Ok(reply),
+    };
+
+    let description = match description.trim() {
+        "" | "none" => None,
+        description => Some(description),
+    };
+
+    if let Err(why) = check_template(title, description) {
+        return Ok(Reply::error(why));
+    }
+
+    set_announcement_template(guild_id, ctf_id, kind, title, description).await?;
+
+    Ok(Reply::text(format!(
+        "Updated the ``{}`` template, use ``!ctf template preview {}`` to see how it looks",
+        kind.as_str(),
+        kind.as_str()
+    )))
+}
+
+pub async fn reset_template(
+    invocation: &Invocation,
+    kind: &str,
+    ctf_name: Option<&str>,
+) -> Result<Reply, Error> {
+    let (guild_id, ctf_id, kind) = match template_target(invocation, kind, ctf_name).await {
+        Ok(target) => target,
+        Err(reply) => return Ok(reply),
+    };
+
+    if remove_announcement_template(guild_id, ctf_id, kind).await? {
+        Ok(Reply::text(format!(
+            "Reset the ``{}`` template",
+            kind.as_str()
+        )))
+    } else {
+        Ok(Reply::error(format!(
+            "There's no ``{}`` template set to reset",
+            kind.as_str()
+        )))
+    }
+}
+
+pub async fn preview_template(
+    invocation: &Invocation,
+    kind: &str,
+    ctf_name: Option<&str>,
+) -> Result<Reply, Error> {
+    let (guild_id, ctf_id, kind) = match template_target(invocation, kind, ctf_name).await {
+        Ok(target) => target,
+        Err(reply) => return Ok(reply),
+    };
+
+    let (title, description) = render_announcement(
+        GuildId(guild_id as u64),
+        ctf_id,
+        kind,
+        &TemplateValues::sample(),
+    )
+    .await;
+
+    Ok(Reply::embeds(vec![build_embed(|e| {
+        e.title(title);
+        if let Some(description) = description {
+            e.description(description);
+        }
+        e.field(
+            "🧩 Placeholders",
+            PLACEHOLDERS
+                .iter()
+                .map(|(name, meaning)| format!("``{{{}}}`` {}", name, meaning))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            false,
+        );
+        e.footer(|f| f.text("Preview with made up values"));
+    })]))
+}
+
+#[command]
+#[allowed_roles("Organiser")]
+#[example("add ctf \"web\" #web-solves @web")]
+#[example("add htb \"machine\" #boxes")]
+#[example("add htb \"machine\" #student-boxes \"student\"")]
+#[example("add ctf \"pwn\" #pwn-solves \"CTF name\"")]
+#[example("remove ctf \"web\"")]
+#[example("list")]
+#[description = "Routes solve announcements for a category (or HTB content type) to another channel, optionally pinging a role"]
+async fn route(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let action = args.single::<String>().unwrap_or_default();
+
+    let data = ctx.data.read().await;
+    let teams = data
+        .get::<HTBTeamsContainer>()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+
+    let reply = match (action.as_str(), args.remaining()) {
+        ("list", 0) => list_routes(&invocation, teams).await,
+        ("add", 3..=5) => {
+            let source = args.single::<String>()?;
+            let category = args.single_quoted::<String>()?;
+            let channel_id = args.single::<ChannelId>()?;
+            let role_id = args.single::<RoleId>().ok();
+            let name = args.single_quoted::<String>().ok();
+            add_route(
+                &invocation,
+                teams,
+                &source,
+                &category,
+                channel_id,
+                role_id,
+                name.as_deref(),
+            )
+            .await
+        }
+        ("remove", 2) | ("remove", 3) => {
+            let source = args.single::<String>()?;
+            let category = args.single_quoted::<String>()?;
+            let name = args.single_quoted::<String>().ok();
+            remove_route(&invocation, teams, &source, &category, name.as_deref()).await
+        }
+        _ => Ok(route_usage()),
+    };
+
+    send_reply(ctx, msg, reply).await
+}
+
+fn route_usage() -> Reply {
+    Reply::error(
+        "Usage: ``!ctf route add <ctf/htb> \"category\" #channel @role \"CTF or team name\"``, ``!ctf route remove <ctf/htb> \"category\" \"CTF or team name\"`` or ``!ctf route list``, the role and name are optional. HTB categories can also be ``machine`` or ``challenge``",
+    )
+}
+
+/// What a route is for: a guild, and either a CTF if one was named or a HTB team.
+struct RouteTarget<'a> {
+    guild_id: i64,
+    ctf_id: Option<i32>,
+    team: Option<&'a HTBTeam>,
+    source: RouteSource,
+}
+
+impl RouteTarget<'_> {
+    fn label(&self) -> String {
+        match self.team {
+            Some(team) => format!("{} {}", self.source.as_str().to_uppercase(), team.name),
+            None => self.source.as_str().to_uppercase(),
+        }
+    }
+}
+
+/// Works out which guild, CTF or team and poller a route command is for. Each HTB team has its
+/// own channel, so HTB routes are for one team, the one for this channel unless another is named.
+async fn route_target<'a>(
+    invocation: &Invocation,
+    teams: &'a [HTBTeam],
+    source: &str,
+    name: Option<&str>,
+) -> Result<RouteTarget<'a>, Reply> {
+    let source = match RouteSource::parse(source) {
+        Some(source) => source,
+        None => return Err(route_usage()),
+    };
+
+    if source == RouteSource::HTB {
+        let (guild_id, _) = guild_and_ctf(invocation, None).await?;
+
+        let team = match select_team(teams, name, invocation.channel_id) {
+            Some(team) => team,
+            None => {
+                return Err(match name {
+                    Some(name) => Reply::error(format!("No HTB team called '{}'!", name)),
+                    None => Reply::error("HTB isn't available right now, try again later."),
                 })
-            })
-            .await?;
+            }
+        };
+
+        return Ok(RouteTarget {
+            guild_id,
+            ctf_id: None,
+            team: Some(team),
+            source,
+        });
     }
 
-    Ok(())
+    let (guild_id, ctf_id) = guild_and_ctf(invocation, name).await?;
+
+    Ok(RouteTarget {
+        guild_id,
+        ctf_id,
+        team: None,
+        source,
+    })
+}
+
+pub async fn add_route(
+    invocation: &Invocation,
+    teams: &[HTBTeam],
+    source: &str,
+    category: &str,
+    channel_id: ChannelId,
+    role_id: Option<RoleId>,
+    name: Option<&str>,
+) -> Result<Reply, Error> {
+    let target = match route_target(invocation, teams, source, name).await {
+        Ok(target) => target,
+        Err(reply) => return Ok(reply),
+    };
+
+    add_announcement_route(
+        target.guild_id,
+        target.ctf_id,
+        target.team.map(|team| team.team_id),
+        target.source,
+        category.trim(),
+        channel_id.0 as i64,
+        role_id.map(|role_id| role_id.0 as i64),
+    )
+    .await?;
+
+    Ok(Reply::text(format!(
+        "{} solves in ``{}`` will be announced in <#{}>",
+        target.label(),
+        category.trim(),
+        channel_id.0
+    )))
+}
+
+pub async fn remove_route(
+    invocation: &Invocation,
+    teams: &[HTBTeam],
+    source: &str,
+    category: &str,
+    name: Option<&str>,
+) -> Result<Reply, Error> {
+    let target = match route_target(invocation, teams, source, name).await {
+        Ok(target) => target,
+        Err(reply) => return Ok(reply),
+    };
+
+    let removed = remove_announcement_route(
+        target.guild_id,
+        target.ctf_id,
+        target.team.map(|team| team.team_id),
+        target.source,
+        category.trim(),
+    )
+    .await?;
+
+    if removed {
+        Ok(Reply::text(format!(
+            "{} solves in ``{}`` will be announced in the default channel",
+            target.label(),
+            category.trim()
+        )))
+    } else {
+        Ok(Reply::error(format!(
+            "There's no route for ``{}`` to remove",
+            category.trim()
+        )))
+    }
+}
+
+pub async fn list_routes(invocation: &Invocation, teams: &[HTBTeam]) -> Result<Reply, Error> {
+    let guild_id = match invocation.guild_id {
+        Some(guild_id) => guild_id.0 as i64,
+        None => return Ok(Reply::error("This can only be used in a server!")),
+    };
+
+    let mut lines = vec![];
+    for source in [RouteSource::Ctf, RouteSource::HTB].iter().copied() {
+        for route in get_announcement_routes(guild_id, source).await? {
+            let scope = match (route.ctf_id, route.team_id) {
+                (Some(ctf_id), _) => match get_ctf_from_id(ctf_id).await {
+                    Ok(ctf) => ctf.name,
+                    Err(_) => "Unknown CTF".to_string(),
+                },
+                (None, Some(team_id)) => match teams.iter().find(|team| team.team_id == team_id) {
+                    Some(team) => format!("HTB {}", team.name),
+                    None => "Unknown HTB team".to_string(),
+                },
+                (None, None) => source.as_str().to_uppercase(),
+            };
+
+            let ping = route
+                .role_id
+                .map(|role_id| format!(", pinging <@&{}>", role_id))
+                .unwrap_or_default();
+
+            lines.push(format!(
+                "{}: ``{}`` ➡ <#{}>{}",
+                scope, route.category, route.channel_id, ping
+            ));
+        }
+    }
+
+    if lines.is_empty() {
+        return Ok(Reply::text(
+            "No routes set, every solve is announced in the default channel",
+        ));
+    }
+
+    Ok(Reply::embeds(vec![build_embed(|e| {
+        e.title("🔀 Announcement routes");
+        e.description(lines.join("\n"));
+    })]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_end_time() {
+        assert_eq!(
+            parse_end_time("2026-10-20 18:00"),
+            Some(
+                NaiveDateTime::parse_from_str("2026-10-20 18:00:00", "%Y-%m-%d %H:%M:%S").unwrap()
+            )
+        );
+        assert_eq!(parse_end_time("tomorrow"), None);
+    }
+
+    #[test]
+    fn test_parse_stale_minutes() {
+        assert_eq!(parse_stale_minutes("120"), Some(Some(120)));
+        assert_eq!(parse_stale_minutes("OFF"), Some(None));
+        assert_eq!(parse_stale_minutes("0"), Some(None));
+        assert_eq!(parse_stale_minutes("-5"), None);
+        assert_eq!(parse_stale_minutes("soon"), None);
+    }
+
+    #[test]
+    fn test_pick_writeup_challenge() {
+        let ended = Ctf {
+            active: false,
+            channel_id: 10,
+            ..Ctf::sample(1, "Old CTF")
+        };
+        let running = Ctf {
+            channel_id: 20,
+            ..Ctf::sample(2, "New CTF")
+        };
+        let challenge = |id, ctf: &Ctf| {
+            let challenge = Challenge {
+                id,
+                ctf_id: ctf.id,
+                ..Challenge::sample("Baby RSA", "crypto")
+            };
+            (challenge, ctf.clone())
+        };
+        let picked = |matches, channel_id, ctf_name| {
+            pick_writeup_challenge(matches, channel_id, ctf_name)
+                .ok()
+                .map(|challenge| challenge.id)
+        };
+
+        // The CTF is over, but the writeup still goes in
+        assert_eq!(picked(vec![challenge(1, &ended)], 30, None), Some(1));
+
+        let both = || vec![challenge(1, &ended), challenge(2, &running)];
+        assert_eq!(picked(both(), 10, None), Some(1));
+        assert_eq!(picked(both(), 30, None), Some(2));
+        assert_eq!(picked(both(), 30, Some("old ctf")), Some(1));
+        assert_eq!(picked(both(), 30, Some("Other CTF")), None);
+        assert_eq!(
+            picked(vec![challenge(1, &ended), challenge(3, &ended)], 30, None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_format_notes() {
+        let note = |discord_id, content: &str| ChallengeNote {
+            id: 1,
+            challenge_id: 1,
+            discord_id,
+            author: "alice".to_string(),
+            content: content.to_string(),
+            created_at: NaiveDateTime::parse_from_str("2026-10-19 09:05:00", "%Y-%m-%d %H:%M:%S")
+                .unwrap(),
+        };
+
+        assert_eq!(
+            format_notes(&[
+                note(Some(10), "key is in the EXIF"),
+                note(None, "try rockyou")
+            ]),
+            "``19 Oct 09:05`` <@10>: key is in the EXIF\n``19 Oct 09:05`` **alice**: try rockyou"
+        );
+
+        let long = "a".repeat(900);
+        let notes: Vec<ChallengeNote> = (0..6).map(|_| note(None, &long)).collect();
+        let formatted = format_notes(&notes);
+        assert!(formatted.starts_with("...and 2 older\n"));
+        assert!(formatted.chars().count() <= MAX_DESCRIPTION_LENGTH);
+    }
 }
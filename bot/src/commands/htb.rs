@@ -1,62 +1,151 @@
-use ctfdb::htb::db::{
-    add_working, get_challenge_from_id, get_solves_for_username, remove_working,
-    search_for_challenge_by_name, set_discord_id_for,
+use std::sync::Arc;
+
+use chrono::Local;
+use ctfdb::htb::{
+    db::{
+        add_working, create_link_request, get_all_user_mappings, get_challenge_from_id,
+        get_rank_history, get_solves_for_username, get_team_leaderboard,
+        get_tracked_content_progress, remove_discord_id_mappings, remove_working,
+        search_challenges, set_discord_id_for, verify_link_request,
+    },
+    structs::{bucket_rank_history, HTBApi, HTBContentType, HTBSearchFilter, RankBucket},
 };
+use ctfdb::models::HTBChallenge;
+use failure::Error;
 use serenity::client::Context;
 use serenity::framework::standard::{macros::*, Args, CommandResult};
-
 use serenity::model::channel::Message;
+use serenity::model::id::ChannelId;
+use tokio::sync::Mutex;
 
-use crate::populate_embed_from_htb_challenge;
+use crate::{
+    charts::render_rank_chart,
+    commands::core::{build_embed, send_reply, Invocation, Reply},
+    discord_name_provider::htb_working,
+    populate_embed_from_htb_challenge,
+    status_board::{join_within, MAX_DESCRIPTION_LENGTH},
+    teams::{select_team, split_team_selector, HTBTeam, HTBTeamsContainer},
+};
 
 #[group]
-#[commands(working, giveup, search, solves)]
+#[commands(
+    working,
+    giveup,
+    search,
+    solves,
+    progress,
+    leaderboard,
+    link,
+    verify,
+    unlink,
+    links,
+    forcelink,
+    notify,
+    rank
+)]
 #[prefixes("htb", "h")]
 pub struct HTBer;
 
+/// Works out which team a command is for, replying with why when there's no such team.
+pub async fn resolve_team(
+    ctx: &Context,
+    selector: Option<&str>,
+    channel_id: ChannelId,
+) -> Result<HTBTeam, Reply> {
+    let data = ctx.data.read().await;
+    let teams = data
+        .get::<HTBTeamsContainer>()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+
+    match select_team(teams, selector, channel_id) {
+        Some(team) => Ok(team.clone()),
+        None => Err(match selector {
+            Some(name) => Reply::error(format!("No HTB team called '{}'!", name)),
+            None => Reply::error("HTB isn't available right now, try again later."),
+        }),
+    }
+}
+
+/// Collects the command's arguments and works out which team it's for from any ``team=<name>``
+/// selector. Replies and returns ``None`` when there's no such team.
+async fn team_and_args(
+    ctx: &Context,
+    msg: &Message,
+    args: &mut Args,
+) -> CommandResult<Option<(HTBTeam, Vec<String>)>> {
+    let mut collected = vec![];
+    while !args.is_empty() {
+        collected.push(args.single_quoted::<String>()?);
+    }
+
+    let (selector, rest) = split_team_selector(collected);
+
+    match resolve_team(ctx, selector.as_deref(), msg.channel_id).await {
+        Ok(team) => Ok(Some((team, rest))),
+        Err(reply) => {
+            send_reply(ctx, msg, Ok(reply)).await?;
+            Ok(None)
+        }
+    }
+}
+
 #[command]
 #[allowed_roles("CTFer")]
 #[aliases("w")]
 #[example("\"Challenge name\"")]
+#[example("\"Challenge name\" team=student")]
 #[description = "Marks you as working on the provided challenge"]
 async fn working(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    if args.len() == 1 {
-        let challenge_name = args.single_quoted::<String>()?;
-
-        let username = msg
-            .author_nick(&ctx.http)
-            .await
-            .unwrap_or_else(|| msg.author.name.clone());
-
-        match add_working(username, &challenge_name).await {
-            Ok(_) => {
-                msg.reply(
-                    &ctx.http,
-                    &format!("Marked you as working on '{}'", &challenge_name),
-                )
-                .await?;
-            }
-            Err(why) => {
-                msg.reply(
-                    &ctx.http,
-                    format!(
-                        "Error when adding to working for '{}'... {}",
-                        &challenge_name, why
-                    ),
-                )
-                .await?;
-                eprintln!(
-                    "Error on adding to working for '{}' ... '{}'",
-                    &msg.author.name, why
-                );
-            }
-        }
+    let (team, args) = match team_and_args(ctx, msg, &mut args).await? {
+        Some(team_and_args) => team_and_args,
+        None => return Ok(()),
+    };
+
+    let reply = if let [challenge_name] = args.as_slice() {
+        mark_htb_working(
+            &team,
+            &Invocation::from_message(ctx, msg).await,
+            challenge_name,
+        )
+        .await
     } else {
-        msg.reply(&ctx.http, "Usage: ``!htb working \"Challenge name\"``")
-            .await?;
-    }
+        Ok(Reply::error(
+            "Usage: ``!htb working \"Challenge name\" [team=<team>]``",
+        ))
+    };
 
-    Ok(())
+    send_reply(ctx, msg, reply).await
+}
+
+pub async fn mark_htb_working(
+    team: &HTBTeam,
+    invocation: &Invocation,
+    challenge_name: &str,
+) -> Result<Reply, Error> {
+    match add_working(
+        team.team_id,
+        invocation.user_id.0 as i64,
+        &invocation.username,
+        challenge_name,
+    )
+    .await
+    {
+        Ok(_) => Ok(Reply::text(format!(
+            "Marked you as working on '{}'",
+            challenge_name
+        ))),
+        Err(why) => {
+            eprintln!(
+                "Error on adding to working for '{}' ... '{}'",
+                invocation.username, why
+            );
+            Ok(Reply::error(format!(
+                "Error when adding to working for '{}'... {}",
+                challenge_name, why
+            )))
+        }
+    }
 }
 
 // For lack of a better name...
@@ -66,126 +155,573 @@ async fn working(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult
 #[example("\"Challenge name\"")]
 #[description = "Removes you from working on the given challenge"]
 async fn giveup(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    if args.len() == 1 {
-        let challenge_name = args.single_quoted::<String>()?;
-        let username = msg
-            .author_nick(&ctx.http)
-            .await
-            .unwrap_or_else(|| msg.author.name.clone());
-
-        remove_working(username, &challenge_name).await?;
-
-        msg.reply(
-            &ctx.http,
-            &format!("Removed you from working on '{}'", &challenge_name),
+    let (team, args) = match team_and_args(ctx, msg, &mut args).await? {
+        Some(team_and_args) => team_and_args,
+        None => return Ok(()),
+    };
+
+    let reply = if let [challenge_name] = args.as_slice() {
+        htb_give_up(
+            &team,
+            &Invocation::from_message(ctx, msg).await,
+            challenge_name,
         )
-        .await?;
+        .await
     } else {
-        msg.reply(&ctx.http, "Usage: ``!htb giveup \"Challenge name\"``")
-            .await?;
-    }
+        Ok(Reply::error(
+            "Usage: ``!htb giveup \"Challenge name\" [team=<team>]``",
+        ))
+    };
+
+    send_reply(ctx, msg, reply).await
+}
+
+pub async fn htb_give_up(
+    team: &HTBTeam,
+    invocation: &Invocation,
+    challenge_name: &str,
+) -> Result<Reply, Error> {
+    remove_working(team.team_id, invocation.user_id.0 as i64, challenge_name).await?;
 
-    Ok(())
+    Ok(Reply::text(format!(
+        "Removed you from working on '{}'",
+        challenge_name
+    )))
 }
 
+/// The most results `search` will post, so a broad filter doesn't flood the channel.
+const MAX_SEARCH_RESULTS: usize = 10;
+
 #[command]
 #[allowed_roles("CTFer")]
 #[example("\"Challenge name\"")]
-#[description = "Searches for the status of the given challenge"]
+#[example("\"Machine name\" retired")]
+#[example("retired difficulty=easy os=linux")]
+#[example("\"Challenge name\" team=student")]
+#[description = "Searches for the status of the given challenge, optionally filtered by active/retired, difficulty and OS"]
 async fn search(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    if args.len() == 1 {
-        let challenge_name = args.single_quoted::<String>()?;
-
-        let challenges = search_for_challenge_by_name(&challenge_name).await?;
-
-        if !challenges.is_empty() {
-            for challenge in challenges {
-                msg.channel_id
-                    .send_message(&ctx.http, |m| {
-                        m.embed(|e| {
-                            populate_embed_from_htb_challenge(challenge, e);
-                            e
-                        })
-                    })
-                    .await?;
-            }
-        } else {
-            msg.reply(&ctx.http, "No challenge found by that name!")
-                .await?;
-        }
+    let (team, search_args) = match team_and_args(ctx, msg, &mut args).await? {
+        Some(team_and_args) => team_and_args,
+        None => return Ok(()),
+    };
+
+    let reply = if search_args.is_empty() {
+        Ok(Reply::error(
+            "Usage: ``!htb search \"Challenge name\" [active/retired] [difficulty=<difficulty>] [os=<os>] [team=<team>]``",
+        ))
     } else {
-        msg.reply(&ctx.http, "Usage: ``!htb search \"Challenge name\"``")
-            .await?;
+        search_htb(
+            &team,
+            &Invocation::from_message(ctx, msg).await,
+            &HTBSearchFilter::from_args(&search_args),
+        )
+        .await
+    };
+
+    send_reply(ctx, msg, reply).await
+}
+
+pub async fn search_htb(
+    team: &HTBTeam,
+    invocation: &Invocation,
+    filter: &HTBSearchFilter,
+) -> Result<Reply, Error> {
+    let challenges = search_challenges(team.team_id, filter).await?;
+
+    if challenges.is_empty() {
+        return Ok(Reply::error("No challenge found by that name!"));
+    }
+
+    let total = challenges.len();
+    let shown: Vec<HTBChallenge> = challenges.into_iter().take(MAX_SEARCH_RESULTS).collect();
+
+    let challenge_ids: Vec<i32> = shown.iter().map(|challenge| challenge.id).collect();
+    let working = htb_working(&invocation.http, invocation.guild_id, &challenge_ids).await?;
+
+    let reply = Reply::embeds(
+        shown
+            .into_iter()
+            .map(|challenge| {
+                let working = working.get(&challenge.id).map(String::as_str);
+                build_embed(|e| populate_embed_from_htb_challenge(challenge, working, e))
+            })
+            .collect(),
+    );
+
+    if total > MAX_SEARCH_RESULTS {
+        return Ok(reply.with_content(format!(
+            "Found {} results, showing the first {}. Try narrowing down your search!",
+            total, MAX_SEARCH_RESULTS
+        )));
     }
 
-    Ok(())
+    Ok(reply)
 }
 
 #[command]
 #[allowed_roles("CTFer")]
 #[example("\"Username\"")]
+#[example("\"Username\" team=student")]
 #[description = "Searches for solves of a given user"]
 async fn solves(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    if args.len() == 1 {
-        let username = args.single_quoted::<String>()?;
-
-        let solves = get_solves_for_username(&username).await?;
-
-        if !solves.is_empty() {
-            for solve in solves {
-                let challenge = get_challenge_from_id(solve.challenge_id).await?;
-
-                if !challenge.is_empty() {
-                    let first_challenge = challenge[0].clone();
-
-                    msg.channel_id
-                        .send_message(&ctx.http, |m| {
-                            m.embed(|e| {
-                                populate_embed_from_htb_challenge(first_challenge, e);
-                                e
-                            })
-                        })
-                        .await?;
-                }
-            }
+    let (team, args) = match team_and_args(ctx, msg, &mut args).await? {
+        Some(team_and_args) => team_and_args,
+        None => return Ok(()),
+    };
+
+    let reply = if let [username] = args.as_slice() {
+        list_htb_solves(&team, &Invocation::from_message(ctx, msg).await, username).await
+    } else {
+        Ok(Reply::error(
+            "Usage: ``!htb solves \"Username\" [team=<team>]``",
+        ))
+    };
+
+    send_reply(ctx, msg, reply).await
+}
+
+pub async fn list_htb_solves(
+    team: &HTBTeam,
+    invocation: &Invocation,
+    username: &str,
+) -> Result<Reply, Error> {
+    let solves = get_solves_for_username(team.team_id, username).await?;
+
+    if solves.is_empty() {
+        return Ok(Reply::error("No solves found for that user!"));
+    }
+
+    let mut embeds = vec![];
+
+    for solve in solves {
+        let challenge = get_challenge_from_id(
+            team.team_id,
+            solve.challenge_id,
+            HTBContentType::from_solve_type(&solve.solve_type),
+        )
+        .await?;
+
+        if let Some(first_challenge) = challenge.into_iter().next() {
+            let working =
+                htb_working(&invocation.http, invocation.guild_id, &[first_challenge.id]).await?;
+            let working = working.get(&first_challenge.id).map(String::as_str);
+
+            embeds.push(build_embed(|e| {
+                populate_embed_from_htb_challenge(first_challenge, working, e)
+            }));
+        }
+    }
+
+    Ok(Reply::embeds(embeds))
+}
+
+#[command]
+#[allowed_roles("CTFer")]
+#[aliases("p")]
+#[example("team=student")]
+#[description = "Shows the team's flag progress on Pro Labs, Fortresses and Endgames"]
+async fn progress(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let (team, _) = match team_and_args(ctx, msg, &mut args).await? {
+        Some(team_and_args) => team_and_args,
+        None => return Ok(()),
+    };
+
+    send_reply(ctx, msg, show_htb_progress(&team).await).await
+}
+
+pub async fn show_htb_progress(team: &HTBTeam) -> Result<Reply, Error> {
+    let progress = get_tracked_content_progress(team.team_id).await?;
+
+    // Only bother showing content that someone has actually started on
+    let started: Vec<_> = progress
+        .into_iter()
+        .filter(|(_, captured)| *captured > 0)
+        .collect();
+
+    if started.is_empty() {
+        return Ok(Reply::text(
+            "No flags captured on Pro Labs, Fortresses or Endgames yet!",
+        ));
+    }
+
+    Ok(Reply::embeds(vec![build_embed(|e| {
+        e.title(format!("🚩 Team progress ({})", team.name));
+        for (content, captured) in started {
+            e.field(
+                format!("{} ({})", content.name, content.content_type.display_name()),
+                format!("{}/{} flags", captured, content.total_flags),
+                true,
+            );
+        }
+    })]))
+}
+
+/// How many members to show in the weekly movers section of the leaderboard.
+const WEEKLY_MOVERS: usize = 3;
+
+#[command]
+#[allowed_roles("CTFer")]
+#[aliases("lb")]
+#[example("team=student")]
+#[description = "Shows the internal team standings and this week's biggest movers"]
+async fn leaderboard(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let (team, _) = match team_and_args(ctx, msg, &mut args).await? {
+        Some(team_and_args) => team_and_args,
+        None => return Ok(()),
+    };
+
+    send_reply(ctx, msg, show_htb_leaderboard(&team).await).await
+}
+
+pub async fn show_htb_leaderboard(team: &HTBTeam) -> Result<Reply, Error> {
+    let leaderboard = get_team_leaderboard(team.team_id).await?;
+
+    if leaderboard.is_empty() {
+        return Ok(Reply::text("No team member stats have been collected yet!"));
+    }
+
+    let standings = leaderboard
+        .iter()
+        .enumerate()
+        .map(|(position, entry)| {
+            format!(
+                "**{}.** {} - {} points ({}, #{}) 👤 {} 💻 {}",
+                position + 1,
+                entry.name,
+                entry.points,
+                entry.rank_text,
+                entry.rank,
+                entry.user_owns,
+                entry.root_owns
+            )
+        })
+        .collect::<Vec<String>>();
+
+    let mut movers: Vec<_> = leaderboard
+        .iter()
+        .filter(|entry| entry.points_gained > 0 || entry.owns_gained > 0)
+        .collect();
+    movers.sort_by(|a, b| b.points_gained.cmp(&a.points_gained));
+
+    let movers = movers
+        .into_iter()
+        .take(WEEKLY_MOVERS)
+        .map(|entry| {
+            format!(
+                "{} +{} points, +{} owns",
+                entry.name, entry.points_gained, entry.owns_gained
+            )
+        })
+        .collect::<Vec<String>>();
+
+    Ok(Reply::embeds(vec![build_embed(|e| {
+        e.title(format!("🏆 Team leaderboard ({})", team.name));
+        e.description(join_within(standings, "\n", MAX_DESCRIPTION_LENGTH));
+
+        if movers.is_empty() {
+            e.field("📈 Weekly movers", "Nobody has moved this week", false);
         } else {
-            msg.reply(&ctx.http, "No solves found for that user!")
-                .await?;
+            e.field("📈 Weekly movers", movers.join("\n"), false);
         }
-    } else {
-        msg.reply(&ctx.http, "Usage: ``!htb solves \"Username\"``")
-            .await?;
+    })]))
+}
+
+/// Linking isn't tied to a team, so any logged in API will do, the primary team's is used.
+async fn get_htb_api(ctx: &Context) -> Option<Arc<Mutex<HTBApi>>> {
+    ctx.data
+        .read()
+        .await
+        .get::<HTBTeamsContainer>()
+        .and_then(|teams| teams.first())
+        .map(|team| team.api.clone())
+}
+
+#[command]
+#[allowed_roles("CTFer")]
+#[example("<htb id>")]
+#[description = "Starts linking your HTB account, giving you a code to put in your HTB profile"]
+async fn link(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let reply = match (args.len(), args.single::<i32>()) {
+        (1, Ok(htb_id)) => {
+            start_htb_link(ctx, &Invocation::from_message(ctx, msg).await, htb_id).await
+        }
+        _ => Ok(Reply::error("Usage: ``!htb link <htb id>``")),
+    };
+
+    send_reply(ctx, msg, reply).await
+}
+
+pub async fn start_htb_link(
+    ctx: &Context,
+    invocation: &Invocation,
+    htb_id: i32,
+) -> Result<Reply, Error> {
+    let htb_api = match get_htb_api(ctx).await {
+        Some(htb_api) => htb_api,
+        None => {
+            return Ok(Reply::error(
+                "HTB isn't available right now, try again later.",
+            ))
+        }
+    };
+
+    // Make sure the account exists before handing out a code for it
+    let htb_name = match htb_api.lock().await.get_user_overview(htb_id).await {
+        Ok(overview) => overview.profile.name,
+        Err(why) => {
+            eprintln!("Error when looking up HTB user {}... {}", htb_id, why);
+            return Ok(Reply::error("Couldn't find a HTB user with that ID!"));
+        }
+    };
+
+    let code = create_link_request(htb_id, *invocation.user_id.as_u64() as i64).await?;
+
+    Ok(Reply::text(format!(
+        "To link **{}**, add ``{}`` to your HTB profile description and then run ``!htb verify`` within the hour.",
+        htb_name, code
+    )))
+}
+
+#[command]
+#[allowed_roles("CTFer")]
+#[description = "Checks your HTB profile for your link code, and links your account if it's there"]
+async fn verify(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let reply = verify_htb_link(ctx, &Invocation::from_message(ctx, msg).await).await;
+    send_reply(ctx, msg, reply).await
+}
+
+pub async fn verify_htb_link(ctx: &Context, invocation: &Invocation) -> Result<Reply, Error> {
+    let htb_api = match get_htb_api(ctx).await {
+        Some(htb_api) => htb_api,
+        None => {
+            return Ok(Reply::error(
+                "HTB isn't available right now, try again later.",
+            ))
+        }
+    };
+
+    let discord_id = *invocation.user_id.as_u64() as i64;
+    let result = verify_link_request(discord_id, &*htb_api.lock().await).await;
+
+    match result {
+        Ok(htb_name) => Ok(Reply::text(format!(
+            "Linked you to **{}**! You can remove the code from your profile now.",
+            htb_name
+        ))),
+        Err(why) => Ok(Reply::error(format!("Unable to link... {}", why))),
+    }
+}
+
+#[command]
+#[allowed_roles("CTFer")]
+#[description = "Unlinks any HTB accounts linked to you"]
+async fn unlink(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let reply = unlink_htb(&Invocation::from_message(ctx, msg).await).await;
+    send_reply(ctx, msg, reply).await
+}
+
+pub async fn unlink_htb(invocation: &Invocation) -> Result<Reply, Error> {
+    let removed = remove_discord_id_mappings(*invocation.user_id.as_u64() as i64).await?;
+
+    if removed == 0 {
+        return Ok(Reply::error("You don't have a HTB account linked!"));
     }
 
-    Ok(())
+    Ok(Reply::text("Unlinked your HTB account."))
+}
+
+#[command]
+#[allowed_roles("CTFer")]
+#[description = "Lists which discord users are linked to which HTB accounts"]
+async fn links(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    send_reply(ctx, msg, list_htb_links().await).await
+}
+
+pub async fn list_htb_links() -> Result<Reply, Error> {
+    let mappings = get_all_user_mappings().await?;
+
+    if mappings.is_empty() {
+        return Ok(Reply::text("Nobody has linked their HTB account yet!"));
+    }
+
+    let lines = mappings
+        .iter()
+        .map(|mapping| {
+            format!(
+                "<@{}> - [{}](https://app.hackthebox.com/users/{})",
+                mapping.discord_id, mapping.htb_id, mapping.htb_id
+            )
+        })
+        .collect::<Vec<String>>();
+
+    Ok(Reply::embeds(vec![build_embed(|e| {
+        e.title("🔗 Linked HTB accounts");
+        e.description(join_within(lines, "\n", MAX_DESCRIPTION_LENGTH));
+    })]))
 }
 
 #[command]
 #[allowed_roles("Organiser")]
 #[example("<htb id> <discord id>")]
-#[description = "Links a users HTB ID to a discord ID"]
-async fn link(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    if args.len() == 2 {
+#[description = "Links a users HTB ID to a discord ID without verification"]
+async fn forcelink(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let reply = if args.len() == 2 {
         let htb_id = args.single::<i32>()?;
         let discord_id = args.single::<i64>()?;
 
-        match set_discord_id_for(htb_id, discord_id).await {
-            Ok(_) => {
-                msg.reply(&ctx.http, "User mapping entered.").await?;
-            }
-            Err(why) => {
-                msg.reply(&ctx.http, "Unable to enter using mapping. Refer to logs.")
-                    .await?;
-                eprintln!(
-                    "Error when trying to enter htb id {} & discord id {} into the database... {}",
-                    htb_id, discord_id, why
-                );
-            }
+        force_htb_link(htb_id, discord_id).await
+    } else {
+        Ok(Reply::error(
+            "Usage: ``!htb forcelink <htb id> <discord id>``",
+        ))
+    };
+
+    send_reply(ctx, msg, reply).await
+}
+
+pub async fn force_htb_link(htb_id: i32, discord_id: i64) -> Result<Reply, Error> {
+    match set_discord_id_for(htb_id, discord_id).await {
+        Ok(_) => Ok(Reply::text("User mapping entered.")),
+        Err(why) => {
+            eprintln!(
+                "Error when trying to enter htb id {} & discord id {} into the database... {}",
+                htb_id, discord_id, why
+            );
+            Ok(Reply::error(
+                "Unable to enter using mapping. Refer to logs.",
+            ))
+        }
+    }
+}
+
+#[command]
+#[allowed_roles("CTFer")]
+#[example("team=student")]
+#[description = "Toggles being pinged shortly before new HTB machines release"]
+async fn notify(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let (team, _) = match team_and_args(ctx, msg, &mut args).await? {
+        Some(team_and_args) => team_and_args,
+        None => return Ok(()),
+    };
+
+    let reply = toggle_htb_notify(ctx, &team, &Invocation::from_message(ctx, msg).await).await;
+    send_reply(ctx, msg, reply).await
+}
+
+pub async fn toggle_htb_notify(
+    ctx: &Context,
+    team: &HTBTeam,
+    invocation: &Invocation,
+) -> Result<Reply, Error> {
+    let role_id = match team.release_role {
+        Some(role_id) => role_id,
+        None => {
+            return Ok(Reply::error(
+                "Release notifications aren't set up on this server.",
+            ))
         }
+    };
+
+    let guild_id = match invocation.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(Reply::error("This command can only be used in a server.")),
+    };
+
+    let mut member = guild_id.member(&ctx.http, invocation.user_id).await?;
+
+    if member.roles.contains(&role_id) {
+        member.remove_role(&ctx.http, role_id).await?;
+        Ok(Reply::text(
+            "You'll no longer be pinged about new machine releases.",
+        ))
+    } else {
+        member.add_role(&ctx.http, role_id).await?;
+        Ok(Reply::text(
+            "You'll now be pinged shortly before new machines release!",
+        ))
+    }
+}
+
+/// How many days of history `rank` shows when not given any.
+pub const DEFAULT_RANK_HISTORY_DAYS: i64 = 30;
+
+#[command]
+#[allowed_roles("CTFer")]
+#[example("30")]
+#[example("30 team=student")]
+#[description = "Charts the team's rank and points over the last few days (30 by default)"]
+async fn rank(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let (team, args) = match team_and_args(ctx, msg, &mut args).await? {
+        Some(team_and_args) => team_and_args,
+        None => return Ok(()),
+    };
+
+    let days = match args.as_slice() {
+        [] => Some(DEFAULT_RANK_HISTORY_DAYS),
+        [days] => days.parse::<i64>().ok().filter(|days| *days > 0),
+        _ => None,
+    };
+
+    let reply = match days {
+        Some(days) => chart_htb_rank(&team, days).await,
+        None => Ok(Reply::error("Usage: ``!htb rank [days] [team=<team>]``")),
+    };
+
+    send_reply(ctx, msg, reply).await
+}
+
+pub async fn chart_htb_rank(team: &HTBTeam, days: i64) -> Result<Reply, Error> {
+    let from = Local::now().naive_local() - chrono::Duration::days(days);
+    let history = get_rank_history(team.team_id, Some(from), None).await?;
+
+    // Over longer periods, a point per day is plenty
+    let bucket = if days > 7 {
+        Some(RankBucket::Day)
     } else {
-        msg.reply(&ctx.http, "Usage: ``!htb link <htb id> <discord id>``")
-            .await?;
+        None
+    };
+    let history = bucket_rank_history(&history, bucket);
+
+    if history.len() < 2 {
+        return Ok(Reply::error(
+            "Not enough rank history for that period to draw a chart yet!",
+        ));
     }
 
-    Ok(())
+    let chart = render_rank_chart(&history)?;
+
+    let first = &history[0];
+    let latest = &history[history.len() - 1];
+    let best_rank = history
+        .iter()
+        .map(|point| point.rank)
+        .min()
+        .unwrap_or(latest.rank);
+
+    let embed = build_embed(|e| {
+        e.title(format!(
+            "📈 {} team rank over the last {} days",
+            team.name, days
+        ));
+        e.description("Rank on top, points below");
+        e.field(
+            "🏅 Rank",
+            format!("#{} → #{}", first.rank, latest.rank),
+            true,
+        );
+        e.field("⭐ Best rank", format!("#{}", best_rank), true);
+        e.field(
+            "💰 Points",
+            format!(
+                "{} → {} ({:+})",
+                first.points,
+                latest.points,
+                latest.points - first.points
+            ),
+            true,
+        );
+        e.attachment("rank.png");
+    });
+
+    Ok(Reply::embeds(vec![embed]).with_attachment("rank.png", chart))
 }
@@ -1,2 +1,7 @@
+pub mod board;
+pub mod buttons;
+pub mod checkin;
+pub mod core;
 pub mod ctf;
 pub mod htb;
+pub mod slash;
@@ -0,0 +1,981 @@
+use std::borrow::Cow;
+
+use failure::Error;
+use serenity::{
+    builder::{
+        CreateActionRow, CreateApplicationCommand, CreateApplicationCommandOption, CreateEmbed,
+    },
+    client::Context,
+    http::AttachmentType,
+    model::{
+        id::{ChannelId, GuildId, RoleId},
+        interactions::{
+            application_command::{
+                ApplicationCommandInteraction, ApplicationCommandInteractionDataOption,
+                ApplicationCommandOptionType,
+            },
+            autocomplete::AutocompleteInteraction,
+            Interaction, InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+        },
+    },
+};
+
+use ctfdb::{
+    announcements::TemplateKind,
+    ctfs::db::{get_active_ctfs, get_challenges_for_channel},
+    htb::{db::search_for_challenge_by_name, structs::HTBSearchFilter},
+    writeups::WriteupBody,
+};
+
+use crate::{
+    commands::{
+        board::{handle_board_button, show_board, BOARD_BUTTON_PREFIX},
+        buttons::handle_challenge_button,
+        checkin::{handle_checkin_button, CHECKIN_BUTTON_PREFIX},
+        core::{add_action_rows, has_role, reply_or_error, Invocation, Reply},
+        ctf::{
+            add_note, add_route, end_ctf, export_writeups, give_up, list_active_ctfs,
+            list_challenges, list_notes, list_routes, mark_working, preview_template, remove_route,
+            reset_template, search_challenge, set_end_time, set_stale_minutes, set_template,
+            show_stats, start_ctf, submit_writeup,
+        },
+        htb::{
+            chart_htb_rank, force_htb_link, htb_give_up, list_htb_links, list_htb_solves,
+            mark_htb_working, resolve_team, search_htb, show_htb_leaderboard, show_htb_progress,
+            start_htb_link, toggle_htb_notify, unlink_htb, verify_htb_link,
+            DEFAULT_RANK_HISTORY_DAYS,
+        },
+    },
+    teams::HTBTeamsContainer,
+};
+
+/// Discord won't take more than this many embeds in one message.
+const MAX_EMBEDS_PER_MESSAGE: usize = 10;
+
+/// Discord won't take more than this many autocomplete choices.
+const MAX_AUTOCOMPLETE_CHOICES: usize = 25;
+
+/// Registers ``/ctf`` and ``/htb`` with the guild, mirroring the prefix commands.
+pub async fn register_slash_commands(ctx: &Context, guild_id: GuildId) -> Result<(), Error> {
+    let team_names: Vec<String> = ctx
+        .data
+        .read()
+        .await
+        .get::<HTBTeamsContainer>()
+        .map(|teams| teams.iter().map(|team| team.name.clone()).collect())
+        .unwrap_or_default();
+
+    guild_id
+        .set_application_commands(&ctx.http, |commands| {
+            commands
+                .create_application_command(|command| create_ctf_command(command, &team_names))
+                .create_application_command(|command| create_htb_command(command, &team_names))
+        })
+        .await?;
+
+    Ok(())
+}
+
+fn subcommand<'a>(
+    option: &'a mut CreateApplicationCommandOption,
+    name: &str,
+    description: &str,
+) -> &'a mut CreateApplicationCommandOption {
+    option
+        .name(name)
+        .description(description)
+        .kind(ApplicationCommandOptionType::SubCommand)
+}
+
+fn string_option<'a>(
+    option: &'a mut CreateApplicationCommandOption,
+    name: &str,
+    description: &str,
+    required: bool,
+) -> &'a mut CreateApplicationCommandOption {
+    option
+        .name(name)
+        .description(description)
+        .kind(ApplicationCommandOptionType::String)
+        .required(required)
+}
+
+fn challenge_option(
+    option: &mut CreateApplicationCommandOption,
+) -> &mut CreateApplicationCommandOption {
+    string_option(option, "challenge", "The challenge's name", true).set_autocomplete(true)
+}
+
+fn team_option<'a>(
+    option: &'a mut CreateApplicationCommandOption,
+    team_names: &[String],
+) -> &'a mut CreateApplicationCommandOption {
+    string_option(
+        option,
+        "team",
+        "The HTB team, defaults to the team for this channel",
+        false,
+    );
+
+    for name in team_names {
+        option.add_string_choice(name, name);
+    }
+
+    option
+}
+
+fn create_ctf_command<'a>(
+    command: &'a mut CreateApplicationCommand,
+    team_names: &[String],
+) -> &'a mut CreateApplicationCommand {
+    command
+        .name("ctf")
+        .description("CTF commands")
+        .create_option(|option| {
+            subcommand(
+                option,
+                "start",
+                "Starts a CTF, and will begin polling for challenge status changes",
+            )
+            .create_sub_option(|o| string_option(o, "name", "The CTF's name", true))
+            .create_sub_option(|o| string_option(o, "url", "The CTF's URL", true))
+            .create_sub_option(|o| string_option(o, "api_key", "The CTFd API key", true))
+            .create_sub_option(|o| {
+                o.name("channel")
+                    .description("The channel to post updates to")
+                    .kind(ApplicationCommandOptionType::Channel)
+            })
+        })
+        .create_option(|option| {
+            subcommand(option, "end", "Ends a CTF with the provided name").create_sub_option(|o| {
+                string_option(o, "ctf", "The CTF's name", true).set_autocomplete(true)
+            })
+        })
+        .create_option(|option| {
+            subcommand(
+                option,
+                "endtime",
+                "Sets when a CTF ends (in the bot's local time), shown on its status board",
+            )
+            .create_sub_option(|o| {
+                string_option(o, "ctf", "The CTF's name", true).set_autocomplete(true)
+            })
+            .create_sub_option(|o| string_option(o, "time", "e.g. 2026-10-20 18:00", true))
+        })
+        .create_option(|option| {
+            subcommand(
+                option,
+                "stale",
+                "Sets how long before people working on a challenge are asked if they're still on it",
+            )
+            .create_sub_option(|o| {
+                string_option(o, "ctf", "The CTF's name", true).set_autocomplete(true)
+            })
+            .create_sub_option(|o| {
+                o.name("minutes")
+                    .description("How many minutes, 0 turns it off")
+                    .kind(ApplicationCommandOptionType::Integer)
+                    .required(true)
+            })
+        })
+        .create_option(|option| subcommand(option, "active", "Shows all active CTFs"))
+        .create_option(|option| {
+            subcommand(
+                option,
+                "list",
+                "Lists all challenges for given CTF, or the CTF for this channel",
+            )
+            .create_sub_option(|o| {
+                string_option(o, "ctf", "The CTF's name", false).set_autocomplete(true)
+            })
+        })
+        .create_option(|option| {
+            subcommand(
+                option,
+                "board",
+                "Posts a board of all challenges for given CTF, or the CTF for this channel",
+            )
+            .create_sub_option(|o| {
+                string_option(o, "ctf", "The CTF's name", false).set_autocomplete(true)
+            })
+        })
+        .create_option(|option| {
+            subcommand(
+                option,
+                "working",
+                "Marks you as working on the provided challenge",
+            )
+            .create_sub_option(challenge_option)
+        })
+        .create_option(|option| {
+            subcommand(
+                option,
+                "giveup",
+                "Removes you from working on the given challenge",
+            )
+            .create_sub_option(challenge_option)
+        })
+        .create_option(|option| {
+            subcommand(
+                option,
+                "search",
+                "Searches for the status of the given challenge",
+            )
+            .create_sub_option(challenge_option)
+        })
+        .create_option(|option| {
+            subcommand(
+                option,
+                "note",
+                "Adds a note to the given challenge for the rest of the team",
+            )
+            .create_sub_option(challenge_option)
+            .create_sub_option(|o| string_option(o, "note", "What to note down", true))
+        })
+        .create_option(|option| {
+            subcommand(option, "notes", "Lists the notes on the given challenge")
+                .create_sub_option(challenge_option)
+        })
+        .create_option(|option| {
+            subcommand(
+                option,
+                "writeup",
+                "Stores a link to your writeup for the given challenge",
+            )
+            .create_sub_option(challenge_option)
+            .create_sub_option(|o| string_option(o, "url", "Where the writeup is", true))
+            .create_sub_option(|o| {
+                string_option(
+                    o,
+                    "ctf",
+                    "The challenge's CTF, if its name isn't unique",
+                    false,
+                )
+            })
+        })
+        .create_option(|option| {
+            subcommand(
+                option,
+                "export",
+                "Exports the CTF's challenges, notes and writeups as a folder per challenge",
+            )
+            .create_sub_option(|o| {
+                string_option(o, "ctf", "The CTF's name", true).set_autocomplete(true)
+            })
+        })
+        .create_option(|option| {
+            subcommand(option, "stats", "Displays the stats for all active ctfs")
+        })
+        .create_option(|option| {
+            subcommand(
+                option,
+                "template",
+                "Sets, resets or previews the template for solve announcements",
+            )
+            .create_sub_option(|o| {
+                string_option(o, "action", "What to do with the template", true)
+                    .add_string_choice("set", "set")
+                    .add_string_choice("reset", "reset")
+                    .add_string_choice("preview", "preview")
+            })
+            .create_sub_option(|o| {
+                string_option(o, "kind", "Which announcement the template is for", true);
+
+                for kind in TemplateKind::KINDS.iter() {
+                    o.add_string_choice(kind.as_str(), kind.as_str());
+                }
+
+                o
+            })
+            .create_sub_option(|o| {
+                string_option(
+                    o,
+                    "title",
+                    "The title, when setting, e.g. {flag} {challenge} by {solver}",
+                    false,
+                )
+            })
+            .create_sub_option(|o| {
+                string_option(o, "description", "The description, when setting", false)
+            })
+            .create_sub_option(|o| {
+                string_option(o, "ctf", "Only use it for this CTF", false).set_autocomplete(true)
+            })
+        })
+        .create_option(|option| {
+            subcommand(
+                option,
+                "route",
+                "Routes solve announcements for a category to another channel",
+            )
+            .create_sub_option(|o| {
+                string_option(o, "action", "What to do with the route", true)
+                    .add_string_choice("add", "add")
+                    .add_string_choice("remove", "remove")
+                    .add_string_choice("list", "list")
+            })
+            .create_sub_option(|o| {
+                string_option(
+                    o,
+                    "source",
+                    "Which solves it's for, when adding or removing",
+                    false,
+                )
+                .add_string_choice("ctf", "ctf")
+                .add_string_choice("htb", "htb")
+            })
+            .create_sub_option(|o| {
+                string_option(
+                    o,
+                    "category",
+                    "The category, or machine/challenge for HTB",
+                    false,
+                )
+            })
+            .create_sub_option(|o| {
+                o.name("channel")
+                    .description("The channel to announce them in, when adding")
+                    .kind(ApplicationCommandOptionType::Channel)
+            })
+            .create_sub_option(|o| {
+                o.name("role")
+                    .description("A role to ping, when adding")
+                    .kind(ApplicationCommandOptionType::Role)
+            })
+            .create_sub_option(|o| {
+                string_option(o, "ctf", "Only use it for this CTF", false).set_autocomplete(true)
+            })
+            .create_sub_option(|o| team_option(o, team_names))
+        })
+}
+
+fn create_htb_command<'a>(
+    command: &'a mut CreateApplicationCommand,
+    team_names: &[String],
+) -> &'a mut CreateApplicationCommand {
+    command
+        .name("htb")
+        .description("HTB commands")
+        .create_option(|option| {
+            subcommand(
+                option,
+                "working",
+                "Marks you as working on the provided challenge",
+            )
+            .create_sub_option(challenge_option)
+            .create_sub_option(|o| team_option(o, team_names))
+        })
+        .create_option(|option| {
+            subcommand(
+                option,
+                "giveup",
+                "Removes you from working on the given challenge",
+            )
+            .create_sub_option(challenge_option)
+            .create_sub_option(|o| team_option(o, team_names))
+        })
+        .create_option(|option| {
+            subcommand(
+                option,
+                "search",
+                "Searches active & retired content, optionally filtered by status, difficulty and OS",
+            )
+            .create_sub_option(|o| {
+                string_option(o, "name", "The machine or challenge's name", false)
+                    .set_autocomplete(true)
+            })
+            .create_sub_option(|o| {
+                string_option(o, "status", "Active or retired", false)
+                    .add_string_choice("active", "active")
+                    .add_string_choice("retired", "retired")
+            })
+            .create_sub_option(|o| string_option(o, "difficulty", "e.g. easy", false))
+            .create_sub_option(|o| string_option(o, "os", "e.g. linux", false))
+            .create_sub_option(|o| team_option(o, team_names))
+        })
+        .create_option(|option| {
+            subcommand(option, "solves", "Searches for solves of a given user")
+                .create_sub_option(|o| string_option(o, "username", "The user's name", true))
+                .create_sub_option(|o| team_option(o, team_names))
+        })
+        .create_option(|option| {
+            subcommand(
+                option,
+                "progress",
+                "Shows the team's flag progress on Pro Labs, Fortresses and Endgames",
+            )
+            .create_sub_option(|o| team_option(o, team_names))
+        })
+        .create_option(|option| {
+            subcommand(
+                option,
+                "leaderboard",
+                "Shows the internal team standings and this week's biggest movers",
+            )
+            .create_sub_option(|o| team_option(o, team_names))
+        })
+        .create_option(|option| {
+            subcommand(
+                option,
+                "link",
+                "Gives you a code to put in your HTB profile to link your account",
+            )
+            .create_sub_option(|o| {
+                o.name("htb_id")
+                    .description("Your HTB user ID")
+                    .kind(ApplicationCommandOptionType::Integer)
+                    .required(true)
+            })
+        })
+        .create_option(|option| {
+            subcommand(
+                option,
+                "verify",
+                "Checks your HTB profile for the code and links your account",
+            )
+        })
+        .create_option(|option| subcommand(option, "unlink", "Unlinks your HTB account"))
+        .create_option(|option| subcommand(option, "links", "Lists the linked HTB accounts"))
+        .create_option(|option| {
+            subcommand(
+                option,
+                "forcelink",
+                "Links the provided HTB ID with a Discord user, skipping verification",
+            )
+            .create_sub_option(|o| {
+                o.name("htb_id")
+                    .description("The HTB user ID")
+                    .kind(ApplicationCommandOptionType::Integer)
+                    .required(true)
+            })
+            .create_sub_option(|o| {
+                o.name("user")
+                    .description("The Discord user")
+                    .kind(ApplicationCommandOptionType::User)
+                    .required(true)
+            })
+        })
+        .create_option(|option| {
+            subcommand(
+                option,
+                "notify",
+                "Toggles being pinged shortly before new machines release",
+            )
+            .create_sub_option(|o| team_option(o, team_names))
+        })
+        .create_option(|option| {
+            subcommand(
+                option,
+                "rank",
+                "Charts the team's rank and points over the last few days (30 by default)",
+            )
+            .create_sub_option(|o| {
+                o.name("days")
+                    .description("How many days to chart")
+                    .kind(ApplicationCommandOptionType::Integer)
+            })
+            .create_sub_option(|o| team_option(o, team_names))
+        })
+}
+
+/// The options given to a slash subcommand.
+struct SlashArgs<'a>(&'a [ApplicationCommandInteractionDataOption]);
+
+impl SlashArgs<'_> {
+    fn string(&self, name: &str) -> Option<String> {
+        self.0
+            .iter()
+            .find(|option| option.name == name)
+            .and_then(|option| option.value.as_ref())
+            .and_then(|value| value.as_str())
+            .map(str::to_string)
+    }
+
+    fn required_string(&self, name: &str) -> Result<String, Error> {
+        self.string(name)
+            .ok_or_else(|| format_err!("Missing the {} option", name))
+    }
+
+    fn integer(&self, name: &str) -> Option<i64> {
+        self.0
+            .iter()
+            .find(|option| option.name == name)
+            .and_then(|option| option.value.as_ref())
+            .and_then(|value| value.as_i64())
+    }
+
+    /// Users and channels come through as their snowflake.
+    fn id(&self, name: &str) -> Option<u64> {
+        self.string(name).and_then(|id| id.parse::<u64>().ok())
+    }
+}
+
+/// The role a subcommand needs, the same as its prefix command's ``allowed_roles``.
+fn required_role(command: &str, subcommand: &str) -> &'static str {
+    match (command, subcommand) {
+        ("ctf", "start")
+        | ("ctf", "end")
+        | ("ctf", "endtime")
+        | ("ctf", "template")
+        | ("ctf", "route")
+        | ("ctf", "stale")
+        | ("ctf", "export")
+        | ("htb", "forcelink") => "Organiser",
+        _ => "CTFer",
+    }
+}
+
+pub async fn handle_interaction(ctx: &Context, interaction: Interaction) {
+    match interaction {
+        Interaction::ApplicationCommand(command) => {
+            // Discord only waits 3 seconds for an answer, and some commands wait on HTB or draw
+            // charts, so every command is deferred and answered once it's done
+            if let Err(why) = command
+                .create_interaction_response(&ctx.http, |response| {
+                    response.kind(InteractionResponseType::DeferredChannelMessageWithSource)
+                })
+                .await
+            {
+                eprintln!(
+                    "Error when deferring /{} command... {}",
+                    command.data.name, why
+                );
+                return;
+            }
+
+            let reply = reply_or_error(run_slash_command(ctx, &command).await);
+
+            if let Err(why) = respond(ctx, &command, reply).await {
+                eprintln!(
+                    "Error when responding to /{} command... {}",
+                    command.data.name, why
+                );
+            }
+        }
+        Interaction::Autocomplete(autocomplete) => {
+            if let Err(why) = respond_to_autocomplete(ctx, &autocomplete).await {
+                eprintln!("Error when responding to autocomplete... {}", why);
+            }
+        }
+        Interaction::MessageComponent(component) => {
+            if component.data.custom_id.starts_with(BOARD_BUTTON_PREFIX) {
+                handle_board_button(ctx, &component).await;
+            } else if component.data.custom_id.starts_with(CHECKIN_BUTTON_PREFIX) {
+                handle_checkin_button(ctx, &component).await;
+            } else {
+                handle_challenge_button(ctx, &component).await;
+            }
+        }
+        _ => {}
+    }
+}
+
+async fn run_slash_command(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+) -> Result<Reply, Error> {
+    let subcommand = match command.data.options.first() {
+        Some(subcommand) => subcommand,
+        None => return Ok(Reply::error("Unknown command!")),
+    };
+
+    let role = required_role(&command.data.name, &subcommand.name);
+    if !has_role(ctx, command.member.as_ref(), role).await {
+        return Ok(Reply::error(format!(
+            "You need the {} role to use this command.",
+            role
+        )));
+    }
+
+    let invocation = Invocation::from_interaction(ctx, command);
+    let args = SlashArgs(&subcommand.options);
+
+    match command.data.name.as_str() {
+        "ctf" => run_ctf_subcommand(ctx, &invocation, &subcommand.name, &args).await,
+        "htb" => run_htb_subcommand(ctx, &invocation, &subcommand.name, &args).await,
+        _ => Ok(Reply::error("Unknown command!")),
+    }
+}
+
+async fn run_ctf_subcommand(
+    ctx: &Context,
+    invocation: &Invocation,
+    subcommand: &str,
+    args: &SlashArgs<'_>,
+) -> Result<Reply, Error> {
+    match subcommand {
+        "start" => {
+            start_ctf(
+                &args.required_string("name")?,
+                &args.required_string("url")?,
+                &args.required_string("api_key")?,
+                args.id("channel").unwrap_or(0) as i64,
+            )
+            .await
+        }
+        "end" => end_ctf(&args.required_string("ctf")?).await,
+        "endtime" => {
+            set_end_time(
+                &args.required_string("ctf")?,
+                &args.required_string("time")?,
+            )
+            .await
+        }
+        "stale" => {
+            let minutes = args.integer("minutes").unwrap_or_default();
+            set_stale_minutes(&args.required_string("ctf")?, &minutes.to_string()).await
+        }
+        "active" => list_active_ctfs().await,
+        "list" => list_challenges(invocation, args.string("ctf").as_deref()).await,
+        "board" => show_board(invocation, args.string("ctf").as_deref()).await,
+        "working" => mark_working(invocation, &args.required_string("challenge")?).await,
+        "giveup" => give_up(invocation, &args.required_string("challenge")?).await,
+        "search" => search_challenge(invocation, &args.required_string("challenge")?).await,
+        "note" => {
+            add_note(
+                invocation,
+                &args.required_string("challenge")?,
+                &args.required_string("note")?,
+            )
+            .await
+        }
+        "notes" => list_notes(invocation, &args.required_string("challenge")?).await,
+        "writeup" => {
+            let body = WriteupBody::url(&args.required_string("url")?);
+            submit_writeup(
+                invocation,
+                &args.required_string("challenge")?,
+                body,
+                args.string("ctf").as_deref(),
+            )
+            .await
+        }
+        "export" => export_writeups(&args.required_string("ctf")?).await,
+        "stats" => show_stats().await,
+        "template" => {
+            let kind = args.required_string("kind")?;
+            let ctf_name = args.string("ctf");
+
+            match args.required_string("action")?.as_str() {
+                "set" => {
+                    set_template(
+                        invocation,
+                        &kind,
+                        &args.required_string("title")?,
+                        &args.string("description").unwrap_or_default(),
+                        ctf_name.as_deref(),
+                    )
+                    .await
+                }
+                "reset" => reset_template(invocation, &kind, ctf_name.as_deref()).await,
+                _ => preview_template(invocation, &kind, ctf_name.as_deref()).await,
+            }
+        }
+        "route" => {
+            let data = ctx.data.read().await;
+            let teams = data
+                .get::<HTBTeamsContainer>()
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+
+            // HTB routes are for a team rather than a CTF
+            let name = match args.string("source").as_deref() {
+                Some("htb") => args.string("team"),
+                _ => args.string("ctf"),
+            };
+
+            match args.required_string("action")?.as_str() {
+                "add" => {
+                    let channel_id = match args.id("channel") {
+                        Some(channel_id) => ChannelId(channel_id),
+                        None => return Ok(Reply::error("Pick a channel to announce them in")),
+                    };
+
+                    add_route(
+                        invocation,
+                        teams,
+                        &args.required_string("source")?,
+                        &args.required_string("category")?,
+                        channel_id,
+                        args.id("role").map(RoleId),
+                        name.as_deref(),
+                    )
+                    .await
+                }
+                "remove" => {
+                    remove_route(
+                        invocation,
+                        teams,
+                        &args.required_string("source")?,
+                        &args.required_string("category")?,
+                        name.as_deref(),
+                    )
+                    .await
+                }
+                _ => list_routes(invocation, teams).await,
+            }
+        }
+        _ => Ok(Reply::error("Unknown command!")),
+    }
+}
+
+async fn run_htb_subcommand(
+    ctx: &Context,
+    invocation: &Invocation,
+    subcommand: &str,
+    args: &SlashArgs<'_>,
+) -> Result<Reply, Error> {
+    // These aren't tied to a team
+    match subcommand {
+        "link" => {
+            let htb_id = args
+                .integer("htb_id")
+                .ok_or_else(|| format_err!("Missing the htb_id option"))?;
+            return start_htb_link(ctx, invocation, htb_id as i32).await;
+        }
+        "verify" => return verify_htb_link(ctx, invocation).await,
+        "unlink" => return unlink_htb(invocation).await,
+        "links" => return list_htb_links().await,
+        "forcelink" => {
+            let htb_id = args
+                .integer("htb_id")
+                .ok_or_else(|| format_err!("Missing the htb_id option"))?;
+            let discord_id = args
+                .id("user")
+                .ok_or_else(|| format_err!("Missing the user option"))?;
+            return force_htb_link(htb_id as i32, discord_id as i64).await;
+        }
+        _ => {}
+    }
+
+    let team = match resolve_team(ctx, args.string("team").as_deref(), invocation.channel_id).await
+    {
+        Ok(team) => team,
+        Err(reply) => return Ok(reply),
+    };
+
+    match subcommand {
+        "working" => mark_htb_working(&team, invocation, &args.required_string("challenge")?).await,
+        "giveup" => htb_give_up(&team, invocation, &args.required_string("challenge")?).await,
+        "search" => {
+            let filter = HTBSearchFilter {
+                name: args.string("name"),
+                retired: args
+                    .string("status")
+                    .as_deref()
+                    .and_then(HTBSearchFilter::retired_from_status),
+                difficulty: args.string("difficulty"),
+                os: args.string("os"),
+            };
+            search_htb(&team, invocation, &filter).await
+        }
+        "solves" => list_htb_solves(&team, invocation, &args.required_string("username")?).await,
+        "progress" => show_htb_progress(&team).await,
+        "leaderboard" => show_htb_leaderboard(&team).await,
+        "notify" => toggle_htb_notify(ctx, &team, invocation).await,
+        "rank" => match args.integer("days") {
+            Some(days) if days <= 0 => Ok(Reply::error("Days needs to be more than 0!")),
+            days => chart_htb_rank(&team, days.unwrap_or(DEFAULT_RANK_HISTORY_DAYS)).await,
+        },
+        _ => Ok(Reply::error("Unknown command!")),
+    }
+}
+
+/// Sends a command's reply in response to a deferred slash command. The first message fills in
+/// the deferred one, and anything that doesn't fit follows it.
+async fn respond(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    reply: Reply,
+) -> Result<(), Error> {
+    let Reply {
+        mut content,
+        embeds,
+        components,
+        mut attachment,
+        ephemeral,
+        private,
+    } = reply;
+
+    // Slash commands can't DM, but only showing it to whoever asked is close enough
+    let flags = if ephemeral || private {
+        // Whether a deferred reply is shown to everyone is fixed when it's deferred, so it's
+        // removed and the reply is sent fresh instead
+        command
+            .delete_original_interaction_response(&ctx.http)
+            .await?;
+        InteractionApplicationCommandCallbackDataFlags::EPHEMERAL
+    } else {
+        InteractionApplicationCommandCallbackDataFlags::empty()
+    };
+
+    let mut chunks = group_embeds(embeds, components);
+    if chunks.is_empty() {
+        chunks.push(Default::default());
+    }
+
+    for (chunk, rows) in chunks {
+        let content = content.take();
+        let attachment = attachment.take();
+
+        command
+            .create_followup_message(&ctx.http, |message| {
+                if let Some((filename, data)) = attachment {
+                    message.add_file(AttachmentType::Bytes {
+                        data: Cow::from(data),
+                        filename,
+                    });
+                }
+                if let Some(content) = &content {
+                    message.content(content);
+                }
+                for embed in chunk {
+                    message.add_embed(embed);
+                }
+                if !rows.is_empty() {
+                    message.components(|c| add_action_rows(c, rows));
+                }
+                message.flags(flags)
+            })
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Splits embeds into as few messages as Discord allows. Buttons belong to a whole message, so an
+/// embed with buttons gets a message to itself.
+fn group_embeds(
+    embeds: Vec<CreateEmbed>,
+    components: Vec<Vec<CreateActionRow>>,
+) -> Vec<(Vec<CreateEmbed>, Vec<CreateActionRow>)> {
+    let mut components = components.into_iter();
+    let mut messages: Vec<(Vec<CreateEmbed>, Vec<CreateActionRow>)> = vec![];
+
+    for embed in embeds {
+        let rows = components.next().unwrap_or_default();
+
+        match messages.last_mut() {
+            Some((chunk, last_rows))
+                if rows.is_empty()
+                    && last_rows.is_empty()
+                    && chunk.len() < MAX_EMBEDS_PER_MESSAGE =>
+            {
+                chunk.push(embed)
+            }
+            _ => messages.push((vec![embed], rows)),
+        }
+    }
+
+    messages
+}
+
+/// Suggests challenge and CTF names as they're typed.
+async fn respond_to_autocomplete(
+    ctx: &Context,
+    autocomplete: &AutocompleteInteraction,
+) -> Result<(), Error> {
+    let subcommand = match autocomplete.data.options.first() {
+        Some(subcommand) => subcommand,
+        None => return Ok(()),
+    };
+
+    let focused = match subcommand.options.iter().find(|option| option.focused) {
+        Some(focused) => focused,
+        None => return Ok(()),
+    };
+
+    let typed = focused
+        .value
+        .as_ref()
+        .and_then(|value| value.as_str())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let names: Vec<String> = match (autocomplete.data.name.as_str(), focused.name.as_str()) {
+        ("ctf", "challenge") => get_challenges_for_channel(autocomplete.channel_id.0 as i64)
+            .await?
+            .into_iter()
+            .map(|challenge| challenge.name)
+            .collect(),
+        ("ctf", "ctf") => get_active_ctfs()
+            .await?
+            .into_iter()
+            .map(|ctf| ctf.name)
+            .collect(),
+        ("htb", "challenge") | ("htb", "name") => {
+            let selector = SlashArgs(&subcommand.options).string("team");
+
+            match resolve_team(ctx, selector.as_deref(), autocomplete.channel_id).await {
+                Ok(team) => search_for_challenge_by_name(team.team_id, &typed)
+                    .await?
+                    .into_iter()
+                    .map(|challenge| challenge.name)
+                    .collect(),
+                Err(_) => vec![],
+            }
+        }
+        _ => vec![],
+    };
+
+    let choices = names
+        .into_iter()
+        .filter(|name| name.to_lowercase().contains(&typed))
+        .take(MAX_AUTOCOMPLETE_CHOICES);
+
+    autocomplete
+        .create_autocomplete_response(&ctx.http, |response| {
+            for name in choices {
+                response.add_string_choice(&name, &name);
+            }
+            response
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_role() {
+        assert_eq!(required_role("ctf", "start"), "Organiser");
+        assert_eq!(required_role("htb", "forcelink"), "Organiser");
+        assert_eq!(required_role("ctf", "endtime"), "Organiser");
+        assert_eq!(required_role("ctf", "template"), "Organiser");
+        assert_eq!(required_role("ctf", "route"), "Organiser");
+        assert_eq!(required_role("ctf", "stale"), "Organiser");
+        assert_eq!(required_role("ctf", "export"), "Organiser");
+        assert_eq!(required_role("ctf", "writeup"), "CTFer");
+        assert_eq!(required_role("ctf", "working"), "CTFer");
+        assert_eq!(required_role("htb", "rank"), "CTFer");
+    }
+
+    #[test]
+    fn test_group_embeds() {
+        let embeds = vec![CreateEmbed::default(); 13];
+
+        let grouped = group_embeds(embeds.clone(), vec![]);
+        assert_eq!(
+            grouped
+                .iter()
+                .map(|(chunk, _)| chunk.len())
+                .collect::<Vec<_>>(),
+            vec![10, 3]
+        );
+
+        let grouped = group_embeds(
+            embeds.into_iter().take(3).collect(),
+            vec![vec![CreateActionRow::default()], vec![], vec![]],
+        );
+        assert_eq!(
+            grouped
+                .iter()
+                .map(|(chunk, rows)| (chunk.len(), rows.len()))
+                .collect::<Vec<_>>(),
+            vec![(1, 1), (2, 0)]
+        );
+    }
+}
@@ -1,6 +1,24 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
 use async_trait::async_trait;
-use ctfdb::DiscordNameProvider;
-use serenity::http::Http;
+use ctfdb::{
+    ctfs::db::get_challenge_workers,
+    htb::db::get_htb_challenge_workers,
+    models::{ChallengeWorker, HTBChallengeWorker},
+    DiscordNameProvider,
+};
+use dashmap::DashMap;
+use failure::Error;
+use once_cell::sync::Lazy;
+use serenity::{http::Http, model::id::GuildId};
+
+/// How long a looked up name is used before asking Discord again.
+const NAME_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Names already looked up by guild and Discord ID, with when. ``None`` is kept too, so someone
+/// who's left isn't looked up on every render.
+static NAME_CACHE: Lazy<DashMap<(u64, i64), (Option<String>, Instant)>> = Lazy::new(DashMap::new);
 
 pub struct AsyncDiscordNameProvider<'a> {
     pub http: &'a Http,
@@ -18,3 +36,166 @@ impl DiscordNameProvider for AsyncDiscordNameProvider<'_> {
         None
     }
 }
+
+/// Someone down as working on a challenge, from either a CTF or HTB.
+pub struct Worker {
+    pub challenge_id: i32,
+    pub discord_id: i64,
+    /// The name they last used, for when Discord can't tell us their current one.
+    pub name: String,
+}
+
+impl From<&ChallengeWorker> for Worker {
+    fn from(worker: &ChallengeWorker) -> Self {
+        Worker {
+            challenge_id: worker.challenge_id,
+            discord_id: worker.discord_id,
+            name: worker.name.clone(),
+        }
+    }
+}
+
+impl From<&HTBChallengeWorker> for Worker {
+    fn from(worker: &HTBChallengeWorker) -> Self {
+        Worker {
+            challenge_id: worker.challenge_id,
+            discord_id: worker.discord_id,
+            name: worker.name.clone(),
+        }
+    }
+}
+
+/// Someone's display name in the guild, only asking Discord when it isn't cached or is stale.
+async fn cached_name(http: &Http, guild_id: GuildId, discord_id: i64) -> Option<String> {
+    let key = (guild_id.0, discord_id);
+
+    if let Some(cached) = NAME_CACHE.get(&key) {
+        let (name, looked_up) = cached.value();
+        if looked_up.elapsed() < NAME_CACHE_TTL {
+            return name.clone();
+        }
+    }
+
+    let name = AsyncDiscordNameProvider {
+        http,
+        guild_id: guild_id.0,
+    }
+    .name_for_id(discord_id)
+    .await;
+
+    NAME_CACHE.insert(key, (name.clone(), Instant::now()));
+    name
+}
+
+/// Everyone's display name in the guild, looking each person up at most once.
+pub async fn resolve_names(
+    http: &Http,
+    guild_id: Option<GuildId>,
+    workers: &[Worker],
+) -> HashMap<i64, String> {
+    let mut names = HashMap::new();
+
+    for worker in workers {
+        if names.contains_key(&worker.discord_id) {
+            continue;
+        }
+
+        let name = match guild_id {
+            Some(guild_id) => cached_name(http, guild_id, worker.discord_id).await,
+            None => None,
+        };
+
+        names.insert(
+            worker.discord_id,
+            name.unwrap_or_else(|| worker.name.clone()),
+        );
+    }
+
+    names
+}
+
+/// Who's working on each challenge, in the order they picked it up.
+pub fn working_by_challenge(
+    workers: &[Worker],
+    names: &HashMap<i64, String>,
+) -> HashMap<i32, String> {
+    let mut working: HashMap<i32, Vec<&str>> = HashMap::new();
+
+    for worker in workers {
+        let name = names
+            .get(&worker.discord_id)
+            .map(String::as_str)
+            .unwrap_or(&worker.name);
+        working.entry(worker.challenge_id).or_default().push(name);
+    }
+
+    working
+        .into_iter()
+        .map(|(challenge_id, names)| (challenge_id, names.join(", ")))
+        .collect()
+}
+
+/// Who's working on each of the CTF challenges, by their names in the guild.
+pub async fn ctf_working(
+    http: &Http,
+    guild_id: Option<GuildId>,
+    challenge_ids: &[i32],
+) -> Result<HashMap<i32, String>, Error> {
+    let workers: Vec<Worker> = get_challenge_workers(challenge_ids)
+        .await?
+        .iter()
+        .map(Worker::from)
+        .collect();
+    let names = resolve_names(http, guild_id, &workers).await;
+
+    Ok(working_by_challenge(&workers, &names))
+}
+
+/// Who's working on each of the HTB challenges, by their names in the guild.
+pub async fn htb_working(
+    http: &Http,
+    guild_id: Option<GuildId>,
+    challenge_ids: &[i32],
+) -> Result<HashMap<i32, String>, Error> {
+    let workers: Vec<Worker> = get_htb_challenge_workers(challenge_ids)
+        .await?
+        .iter()
+        .map(Worker::from)
+        .collect();
+    let names = resolve_names(http, guild_id, &workers).await;
+
+    Ok(working_by_challenge(&workers, &names))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn worker(challenge_id: i32, discord_id: i64, name: &str) -> Worker {
+        Worker {
+            challenge_id,
+            discord_id,
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_working_by_challenge() {
+        let workers = vec![
+            worker(1, 10, "old name"),
+            worker(1, 11, "bob"),
+            worker(2, 10, "old name"),
+            worker(3, 12, "carol"),
+        ];
+
+        let mut names = HashMap::new();
+        names.insert(10, "alice".to_string());
+
+        let working = working_by_challenge(&workers, &names);
+
+        assert_eq!(working.get(&1).map(String::as_str), Some("alice, bob"));
+        assert_eq!(working.get(&2).map(String::as_str), Some("alice"));
+        assert_eq!(working.get(&3).map(String::as_str), Some("carol"));
+        assert_eq!(working.get(&4), None);
+    }
+}
@@ -1,42 +1,103 @@
 #[macro_use]
 extern crate failure;
 
+use std::collections::HashMap;
+
 use chrono::{DateTime, Local};
 use failure::Error;
 use futures::executor::block_on;
 use serenity::{
-    builder::CreateEmbed, framework::standard::CommandResult, http::Http, model::id::ChannelId,
+    builder::CreateEmbed,
+    framework::standard::CommandResult,
+    http::Http,
+    model::id::{ChannelId, GuildId, MessageId, RoleId, UserId},
 };
+use tokio::sync::Mutex;
 
 use ctfdb::{
-    ctfs::db::{
-        check_for_new_solves, get_active_ctfs, get_and_store_scoreboard, mark_solved,
-        update_challenges_and_scores, CTF_CACHE,
+    announcements::{RouteSource, TemplateKind},
+    ctfs::{
+        db::{
+            check_for_new_solves, get_active_ctfs, get_and_store_scoreboard, get_category_thread,
+            get_challenge_workers, get_challenges_for_ctfid, get_challenges_without_thread,
+            get_ctf_from_id, mark_solved, set_challenge_thread, set_challenge_thread_message,
+            update_challenges_and_scores, CTF_CACHE,
+        },
+        structs::MyTeamResponseData,
     },
     htb::{
         db::{
-            add_challenge_announced_for_user, get_latest_rank_from_db, get_solves_to_announce,
-            get_solving_users_for_challenge, insert_rank_into_db, process_new_solves,
-            update_htb_challenges_and_scores, CATEGORY_CACHE,
+            add_challenge_announced_for_user, get_all_user_mappings, get_flag_captures_to_announce,
+            get_latest_rank_from_db, get_new_content_to_announce, get_releases_to_announce,
+            get_releases_to_remind, get_solves_to_announce, get_solving_users_for_challenge,
+            insert_rank_into_db, process_new_solves, set_content_announced,
+            set_flag_capture_announced, set_release_countdown_announced, set_release_reminder_sent,
+            update_htb_challenges_and_scores, update_htb_retired_catalogue,
+            update_htb_scheduled_releases, update_htb_tracked_content,
+            update_team_member_snapshots, CATEGORY_CACHE,
         },
-        structs::{HTBApi, RankStatsData, SolveToAnnounce},
+        structs::{FlagCaptureToAnnounce, HTBApi, RankStatsData, SolveToAnnounce},
     },
-    models::{Challenge, HTBChallenge},
+    models::{Challenge, Ctf, HTBChallenge, HTBScheduledRelease, HTBUserMapping},
     ChallengeProvider, DiscordNameProvider,
 };
 
+use crate::commands::buttons::{challenge_buttons, named_challenge_buttons};
+use crate::discord_name_provider::{resolve_names, Worker};
+use crate::routing::{destination_for, load_routes, team_routes, Destination};
+use crate::status_board::{join_within, update_status_board, MAX_FIELD_LENGTH};
+use crate::templates::{format_duration, render_announcement, TemplateValues};
+
+pub mod charts;
 pub mod commands;
 pub mod discord_name_provider;
+pub mod routing;
+pub mod stale_workers;
+pub mod status_board;
+pub mod teams;
+pub mod templates;
 
 pub type ChallengeProviderService = Box<dyn ChallengeProvider + Send + Sync>;
 
-pub fn populate_embed_from_challenge(challenge: Challenge, e: &mut CreateEmbed) {
+/// The embed field listing who's working on a challenge, swapped out when the buttons are used.
+pub const WORKING_FIELD_NAME: &str = "🧰 Working";
+
+/// Discord won't take a thread name longer than this.
+const MAX_THREAD_NAME_LENGTH: usize = 100;
+
+/// Challenge threads are archived after a day without messages, the longest every server allows.
+const THREAD_ARCHIVE_MINUTES: u16 = 1440;
+
+/// How threads are opened for discussing a CTF's challenges.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThreadMode {
+    PerChallenge,
+    PerCategory,
+    Disabled,
+}
+
+impl ThreadMode {
+    /// Parses ``CTF_THREADS``, which opens a thread per challenge unless it says otherwise.
+    pub fn from_config(config: &str) -> Self {
+        match config.trim().to_lowercase().as_str() {
+            "category" => ThreadMode::PerCategory,
+            "none" | "off" => ThreadMode::Disabled,
+            _ => ThreadMode::PerChallenge,
+        }
+    }
+}
+
+pub fn populate_embed_from_challenge(
+    challenge: Challenge,
+    working: Option<&str>,
+    e: &mut CreateEmbed,
+) {
     e.title(format!("❓ {} ❓", challenge.name));
     e.field("📚 Category", &challenge.category, true);
     e.field("💰 Points", challenge.points, true);
 
-    if challenge.working.is_some() {
-        e.field("🧰 Working", challenge.working.unwrap(), true);
+    if let Some(working) = working {
+        e.field(WORKING_FIELD_NAME, working, true);
     }
 
     if challenge.solved && challenge.solver.is_some() {
@@ -44,25 +105,59 @@ pub fn populate_embed_from_challenge(challenge: Challenge, e: &mut CreateEmbed)
     }
 }
 
-pub fn populate_embed_from_htb_challenge(challenge: HTBChallenge, e: &mut CreateEmbed) {
-    let challenge_category_name = get_challenge_category_from_id(challenge.challenge_category);
+pub fn populate_embed_from_htb_challenge(
+    challenge: HTBChallenge,
+    working: Option<&str>,
+    e: &mut CreateEmbed,
+) {
+    let challenge_category_name = get_htb_category_name(&challenge);
 
     e.title(format!("❓ {} ❓", challenge.name));
     e.field("📚 Category", &challenge_category_name, true);
     e.field("💰 Points", challenge.points, true);
+    e.field("🎯 Difficulty", &challenge.difficulty, true);
+
+    if let Some(os) = &challenge.os {
+        e.field("💻 OS", os, true);
+    }
 
-    if challenge.working.is_some() {
-        e.field("🧰 Working", challenge.working.unwrap(), true);
+    if let Some(release_date) = challenge.release_date {
+        e.field("📅 Released", release_date.format("%e %b %Y"), true);
     }
 
-    if let Ok(solving_users) = block_on(get_solving_users_for_challenge(challenge.htb_id)) {
-        let solving_string = solving_users.join(", ");
-        e.field("🏴‍ Solved", solving_string, true);
+    if challenge.retired {
+        let retired_date = match challenge.retired_date {
+            Some(retired_date) => retired_date.format("%e %b %Y").to_string(),
+            None => "Yes".to_string(),
+        };
+        e.field("🪦 Retired", retired_date, true);
+    }
+
+    if let Some(working) = working {
+        e.field("🧰 Working", working, true);
+    }
+
+    if let Ok(solving_users) = block_on(get_solving_users_for_challenge(
+        challenge.team_id,
+        challenge.htb_id,
+    )) {
+        e.field(
+            "🏴‍ Solved",
+            join_within(solving_users, ", ", MAX_FIELD_LENGTH),
+            true,
+        );
     }
 }
 
-pub fn get_challenge_category_from_id(challenge_category_id: i32) -> String {
-    match CATEGORY_CACHE.get(&challenge_category_id) {
+pub fn get_htb_category_name(challenge: &HTBChallenge) -> String {
+    if challenge.is_machine() {
+        return "Machine".to_string();
+    }
+
+    match challenge
+        .challenge_category
+        .and_then(|category| CATEGORY_CACHE.get(&category))
+    {
         Some(cached) => cached.value().clone(),
         None => "Unknown".to_string(),
     }
@@ -70,28 +165,123 @@ pub fn get_challenge_category_from_id(challenge_category_id: i32) -> String {
 
 pub async fn create_embed_of_challenge_solved(
     challenge: &Challenge,
-    channel_id: &ChannelId,
+    ctf: &Ctf,
+    destination: &Destination,
+    content: Option<String>,
     http: &Http,
+    guild_id: GuildId,
     scoreboard_position: String,
     score: i32,
 ) -> CommandResult {
-    // This should never not be populated
-    let solver_name = challenge.solver.as_ref().unwrap();
+    let values = TemplateValues {
+        // This should never not be populated
+        solver: challenge.solver.clone().unwrap_or_default(),
+        challenge: challenge.name.clone(),
+        category: challenge.category.clone(),
+        points: challenge.points,
+        position: scoreboard_position,
+        score: score.to_string(),
+        flag: "🏴",
+        blood: String::new(),
+        time_since_start: format_duration(Local::now().naive_local() - ctf.started_at),
+    };
 
-    channel_id
+    let (title, description) =
+        render_announcement(guild_id, Some(ctf.id), TemplateKind::CtfSolve, &values).await;
+
+    destination
+        .channel_id
         .send_message(http, |message| {
+            if let Some(content) = content {
+                message.content(content);
+            }
+
             message.embed(|e| {
-                e.title(format!(
-                    "🏴‍ {} has been solved by {}‍",
-                    challenge.name, solver_name
-                ));
-                e.description(format!(
-                    "📈 New team position: {}, Total score: {}",
-                    scoreboard_position, score
-                ));
+                e.title(title);
+                if let Some(description) = description {
+                    e.description(description);
+                }
                 e.field("📚 Category", &challenge.category, true);
                 e.field("💰 Points", &challenge.points, true);
                 e
+            });
+            message.components(|c| c.add_action_row(challenge_buttons(challenge.id)))
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Discord won't take more rows of buttons than this on one message, and each solve gets a row.
+const MAX_SOLVES_PER_MESSAGE: usize = 5;
+
+fn solve_summary(challenge: &Challenge) -> String {
+    format!(
+        "📚 {} - 💰 {} - by {}",
+        challenge.category,
+        challenge.points,
+        challenge.solver.as_deref().unwrap_or("unknown")
+    )
+}
+
+/// Announces one message's worth of a group of solves from the same poll, each headed by the
+/// CTF's solve template and keeping its buttons. The group's first message is given its size, and
+/// carries the ping and the team's totals.
+pub async fn create_embed_of_challenges_solved(
+    challenges: &[Challenge],
+    group_size: Option<usize>,
+    ctf: &Ctf,
+    destination: &Destination,
+    content: Option<String>,
+    http: &Http,
+    guild_id: GuildId,
+    team_stats: &MyTeamResponseData,
+) -> CommandResult {
+    let time_since_start = format_duration(Local::now().naive_local() - ctf.started_at);
+
+    let mut headings = Vec::with_capacity(challenges.len());
+    for challenge in challenges {
+        let values = TemplateValues {
+            solver: challenge.solver.clone().unwrap_or_default(),
+            challenge: challenge.name.clone(),
+            category: challenge.category.clone(),
+            points: challenge.points,
+            position: team_stats.place.clone(),
+            score: team_stats.score.to_string(),
+            flag: "🏴",
+            blood: String::new(),
+            time_since_start: time_since_start.clone(),
+        };
+        let (title, _) =
+            render_announcement(guild_id, Some(ctf.id), TemplateKind::CtfSolve, &values).await;
+        headings.push(title);
+    }
+
+    destination
+        .channel_id
+        .send_message(http, |message| {
+            if let Some(content) = &content {
+                message.content(content);
+            }
+
+            message.embed(|e| {
+                if let Some(group_size) = group_size {
+                    e.title(format!("🏴‍ {} challenges have been solved", group_size));
+                    e.description(format!(
+                        "📈 New team position: {}, Total score: {}",
+                        team_stats.place, team_stats.score
+                    ));
+                }
+                for (challenge, heading) in challenges.iter().zip(headings) {
+                    e.field(heading, solve_summary(challenge), false);
+                }
+                e
+            });
+            message.components(|c| {
+                for challenge in challenges {
+                    c.add_action_row(named_challenge_buttons(challenge));
+                }
+                c
             })
         })
         .await?;
@@ -99,47 +289,118 @@ pub async fn create_embed_of_challenge_solved(
     Ok(())
 }
 
-// Cheeky little function to capitalise the first char of a string.
-fn capitalise_first(s: &str) -> String {
-    let mut c = s.chars();
-    match c.next() {
-        None => String::new(),
-        Some(f) => f.to_uppercase().chain(c).collect(),
+/// Everyone still working on a challenge other than whoever solved it, to be pinged about the solve.
+fn other_workers(
+    challenge: &Challenge,
+    workers: &[Worker],
+    names: &HashMap<i64, String>,
+) -> Vec<i64> {
+    let solver = challenge.solver.as_deref().unwrap_or_default();
+
+    workers
+        .iter()
+        .filter(|worker| worker.challenge_id == challenge.id)
+        .filter(|worker| {
+            let name = names.get(&worker.discord_id).unwrap_or(&worker.name);
+            !name.eq_ignore_ascii_case(solver) && !worker.name.eq_ignore_ascii_case(solver)
+        })
+        .map(|worker| worker.discord_id)
+        .collect()
+}
+
+/// What's sent alongside a solve announcement, pinging the route's role and telling anyone else
+/// working on the solved challenges to move on.
+fn solve_content(role_ping: Option<String>, workers: &[(String, Vec<String>)]) -> Option<String> {
+    let mut lines: Vec<String> = role_ping.into_iter().collect();
+
+    for (challenge, mentions) in workers {
+        if !mentions.is_empty() {
+            lines.push(format!(
+                "{} '{}' has been solved, time to move on to something else!",
+                mentions.join(" "),
+                challenge
+            ));
+        }
+    }
+
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+/// What a blood is called, e.g. ``First blood``. Only the first few owns are bloods, see
+/// ``BLOOD_POSITIONS``, so there isn't one for anything later.
+fn blood_name(position: i32) -> Option<&'static str> {
+    match position {
+        1 => Some("First blood"),
+        2 => Some("Second blood"),
+        3 => Some("Third blood"),
+        _ => None,
     }
 }
 
 pub async fn create_embed_of_htb_challenge_solved(
     solve: &SolveToAnnounce,
-    channel_id: &ChannelId,
+    destination: &Destination,
     http: &Http,
+    guild_id: GuildId,
 ) -> CommandResult {
     let challenge = &solve.challenge;
-    // This should never not be populated
-    let challenge_category_name = get_challenge_category_from_id(challenge.challenge_category);
+    let challenge_category_name = get_htb_category_name(challenge);
 
-    let solve_type = capitalise_first(&solve.solve_type);
+    let blood = solve.blood_position.and_then(blood_name);
 
-    // Build up the content based around the solve type
-    let content;
-    if solve_type.eq("Challenge") {
-        content = format!(
-            "🏴 {} has been solved by {}",
-            &challenge.name, &solve.solver
-        );
-    } else {
-        content = format!(
-            "🏴 {} has been owned by {} on {}",
-            solve_type, &solve.solver, &challenge.name
-        );
-    }
+    // Bloods get a different flag so they stand out in the channel
+    let flag = if blood.is_some() { "🩸" } else { "🏴" };
 
-    channel_id
+    let (position, score) = match get_latest_rank_from_db(challenge.team_id).await {
+        Ok(rank) => (rank.rank.to_string(), rank.points.to_string()),
+        Err(_) => ("Unknown".to_string(), "Unknown".to_string()),
+    };
+
+    let values = TemplateValues {
+        solver: solve.solver.clone(),
+        challenge: challenge.name.clone(),
+        category: challenge_category_name.clone(),
+        points: challenge.points,
+        position,
+        score,
+        flag,
+        blood: blood.unwrap_or_default().to_string(),
+        time_since_start: challenge
+            .release_date
+            .map(|release_date| format_duration(Local::now().naive_local() - release_date))
+            .unwrap_or_default(),
+    };
+
+    let kind = TemplateKind::from_htb_solve_type(&solve.solve_type);
+    let (title, description) = render_announcement(guild_id, None, kind, &values).await;
+
+    destination
+        .channel_id
         .send_message(http, |message| {
+            if let Some(ping) = destination.ping() {
+                message.content(ping);
+            }
+
             message.embed(|e| {
-                e.title(content);
+                e.title(title);
+                if let Some(description) = description {
+                    e.description(description);
+                }
                 e.field("📚 Category", &challenge_category_name, true);
                 e.field("💰 Points", &challenge.points, true);
 
+                if let Some(blood) = blood {
+                    e.field("🩸 Blood", blood, true);
+                }
+
+                if solve.team_first {
+                    e.field("🥇 Team first", "First in the team!", true);
+                }
+
                 if let Some(avatar) = &solve.challenge.machine_avatar {
                     e.thumbnail(format!("https://www.hackthebox.eu/{}", avatar));
                 }
@@ -152,24 +413,481 @@ pub async fn create_embed_of_htb_challenge_solved(
     Ok(())
 }
 
-async fn process_solve(
-    ctfd_service: &ChallengeProviderService,
-    solve: Challenge,
+pub async fn create_embed_of_htb_flag_captured(
+    capture: &FlagCaptureToAnnounce,
+    destination: &Destination,
+    http: &Http,
+) -> CommandResult {
+    let content = &capture.content;
+    let content_type_name = content.content_type.display_name();
+
+    destination
+        .channel_id
+        .send_message(http, |message| {
+            if let Some(ping) = destination.ping() {
+                message.content(ping);
+            }
+
+            message.embed(|e| {
+                e.title(format!(
+                    "🚩 {} has captured '{}' on {}",
+                    &capture.solver, &capture.flag_title, &content.name
+                ));
+                e.field("📚 Category", content_type_name, true);
+                e.field("💰 Points", capture.points, true);
+                e.field(
+                    "📈 Team progress",
+                    format!("{}/{} flags", capture.flags_captured, content.total_flags),
+                    true,
+                );
+                e
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// How long before a machine releases to ping the release role.
+pub const RELEASE_REMINDER_MINUTES: i64 = 30;
+
+pub async fn create_embed_of_new_htb_content(
+    challenge: &HTBChallenge,
+    channel_id: &ChannelId,
+    http: &Http,
+) -> CommandResult {
+    let challenge_category_name = get_htb_category_name(challenge);
+
+    let title = if challenge.is_machine() {
+        format!("🆕 New machine: {}", &challenge.name)
+    } else {
+        format!(
+            "🆕 New {} challenge: {}",
+            challenge_category_name, &challenge.name
+        )
+    };
+
+    channel_id
+        .send_message(http, |message| {
+            message.embed(|e| {
+                e.title(title);
+                e.field("🎯 Difficulty", &challenge.difficulty, true);
+
+                if let Some(os) = &challenge.os {
+                    e.field("💻 OS", os, true);
+                }
+
+                e.field("💰 Points", &challenge.points, true);
+
+                if let Some(avatar) = &challenge.machine_avatar {
+                    e.thumbnail(format!("https://www.hackthebox.eu/{}", avatar));
+                }
+
+                e
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+fn populate_embed_from_htb_release(release: &HTBScheduledRelease, e: &mut CreateEmbed) {
+    // Discord renders these in everyone's own timezone, and the relative one counts down by itself
+    let timestamp = release.release_time.timestamp();
+
+    e.description(format!(
+        "Releases <t:{}:F> (<t:{}:R>)",
+        timestamp, timestamp
+    ));
+    e.field("🎯 Difficulty", &release.difficulty, true);
+    e.field("💻 OS", &release.os, true);
+    e.thumbnail(format!("https://www.hackthebox.eu/{}", &release.avatar));
+}
+
+pub async fn create_embed_of_htb_scheduled_release(
+    release: &HTBScheduledRelease,
+    channel_id: &ChannelId,
+    http: &Http,
+) -> CommandResult {
+    channel_id
+        .send_message(http, |message| {
+            message.embed(|e| {
+                e.title(format!("📅 {} is coming to HTB", &release.name));
+                populate_embed_from_htb_release(release, e);
+                e
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub async fn create_embed_of_htb_release_reminder(
+    release: &HTBScheduledRelease,
+    release_role: Option<RoleId>,
+    channel_id: &ChannelId,
+    http: &Http,
+) -> CommandResult {
+    channel_id
+        .send_message(http, |message| {
+            if let Some(role) = release_role {
+                message.content(format!("<@&{}>", role.0));
+            }
+
+            message.embed(|e| {
+                e.title(format!("⏰ {} is releasing soon!", &release.name));
+                populate_embed_from_htb_release(release, e);
+                e
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Parses the HTB rank to discord role map from ``HTB_RANK_ROLES``, e.g. ``Script Kiddie=123,Hacker=456``.
+/// Anything that can't be parsed is skipped.
+pub fn parse_rank_roles(config: &str) -> Vec<(String, RoleId)> {
+    config
+        .split(',')
+        .filter_map(|entry| {
+            let (rank, role_id) = entry.split_once('=')?;
+            let role_id = role_id.trim().parse::<u64>().ok()?;
+            Some((rank.trim().to_string(), RoleId(role_id)))
+        })
+        .collect()
+}
+
+pub async fn create_embed_of_htb_promotion(
+    htb_name: &str,
+    rank: &str,
+    discord_id: i64,
+    channel_id: &ChannelId,
+    http: &Http,
+) -> CommandResult {
+    channel_id
+        .send_message(http, |message| {
+            message.embed(|e| {
+                e.title(format!("🎉 {} has been promoted to {}!", htb_name, rank));
+                e.description(format!("Congratulations <@{}>!", discord_id));
+                e
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+fn thread_name(prefix: Option<&str>, name: &str) -> String {
+    let name = match prefix {
+        Some(prefix) => format!("{} {}", prefix, name),
+        None => name.to_string(),
+    };
+
+    name.chars().take(MAX_THREAD_NAME_LENGTH).collect()
+}
+
+/// Posts the challenge's details in the channel and opens a thread on them. The message is saved
+/// before the thread is opened, so a failed attempt only retries the thread.
+async fn open_challenge_thread(
+    challenge: &Challenge,
     channel_id: &ChannelId,
+    name: String,
+    http: &Http,
+) -> Result<ChannelId, Error> {
+    let message_id = match challenge.thread_message_id {
+        Some(message_id) => MessageId(message_id as u64),
+        None => {
+            let message = channel_id
+                .send_message(http, |message| {
+                    message.embed(|e| {
+                        populate_embed_from_challenge(challenge.clone(), None, e);
+                        e
+                    });
+                    message.components(|c| c.add_action_row(challenge_buttons(challenge.id)))
+                })
+                .await?;
+
+            set_challenge_thread_message(challenge.id, message.id.0 as i64).await?;
+            message.id
+        }
+    };
+
+    let thread = channel_id
+        .create_public_thread(http, message_id, |thread| {
+            thread
+                .name(name)
+                .auto_archive_duration(THREAD_ARCHIVE_MINUTES)
+        })
+        .await?;
+
+    Ok(thread.id)
+}
+
+/// Opens threads for any of the CTF's challenges that don't have one yet, which is all of them
+/// when the CTF has just started.
+async fn create_challenge_threads(
+    ctf: &Ctf,
+    thread_mode: ThreadMode,
     http: &Http,
 ) -> Result<(), Error> {
-    let team_stats = ctfd_service.team_stats().await?;
+    let channel_id = ChannelId(ctf.channel_id as u64);
 
-    // Only try to create an embed if the channel ID isn't 0
-    if channel_id.0 != 0 {
-        if let Err(why) = create_embed_of_challenge_solved(
-            &solve,
-            channel_id,
+    for challenge in get_challenges_without_thread(ctf.id).await? {
+        if let Err(why) =
+            create_challenge_thread(ctf, &challenge, &channel_id, thread_mode, http).await
+        {
+            eprintln!(
+                "Error when creating thread for '{}'... {}",
+                challenge.name, why
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Opens a challenge's thread, or posts it in its category's thread when they're shared.
+async fn create_challenge_thread(
+    ctf: &Ctf,
+    challenge: &Challenge,
+    channel_id: &ChannelId,
+    thread_mode: ThreadMode,
+    http: &Http,
+) -> Result<(), Error> {
+    let thread_id = match thread_mode {
+        ThreadMode::PerCategory => match get_category_thread(ctf.id, &challenge.category).await? {
+            Some(thread_id) => {
+                let thread_id = ChannelId(thread_id as u64);
+                thread_id
+                    .send_message(http, |message| {
+                        message.embed(|e| {
+                            populate_embed_from_challenge(challenge.clone(), None, e);
+                            e
+                        });
+                        message.components(|c| c.add_action_row(challenge_buttons(challenge.id)))
+                    })
+                    .await?;
+                thread_id
+            }
+            None => {
+                let name = thread_name(None, &challenge.category);
+                open_challenge_thread(challenge, channel_id, name, http).await?
+            }
+        },
+        _ => {
+            let name = thread_name(None, &challenge.name);
+            open_challenge_thread(challenge, channel_id, name, http).await?
+        }
+    };
+
+    set_challenge_thread(challenge.id, thread_id.0 as i64).await?;
+
+    Ok(())
+}
+
+/// Marks the solve in its thread, then flags and archives it. Threads shared by a category stay
+/// open until the whole category is solved.
+async fn close_challenge_thread(
+    solve: &Challenge,
+    thread_mode: ThreadMode,
+    http: &Http,
+) -> Result<(), Error> {
+    let thread_id = match solve.thread_id {
+        Some(thread_id) => ChannelId(thread_id as u64),
+        None => return Ok(()),
+    };
+
+    let solver_name = solve.solver.as_deref().unwrap_or("the team");
+
+    // Posting in an archived thread unarchives it, so this has to go first
+    thread_id
+        .say(
             http,
-            team_stats.place,
-            team_stats.score,
+            format!("🏴‍ {} has been solved by {}", solve.name, solver_name),
         )
-        .await
+        .await?;
+
+    let name = match thread_mode {
+        ThreadMode::PerCategory => {
+            let category_solved = get_challenges_for_ctfid(solve.ctf_id)
+                .await?
+                .iter()
+                .filter(|challenge| challenge.category == solve.category)
+                .all(|challenge| challenge.solved);
+
+            if !category_solved {
+                return Ok(());
+            }
+
+            thread_name(Some("🏴"), &solve.category)
+        }
+        _ => thread_name(Some("🏴"), &solve.name),
+    };
+
+    thread_id
+        .edit_thread(http, |thread| thread.name(name).archived(true))
+        .await?;
+
+    Ok(())
+}
+
+/// Marks announced solves as processed and closes their threads.
+async fn finish_solves(solves: &[Challenge], thread_mode: ThreadMode, http: &Http) {
+    for solve in solves {
+        // If it makes it to this point, it will mark it as 'announced_solved' which basically means "processed"
+        if let Err(why) = mark_solved(solve).await {
+            eprintln!("Error when marking '{}' as solved... {}", solve.name, why);
+            continue;
+        }
+
+        if let Err(why) = close_challenge_thread(solve, thread_mode, http).await {
+            eprintln!("Error when closing thread for '{}'... {}", solve.name, why);
+        }
+    }
+}
+
+/// Announces a cycle's solves with one team stats fetch, in the channel each one's category is
+/// routed to. A lone solve gets its own embed, several for the same channel are grouped together.
+/// Solves are marked as each message announcing them is sent, so a failure part way through a
+/// group only leaves the unsent ones to try again.
+async fn process_solves(
+    ctfd_service: &ChallengeProviderService,
+    ctf: &Ctf,
+    solves: Vec<Challenge>,
+    thread_mode: ThreadMode,
+    guild_id: GuildId,
+    http: &Http,
+) -> Result<(), Error> {
+    let team_stats = ctfd_service.team_stats().await?;
+    let routes = load_routes(guild_id, RouteSource::Ctf).await;
+    let fallback = ChannelId(ctf.channel_id as u64);
+
+    let solve_ids: Vec<i32> = solves.iter().map(|solve| solve.id).collect();
+    let workers: Vec<Worker> = match get_challenge_workers(&solve_ids).await {
+        Ok(workers) => workers.iter().map(Worker::from).collect(),
+        Err(why) => {
+            eprintln!("Error loading who's working on the solves... {}", why);
+            vec![]
+        }
+    };
+    let names = resolve_names(http, Some(guild_id), &workers).await;
+
+    let mut groups: Vec<(Destination, Vec<Challenge>)> = vec![];
+    for solve in solves {
+        let destination = destination_for(&routes, Some(ctf.id), &[&solve.category], fallback);
+
+        match groups.iter_mut().find(|(group, _)| *group == destination) {
+            Some((_, group_solves)) => group_solves.push(solve),
+            None => groups.push((destination, vec![solve])),
+        }
+    }
+
+    let mut failed = 0;
+    for (destination, solves) in groups {
+        // Only try to create an embed if the channel ID isn't 0
+        if destination.channel_id.0 != 0 {
+            let mentions: Vec<(String, Vec<String>)> = solves
+                .iter()
+                .map(|solve| {
+                    let mentions = other_workers(solve, &workers, &names)
+                        .into_iter()
+                        .map(|discord_id| format!("<@{}>", discord_id))
+                        .collect();
+                    (solve.name.clone(), mentions)
+                })
+                .collect();
+            let content = solve_content(destination.ping(), &mentions);
+
+            if let [solve] = solves.as_slice() {
+                let announced = create_embed_of_challenge_solved(
+                    solve,
+                    ctf,
+                    &destination,
+                    content,
+                    http,
+                    guild_id,
+                    team_stats.place.clone(),
+                    team_stats.score,
+                )
+                .await;
+
+                // Left unmarked so it's tried again next cycle
+                if let Err(why) = announced {
+                    eprintln!(
+                        "Error when creating embed for challenge solve in {}... {}",
+                        destination.channel_id, why
+                    );
+                    failed += 1;
+                    continue;
+                }
+            } else {
+                let mut content = content;
+
+                for (index, chunk) in solves.chunks(MAX_SOLVES_PER_MESSAGE).enumerate() {
+                    let group_size = if index == 0 { Some(solves.len()) } else { None };
+                    let announced = create_embed_of_challenges_solved(
+                        chunk,
+                        group_size,
+                        ctf,
+                        &destination,
+                        content.take(),
+                        http,
+                        guild_id,
+                        &team_stats,
+                    )
+                    .await;
+
+                    // The rest of the group is left unmarked so it's tried again next cycle,
+                    // the messages already sent stay announced
+                    if let Err(why) = announced {
+                        eprintln!(
+                            "Error when creating embed for challenge solves in {}... {}",
+                            destination.channel_id, why
+                        );
+                        failed += solves.len() - index * MAX_SOLVES_PER_MESSAGE;
+                        break;
+                    }
+
+                    finish_solves(chunk, thread_mode, http).await;
+                }
+
+                continue;
+            }
+        }
+
+        finish_solves(&solves, thread_mode, http).await;
+    }
+
+    if failed > 0 {
+        return Err(format_err!("{} solve(s) couldn't be announced", failed));
+    }
+
+    Ok(())
+}
+
+async fn process_htb_solve(
+    solve: SolveToAnnounce,
+    channel_id: &ChannelId,
+    guild_id: GuildId,
+    http: &Http,
+) -> Result<(), Error> {
+    let routes = team_routes(
+        load_routes(guild_id, RouteSource::HTB).await,
+        solve.challenge.team_id,
+    );
+    let category = get_htb_category_name(&solve.challenge);
+    let content_type = if solve.challenge.is_machine() {
+        "machine"
+    } else {
+        "challenge"
+    };
+    let destination = destination_for(&routes, None, &[&category, content_type], *channel_id);
+
+    // Only try to create an embed if the channel ID isn't 0
+    if destination.channel_id.0 != 0 {
+        if let Err(why) =
+            create_embed_of_htb_challenge_solved(&solve, &destination, http, guild_id).await
         {
             return Err(format_err!(
                 "Error when creating embed for challenge solve: {}",
@@ -179,28 +897,89 @@ async fn process_solve(
     }
 
     // If it makes it to this point, it will mark it as 'announced_solved' which basically means "processed"
-    mark_solved(&solve).await?;
+    add_challenge_announced_for_user(&solve).await?;
 
     Ok(())
 }
 
-async fn process_htb_solve(
-    solve: SolveToAnnounce,
+async fn process_htb_flag_capture(
+    capture: FlagCaptureToAnnounce,
+    channel_id: &ChannelId,
+    guild_id: GuildId,
+    http: &Http,
+) -> Result<(), Error> {
+    let routes = team_routes(
+        load_routes(guild_id, RouteSource::HTB).await,
+        capture.content.team_id,
+    );
+    let content_type = capture.content.content_type.display_name();
+    let destination = destination_for(&routes, None, &[content_type], *channel_id);
+
+    // Only try to create an embed if the channel ID isn't 0
+    if destination.channel_id.0 != 0 {
+        if let Err(why) = create_embed_of_htb_flag_captured(&capture, &destination, http).await {
+            return Err(format_err!(
+                "Error when creating embed for flag capture: {}",
+                why
+            ));
+        }
+    }
+
+    set_flag_capture_announced(capture.capture_id).await?;
+
+    Ok(())
+}
+
+async fn process_new_htb_content(
+    challenge: HTBChallenge,
     channel_id: &ChannelId,
     http: &Http,
 ) -> Result<(), Error> {
     // Only try to create an embed if the channel ID isn't 0
     if channel_id.0 != 0 {
-        if let Err(why) = create_embed_of_htb_challenge_solved(&solve, channel_id, http).await {
+        if let Err(why) = create_embed_of_new_htb_content(&challenge, channel_id, http).await {
             return Err(format_err!(
-                "Error when creating embed for challenge solve: {}",
+                "Error when creating embed for new content: {}",
                 why
             ));
         }
     }
 
-    // If it makes it to this point, it will mark it as 'announced_solved' which basically means "processed"
-    add_challenge_announced_for_user(&solve, solve.challenge.htb_id).await?;
+    set_content_announced(challenge.id).await?;
+
+    Ok(())
+}
+
+async fn process_htb_releases(
+    team_id: i32,
+    release_role: Option<RoleId>,
+    channel_id: &ChannelId,
+    http: &Http,
+) -> Result<(), Error> {
+    for release in get_releases_to_announce(team_id).await? {
+        if let Err(why) = create_embed_of_htb_scheduled_release(&release, channel_id, http).await {
+            return Err(format_err!(
+                "Error when creating embed for scheduled release: {}",
+                why
+            ));
+        }
+
+        set_release_countdown_announced(release.id).await?;
+    }
+
+    let window = chrono::Duration::minutes(RELEASE_REMINDER_MINUTES);
+    for release in get_releases_to_remind(team_id, window).await? {
+        if let Err(why) =
+            create_embed_of_htb_release_reminder(&release, release_role, channel_id, http).await
+        {
+            return Err(format_err!(
+                "Error when creating embed for release reminder: {}",
+                why
+            ));
+        }
+
+        set_release_reminder_sent(release.id).await?;
+    }
 
     Ok(())
 }
@@ -212,11 +991,11 @@ pub async fn process_rank_status(
 ) -> Result<(), Error> {
     let latest_rank = htb_api.get_team_rank().await?;
 
-    let current_rank = get_latest_rank_from_db().await?;
+    let current_rank = get_latest_rank_from_db(htb_api.config.team_id).await?;
 
     if latest_rank.data.rank != current_rank.rank || latest_rank.data.points != current_rank.points
     {
-        insert_rank_into_db(&latest_rank).await?;
+        insert_rank_into_db(htb_api.config.team_id, &latest_rank).await?;
 
         if let Err(why) =
             update_htb_channel_topic_with_stats(&latest_rank.data, channel_id, http).await
@@ -245,28 +1024,42 @@ pub async fn update_htb_channel_topic_with_stats(
 
 // This needs to be on the tokio runtime so that it can use the serenity framework
 #[tokio::main]
-pub async fn new_solve_poller_task(http: &Http) {
+pub async fn new_solve_poller_task(http: &Http, thread_mode: ThreadMode, guild_id: GuildId) {
     let active_ctfs = get_active_ctfs().await.expect("Unable to get active CTFs");
 
     for ctf in active_ctfs {
+        // Threads are opened before solves are checked, so a solve can close its thread straight away
+        if ctf.channel_id != 0 && thread_mode != ThreadMode::Disabled {
+            if let Err(why) = create_challenge_threads(&ctf, thread_mode, http).await {
+                eprintln!("POLLER: Error when creating challenge threads {}", why);
+            }
+        }
+
         println!("POLLER: Polling CTF: {} for new solves...", ctf.name);
         let solves = check_for_new_solves(&ctf).await;
-        let channel_id = ChannelId(ctf.channel_id as u64);
         if let Some(ctfd_service) = CTF_CACHE.get(&ctf.id) {
             match solves {
                 Ok(solves) => {
                     if solves.is_empty() {
                         println!("POLLER: No new solves found for: {}", ctf.name);
                     } else {
-                        for solve in solves {
-                            match process_solve(&ctfd_service, solve, &channel_id, http).await {
-                                Ok(_) => {
-                                    println!("POLLER: New solve processed.");
-                                    break;
-                                }
-                                Err(why) => {
-                                    eprintln!("Error when processing solve... {}", why);
-                                }
+                        let count = solves.len();
+
+                        match process_solves(
+                            &ctfd_service,
+                            &ctf,
+                            solves,
+                            thread_mode,
+                            guild_id,
+                            http,
+                        )
+                        .await
+                        {
+                            Ok(_) => {
+                                println!("POLLER: {} new solve(s) processed.", count);
+                            }
+                            Err(why) => {
+                                eprintln!("Error when processing solves... {}", why);
                             }
                         }
                     }
@@ -280,7 +1073,7 @@ pub async fn new_solve_poller_task(http: &Http) {
 }
 
 #[tokio::main]
-pub async fn scoreboard_and_scores_task() {
+pub async fn scoreboard_and_scores_task(http: &Http, guild_id: GuildId) {
     for entry in CTF_CACHE.iter() {
         let challenge_provider = entry.value();
         match get_and_store_scoreboard(challenge_provider).await {
@@ -303,29 +1096,220 @@ pub async fn scoreboard_and_scores_task() {
                 eprintln!("Error when updating challenges/scores: {}...", why);
             }
         }
+
+        match get_ctf_from_id(*entry.key()).await {
+            Ok(ctf) => {
+                if let Err(why) = update_status_board(&ctf, http, guild_id).await {
+                    eprintln!("Error when updating status board: {}...", why);
+                }
+            }
+            Err(why) => {
+                eprintln!("Error when loading CTF for status board: {}...", why);
+            }
+        }
+    }
+}
+
+pub async fn warn_if_htb_token_expiring(
+    htb_api: &mut HTBApi,
+    channel_id: &ChannelId,
+    http: &Http,
+    warning_days: i64,
+) -> Result<(), Error> {
+    if htb_api.expiry_warning_sent
+        || !htb_api.app_token_expires_within(chrono::Duration::days(warning_days))
+    {
+        return Ok(());
+    }
+
+    let expiry = htb_api.token_expiry().format("%a %b %e %T").to_string();
+
+    channel_id
+        .send_message(http, |message| {
+            message.embed(|e| {
+                e.title("⚠️ HTB app token is about to expire");
+                e.description(format!(
+                    "The HTB app token expires on {}. Generate a new one from your HTB profile settings and update ``HTB_APP_TOKEN``, otherwise solves will stop being announced.",
+                    expiry
+                ));
+                e
+            })
+        })
+        .await?;
+
+    htb_api.expiry_warning_sent = true;
+
+    Ok(())
+}
+
+#[tokio::main]
+pub async fn htb_retired_catalogue_task(htb_api: &Mutex<HTBApi>) -> Result<(), Error> {
+    update_htb_retired_catalogue(htb_api).await
+}
+
+/// Gives every linked member the role for their HTB rank (taking away any others) and announces promotions.
+#[tokio::main]
+pub async fn htb_rank_role_task(
+    htb_api: &Mutex<HTBApi>,
+    http: &Http,
+    channel_id: &ChannelId,
+    guild_id: GuildId,
+    rank_roles: &[(String, RoleId)],
+) -> Result<(), Error> {
+    for mapping in get_all_user_mappings().await? {
+        if let Err(why) =
+            sync_htb_rank_for_user(&mapping, htb_api, http, channel_id, guild_id, rank_roles).await
+        {
+            eprintln!(
+                "Error when syncing HTB rank for HTB user {}... {}",
+                mapping.htb_id, why
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn sync_htb_rank_for_user(
+    mapping: &HTBUserMapping,
+    htb_api: &Mutex<HTBApi>,
+    http: &Http,
+    channel_id: &ChannelId,
+    guild_id: GuildId,
+    rank_roles: &[(String, RoleId)],
+) -> Result<(), Error> {
+    let profile = htb_api
+        .lock()
+        .await
+        .get_user_overview(mapping.htb_id)
+        .await?
+        .profile;
+
+    let (rank, rank_id) = match (profile.rank, profile.rank_id) {
+        (Some(rank), Some(rank_id)) => (rank, rank_id),
+        _ => return Ok(()),
+    };
+
+    if !rank_roles.is_empty() {
+        let mut member = guild_id
+            .member(http, UserId(mapping.discord_id as u64))
+            .await?;
+
+        for (role_rank, role_id) in rank_roles {
+            let has_role = member.roles.contains(role_id);
+
+            if role_rank.eq_ignore_ascii_case(&rank) {
+                if !has_role {
+                    member.add_role(http, *role_id).await?;
+                }
+            } else if has_role {
+                member.remove_role(http, *role_id).await?;
+            }
+        }
     }
+
+    if mapping.rank_id != Some(rank_id) {
+        set_rank_id_for(mapping.htb_id, rank_id).await?;
+
+        // Don't announce the first time we see someone's rank
+        let promoted = matches!(mapping.rank_id, Some(previous) if rank_id > previous);
+        if promoted && channel_id.0 != 0 {
+            create_embed_of_htb_promotion(
+                &profile.name,
+                &rank,
+                mapping.discord_id,
+                channel_id,
+                http,
+            )
+            .await
+            .map_err(|why| format_err!("Error when creating embed for promotion: {}", why))?;
+        }
+    }
+
+    Ok(())
 }
 
 #[tokio::main]
 pub async fn htb_poller_task(
-    htb_api: &mut HTBApi,
+    htb_api: &Mutex<HTBApi>,
     http: &Http,
     channel_id: &ChannelId,
     discord_name_provider: &dyn DiscordNameProvider,
+    token_warning_days: i64,
+    release_role: Option<RoleId>,
+    guild_id: GuildId,
 ) -> Result<(), Error> {
-    htb_api.handle_token_renewal().await?;
-    update_htb_challenges_and_scores(htb_api).await?;
-    process_new_solves(htb_api, discord_name_provider).await?;
-    process_rank_status(htb_api, channel_id, http).await?;
+    // Commands share the API, so it's only held for each group of requests. Renewing the token is
+    // the only thing that changes it, and nothing else needs it in the meantime.
+    let team_id = {
+        let mut api = htb_api.lock().await;
+
+        if let Err(why) =
+            warn_if_htb_token_expiring(&mut api, channel_id, http, token_warning_days).await
+        {
+            eprintln!("Error when warning about HTB token expiry... {}", why);
+        }
+
+        api.handle_token_renewal().await?;
+        api.config.team_id
+    };
+
+    update_htb_challenges_and_scores(&*htb_api.lock().await).await?;
+
+    match get_new_content_to_announce(team_id).await {
+        Ok(new_content) => {
+            for challenge in new_content {
+                match process_new_htb_content(challenge, channel_id, http).await {
+                    Ok(_) => {
+                        println!("HTB POLLER: New content announced.");
+                    }
+                    Err(why) => {
+                        eprintln!("Error when announcing new HTB content... {}", why);
+                    }
+                }
+            }
+        }
+        Err(why) => {
+            eprintln!("HTB POLLER: Error when fetching new content {}", why);
+        }
+    }
+
+    if let Err(why) = update_htb_scheduled_releases(&*htb_api.lock().await).await {
+        eprintln!("HTB POLLER: Error when updating scheduled releases {}", why);
+    }
+
+    if channel_id.0 != 0 {
+        if let Err(why) = process_htb_releases(team_id, release_role, channel_id, http).await {
+            eprintln!(
+                "HTB POLLER: Error when announcing scheduled releases {}",
+                why
+            );
+        }
+    }
 
-    let solves = get_solves_to_announce().await;
+    // Not every account can see Pro Labs etc, so don't let that stop the rest of the poller.
+    if let Err(why) = update_htb_tracked_content(&*htb_api.lock().await).await {
+        eprintln!(
+            "HTB POLLER: Error when updating Pro Labs/Fortresses/Endgames {}",
+            why
+        );
+    }
+
+    process_new_solves(&*htb_api.lock().await, discord_name_provider).await?;
+    process_rank_status(&*htb_api.lock().await, channel_id, http).await?;
+
+    if let Err(why) = update_team_member_snapshots(&*htb_api.lock().await).await {
+        eprintln!("HTB POLLER: Error when snapshotting team members {}", why);
+    }
+
+    let solves = get_solves_to_announce(team_id).await;
     match solves {
         Ok(solves) => {
             if solves.is_empty() {
                 println!("HTB POLLER: No new solves found for HTB.");
             } else {
                 for solve in solves {
-                    match process_htb_solve(solve, channel_id, http).await {
+                    match process_htb_solve(solve, channel_id, guild_id, http).await {
                         Ok(_) => {
                             println!("HTB POLLER: New solve processed.");
                         }
@@ -340,6 +1324,25 @@ pub async fn htb_poller_task(
             eprintln!("HTB POLLER: Error when fetching new solves {}", why);
         }
     }
+
+    match get_flag_captures_to_announce(team_id).await {
+        Ok(captures) => {
+            for capture in captures {
+                match process_htb_flag_capture(capture, channel_id, guild_id, http).await {
+                    Ok(_) => {
+                        println!("HTB POLLER: New flag capture processed.");
+                    }
+                    Err(why) => {
+                        eprintln!("Error when processing HTB flag capture... {}", why);
+                    }
+                }
+            }
+        }
+        Err(why) => {
+            eprintln!("HTB POLLER: Error when fetching new flag captures {}", why);
+        }
+    }
+
     Ok(())
 }
 
@@ -348,8 +1351,87 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_capitalise_first() {
-        assert_eq!("Yes", capitalise_first("yes"));
-        assert_eq!("Yes", capitalise_first("Yes"));
+    fn test_parse_rank_roles() {
+        let rank_roles = parse_rank_roles("Script Kiddie=123, Hacker = 456,Guru=abc,Elite");
+
+        assert_eq!(
+            rank_roles,
+            vec![
+                ("Script Kiddie".to_string(), RoleId(123)),
+                ("Hacker".to_string(), RoleId(456)),
+            ]
+        );
+        assert!(parse_rank_roles("").is_empty());
+    }
+
+    #[test]
+    fn test_solve_summary() {
+        let challenge = Challenge::sample("Baby RSA", "crypto").solved_by("user");
+
+        assert_eq!(solve_summary(&challenge), "📚 crypto - 💰 100 - by user");
+    }
+
+    #[test]
+    fn test_other_workers() {
+        let challenge = Challenge::sample("Baby RSA", "crypto").solved_by("bob");
+
+        let worker = |challenge_id, discord_id, name: &str| Worker {
+            challenge_id,
+            discord_id,
+            name: name.to_string(),
+        };
+        let workers = vec![
+            worker(1, 10, "alice"),
+            worker(1, 11, "old name"),
+            worker(1, 12, "carol"),
+            worker(2, 13, "dave"),
+        ];
+
+        // The solver is matched on their current name as well as the one they last used
+        let mut names = HashMap::new();
+        names.insert(11, "Bob".to_string());
+
+        assert_eq!(other_workers(&challenge, &workers, &names), vec![10, 12]);
+    }
+
+    #[test]
+    fn test_solve_content() {
+        assert_eq!(solve_content(None, &[]), None);
+        assert_eq!(
+            solve_content(
+                Some("<@&1>".to_string()),
+                &[
+                    ("Baby RSA".to_string(), vec!["<@2>".to_string(), "**carol**".to_string()]),
+                    ("Web 1".to_string(), vec![]),
+                ],
+            ),
+            Some(
+                "<@&1>\n<@2> **carol** 'Baby RSA' has been solved, time to move on to something else!"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_blood_name() {
+        assert_eq!(blood_name(1), Some("First blood"));
+        assert_eq!(blood_name(3), Some("Third blood"));
+        assert_eq!(blood_name(4), None);
+    }
+
+    #[test]
+    fn test_thread_mode_from_config() {
+        assert_eq!(ThreadMode::from_config("Category"), ThreadMode::PerCategory);
+        assert_eq!(ThreadMode::from_config("none"), ThreadMode::Disabled);
+        assert_eq!(ThreadMode::from_config(""), ThreadMode::PerChallenge);
+    }
+
+    #[test]
+    fn test_thread_name() {
+        assert_eq!(thread_name(Some("🏴"), "Baby RSA"), "🏴 Baby RSA");
+        assert_eq!(
+            thread_name(None, &"a".repeat(150)).len(),
+            MAX_THREAD_NAME_LENGTH
+        );
     }
 }
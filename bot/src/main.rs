@@ -4,6 +4,7 @@ extern crate dotenv;
 use std::{
     collections::HashSet,
     env,
+    sync::Arc,
     thread::{self, sleep},
     time::Duration,
 };
@@ -19,29 +20,52 @@ use serenity::{
 use serenity::{client::EventHandler, model::prelude::Activity};
 use serenity::{
     framework::standard::{macros::*, DispatchError},
-    model::id::ChannelId,
+    model::id::GuildId,
 };
-use serenity::{http::Http, model::id::UserId, Client};
+use serenity::{http::Http, model::id::UserId, model::interactions::Interaction, Client};
 use serenity::{model::gateway::Ready, model::Permissions};
+use tokio::sync::Mutex;
 
 use ctf_bot::discord_name_provider::AsyncDiscordNameProvider;
 use ctf_bot::{
-    commands::ctf::*, commands::htb::*, htb_poller_task, new_solve_poller_task,
-    scoreboard_and_scores_task,
+    commands::ctf::*,
+    commands::htb::*,
+    commands::slash::{handle_interaction, register_slash_commands},
+    htb_poller_task, htb_rank_role_task, htb_retired_catalogue_task, new_solve_poller_task,
+    parse_rank_roles, scoreboard_and_scores_task,
+    stale_workers::stale_worker_task,
+    teams::{load_htb_team_configs, HTBTeam, HTBTeamsContainer},
+    ThreadMode,
 };
 use ctfdb::{
     ctfs::db::initial_load_tasks,
-    htb::{api::new_htbapi_instance, db::load_categories_to_cache, structs::HTBAPIConfig},
+    htb::{
+        api::new_htbapi_instance,
+        db::{claim_unassigned_htb_rows, load_categories_to_cache},
+    },
     init_migrations,
 };
 
-pub struct Handler;
+/// How many HTB polling cycles to wait between syncing the retired machine & challenge catalogue.
+const RETIRED_CATALOGUE_SYNC_CYCLES: u32 = 144;
+
+/// How many HTB polling cycles to wait between syncing members' HTB rank roles.
+const RANK_ROLE_SYNC_CYCLES: u32 = 6;
+
+pub struct Handler {
+    guild_id: GuildId,
+}
 
 #[async_trait]
 impl EventHandler for Handler {
     async fn ready(&self, ctx: Context, ready: Ready) {
         println!("{} is connected!", ready.user.name);
 
+        // Guild commands show up straight away, unlike global ones
+        if let Err(why) = register_slash_commands(&ctx, self.guild_id).await {
+            eprintln!("Error when registering slash commands... {}", why);
+        }
+
         ctx.set_activity(Activity::playing(".help for more info!"))
             .await;
 
@@ -54,6 +78,10 @@ impl EventHandler for Handler {
             }
         };
     }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        handle_interaction(&ctx, interaction).await;
+    }
 }
 
 #[help]
@@ -110,6 +138,15 @@ async fn main() {
     let token =
         env::var("DISCORD_TOKEN").expect("Expected a token in your environment (DISCORD_TOKEN)");
     let owner_id_str = env::var("OWNER_ID").expect("Expected an OWNER_ID in your environment!");
+    let application_id = env::var("APPLICATION_ID")
+        .expect("Expected an APPLICATION_ID in your environment!")
+        .parse::<u64>()
+        .expect("Unable to parse APPLICATION_ID into u64... Did you put it in correctly?");
+
+    let guild_id = env::var("GUILD_ID")
+        .expect("No GUILD_ID environment variable found!")
+        .parse::<u64>()
+        .expect("GUILD_ID environment variable was unable to be parsed to a u64...");
 
     // Load all the active ctfs into memory
     if let Err(why) = initial_load_tasks().await {
@@ -136,82 +173,150 @@ async fn main() {
         .group(&HTBER_GROUP);
 
     let mut client = Client::builder(&token)
+        .application_id(application_id)
         .framework(framework)
-        .event_handler(Handler)
+        .event_handler(Handler {
+            guild_id: GuildId(guild_id),
+        })
         .await
         .expect("Error creating client");
 
     // Copy the token so we can use it for HTB as well
     let token_copy = token.clone();
 
+    let thread_mode = ThreadMode::from_config(&env::var("CTF_THREADS").unwrap_or_default());
+
     thread::spawn(move || {
         let http = Http::new_with_token(&token);
         loop {
-            new_solve_poller_task(&http);
+            new_solve_poller_task(&http, thread_mode, GuildId(guild_id));
             sleep(Duration::from_secs(15));
         }
     });
 
-    thread::spawn(move || loop {
-        scoreboard_and_scores_task();
-        sleep(Duration::from_secs(60));
+    let scoreboard_token = token_copy.clone();
+
+    thread::spawn(move || {
+        let http = Http::new_with_token(&scoreboard_token);
+        loop {
+            scoreboard_and_scores_task(&http, GuildId(guild_id));
+            stale_worker_task(&http);
+            sleep(Duration::from_secs(60));
+        }
     });
 
     // Load all required values for HTB API
 
-    let team_id = env::var("HTB_TEAM_ID")
-        .expect("No HTB_TEAM_ID environment variable found!")
-        .parse::<i32>()
-        .expect("HTB_TEAM_ID isn't a number!");
+    let team_configs = load_htb_team_configs().expect("Unable to load HTB team configuration");
 
-    let email = env::var("HTB_EMAIL").expect("No HTB_EMAIL environment variable found!");
-    let pass = env::var("HTB_PASSWORD").expect("No HTB_PASSWORD environment variable found!");
-    let htb_channel_id = env::var("HTB_CHANNEL_ID")
-        .expect("No HTB_CHANNEL_ID environment variable found!")
-        .parse::<u64>()
-        .expect("HTB_CHANNEL_ID environment variable was unable to be parsed to a u64...");
+    let token_warning_days = env::var("HTB_TOKEN_WARNING_DAYS")
+        .ok()
+        .and_then(|days| days.parse::<i64>().ok())
+        .unwrap_or(7);
 
-    let guild_id = env::var("GUILD_ID")
-        .expect("No GUILD_ID environment variable found!")
-        .parse::<u64>()
-        .expect("GUILD_ID environment variable was unable to be parsed to a u64...");
+    // Optional, promotions are still announced without any roles to give out
+    let rank_roles = Arc::new(parse_rank_roles(
+        &env::var("HTB_RANK_ROLES").unwrap_or_default(),
+    ));
 
-    let htb_config = HTBAPIConfig {
-        email,
-        password: pass,
-        team_id,
-    };
-
-    match new_htbapi_instance(htb_config).await {
-        Ok(mut htb_api) => {
-            thread::spawn(move || {
-                let http = Http::new_with_token(&token_copy);
-                let channel_id = ChannelId(htb_channel_id);
-
-                let discord_name_provider = AsyncDiscordNameProvider {
-                    http: &http,
-                    guild_id,
-                };
-
-                if let Err(why) = load_categories_to_cache(&htb_api) {
-                    eprintln!("Error loading categories to cache... {}", why);
+    // Anything stored before teams were configurable belongs to the primary team
+    if let Some(primary) = team_configs.first() {
+        if let Err(why) = claim_unassigned_htb_rows(primary.api_config.team_id).await {
+            eprintln!(
+                "Error when assigning existing HTB data to a team... {}",
+                why
+            );
+        }
+    }
+
+    let mut teams = vec![];
+
+    for (index, team_config) in team_configs.into_iter().enumerate() {
+        let team_name = team_config.name.clone();
+
+        let htb_api = match new_htbapi_instance(team_config.api_config).await {
+            Ok(htb_api) => Arc::new(Mutex::new(htb_api)),
+            Err(why) => {
+                eprintln!(
+                    "Error when creating HTBApi instance for team '{}'... {}",
+                    team_name, why
+                );
+                continue;
+            }
+        };
+
+        teams.push(HTBTeam {
+            name: team_name,
+            team_id: htb_api.lock().await.config.team_id,
+            channel_id: team_config.channel_id,
+            release_role: team_config.release_role,
+            api: htb_api.clone(),
+        });
+
+        let token = token_copy.clone();
+        let channel_id = team_config.channel_id;
+        let release_role = team_config.release_role;
+        let rank_roles = rank_roles.clone();
+
+        // Rank roles follow the member rather than the team, so only the primary team syncs them
+        let sync_rank_roles = index == 0;
+
+        thread::spawn(move || {
+            let http = Http::new_with_token(&token);
+
+            let discord_name_provider = AsyncDiscordNameProvider {
+                http: &http,
+                guild_id,
+            };
+
+            if let Err(why) = load_categories_to_cache(&htb_api.blocking_lock()) {
+                eprintln!("Error loading categories to cache... {}", why);
+            }
+
+            let mut cycle: u32 = 0;
+
+            loop {
+                if let Err(why) = htb_poller_task(
+                    &htb_api,
+                    &http,
+                    &channel_id,
+                    &discord_name_provider,
+                    token_warning_days,
+                    release_role,
+                    GuildId(guild_id),
+                ) {
+                    eprintln!("Error in HTB polling service... {}", why);
                 }
 
-                loop {
-                    if let Err(why) =
-                        htb_poller_task(&mut htb_api, &http, &channel_id, &discord_name_provider)
-                    {
-                        eprintln!("Error in HTB polling service... {}", why);
+                // Retired content rarely changes and takes a lot of requests, so sync it far less often.
+                if cycle % RETIRED_CATALOGUE_SYNC_CYCLES == 0 {
+                    if let Err(why) = htb_retired_catalogue_task(&htb_api) {
+                        eprintln!("Error when syncing retired HTB content... {}", why);
                     }
-                    
-                    // Sleep for 15 minutes to avoid spamming the HTB API.
-                    sleep(Duration::from_secs(600));
                 }
-            });
-        }
-        Err(why) => eprintln!("Error when creating HTBApi instance... {}", why),
+
+                if sync_rank_roles && cycle % RANK_ROLE_SYNC_CYCLES == 0 {
+                    if let Err(why) = htb_rank_role_task(
+                        &htb_api,
+                        &http,
+                        &channel_id,
+                        GuildId(guild_id),
+                        &rank_roles,
+                    ) {
+                        eprintln!("Error when syncing HTB rank roles... {}", why);
+                    }
+                }
+                cycle = cycle.wrapping_add(1);
+
+                // Sleep for 15 minutes to avoid spamming the HTB API.
+                sleep(Duration::from_secs(600));
+            }
+        });
     }
 
+    // Commands need to know which teams there are, and talk to HTB for linking
+    client.data.write().await.insert::<HTBTeamsContainer>(teams);
+
     if let Err(why) = client.start().await {
         eprintln!("Client error: {:?}", why);
     }
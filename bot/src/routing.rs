@@ -0,0 +1,117 @@
+use serenity::model::id::{ChannelId, GuildId, RoleId};
+
+use ctfdb::{
+    announcements::{find_route, get_announcement_routes, RouteSource},
+    models::AnnouncementRoute,
+};
+
+/// Where an announcement is posted, and the role it pings if any.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Destination {
+    pub channel_id: ChannelId,
+    pub role_id: Option<RoleId>,
+}
+
+impl Destination {
+    pub fn new(channel_id: ChannelId) -> Self {
+        Destination {
+            channel_id,
+            role_id: None,
+        }
+    }
+
+    pub fn ping(&self) -> Option<String> {
+        self.role_id.map(|role_id| format!("<@&{}>", role_id.0))
+    }
+}
+
+/// The guild's routes for a poller, none if they can't be loaded so announcements still go out.
+pub async fn load_routes(guild_id: GuildId, source: RouteSource) -> Vec<AnnouncementRoute> {
+    match get_announcement_routes(guild_id.0 as i64, source).await {
+        Ok(routes) => routes,
+        Err(why) => {
+            eprintln!(
+                "Error loading announcement routes, using the default channel... {}",
+                why
+            );
+            vec![]
+        }
+    }
+}
+
+/// The HTB routes set for a team. Each team has its own channel, so another team's routes
+/// shouldn't take its announcements away from it.
+pub fn team_routes(routes: Vec<AnnouncementRoute>, team_id: i32) -> Vec<AnnouncementRoute> {
+    routes
+        .into_iter()
+        .filter(|route| route.team_id == Some(team_id))
+        .collect()
+}
+
+/// Where to announce something in the given categories, falling back to the default channel.
+pub fn destination_for(
+    routes: &[AnnouncementRoute],
+    ctf_id: Option<i32>,
+    categories: &[&str],
+    fallback: ChannelId,
+) -> Destination {
+    match find_route(routes, ctf_id, categories) {
+        Some(route) => Destination {
+            channel_id: ChannelId(route.channel_id as u64),
+            role_id: route.role_id.map(|role_id| RoleId(role_id as u64)),
+        },
+        None => Destination::new(fallback),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_destination_for() {
+        let routes = vec![AnnouncementRoute {
+            id: 1,
+            guild_id: 1,
+            ctf_id: None,
+            source: "ctf".to_string(),
+            category: "web".to_string(),
+            channel_id: 20,
+            role_id: Some(30),
+            team_id: None,
+        }];
+
+        let routed = destination_for(&routes, Some(1), &["Web"], ChannelId(10));
+        assert_eq!(routed.channel_id, ChannelId(20));
+        assert_eq!(routed.ping(), Some("<@&30>".to_string()));
+
+        assert_eq!(
+            destination_for(&routes, Some(1), &["pwn"], ChannelId(10)),
+            Destination::new(ChannelId(10))
+        );
+    }
+
+    #[test]
+    fn test_team_routes() {
+        let route = |id, team_id| AnnouncementRoute {
+            id,
+            guild_id: 1,
+            ctf_id: None,
+            source: "htb".to_string(),
+            category: "machine".to_string(),
+            channel_id: 20,
+            role_id: None,
+            team_id,
+        };
+
+        let routes = team_routes(
+            vec![route(1, Some(5)), route(2, Some(6)), route(3, None)],
+            5,
+        );
+
+        assert_eq!(
+            routes.iter().map(|route| route.id).collect::<Vec<_>>(),
+            vec![1]
+        );
+    }
+}
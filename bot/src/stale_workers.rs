@@ -0,0 +1,199 @@
+use chrono::{Duration, Local, NaiveDateTime};
+use failure::Error;
+use serenity::{http::Http, model::id::UserId};
+
+use ctfdb::{
+    ctfs::db::{get_active_ctfs, get_ctf_workers, remove_worker, set_worker_prompted},
+    models::{Challenge, ChallengeWorker, Ctf},
+};
+
+use crate::commands::checkin::checkin_buttons;
+
+/// How long someone has to answer a check in before they're taken off the challenge.
+pub const CHECKIN_GRACE_MINUTES: i64 = 60;
+
+/// What's due for someone working on a challenge.
+#[derive(Debug, PartialEq)]
+pub enum WorkerCheck {
+    Fresh,
+    Prompt,
+    Expire,
+}
+
+pub fn check_worker(
+    worker: &ChallengeWorker,
+    stale_minutes: i32,
+    now: NaiveDateTime,
+) -> WorkerCheck {
+    match worker.prompted_at {
+        Some(prompted_at) if now - prompted_at >= Duration::minutes(CHECKIN_GRACE_MINUTES) => {
+            WorkerCheck::Expire
+        }
+        Some(_) => WorkerCheck::Fresh,
+        None if now - worker.confirmed_at >= Duration::minutes(stale_minutes as i64) => {
+            WorkerCheck::Prompt
+        }
+        None => WorkerCheck::Fresh,
+    }
+}
+
+async fn prompt_worker(
+    worker: &ChallengeWorker,
+    challenge: &Challenge,
+    ctf: &Ctf,
+    http: &Http,
+) -> Result<(), Error> {
+    let channel = UserId(worker.discord_id as u64)
+        .create_dm_channel(http)
+        .await?;
+
+    channel
+        .send_message(http, |message| {
+            message.embed(|e| {
+                e.title(format!("🧰 Still working on {}?", challenge.name));
+                e.description(format!(
+                    "You've been down as working on '{}' in {} for a while. Let us know if you're still on it, otherwise you'll be taken off it in {} minutes.",
+                    challenge.name, ctf.name, CHECKIN_GRACE_MINUTES
+                ));
+                e
+            });
+            message.components(|c| c.add_action_row(checkin_buttons(worker.id)))
+        })
+        .await?;
+
+    Ok(())
+}
+
+async fn expire_worker(
+    worker: &ChallengeWorker,
+    challenge: &Challenge,
+    http: &Http,
+) -> Result<(), Error> {
+    remove_worker(worker.id).await?;
+
+    let notified = match UserId(worker.discord_id as u64)
+        .create_dm_channel(http)
+        .await
+    {
+        Ok(channel) => channel
+            .say(
+                http,
+                format!(
+                    "Took you off '{}' since we didn't hear back, use ``!ctf working`` if you pick it up again",
+                    challenge.name
+                ),
+            )
+            .await
+            .map(|_| ()),
+        Err(why) => Err(why),
+    };
+
+    if let Err(why) = notified {
+        eprintln!(
+            "Unable to tell {} they were taken off... {}",
+            worker.name, why
+        );
+    }
+
+    Ok(())
+}
+
+/// Asks anyone who's been on a challenge longer than their CTF's staleness window if they're still
+/// on it, and takes them off if they don't answer in time.
+#[tokio::main]
+pub async fn stale_worker_task(http: &Http) {
+    let active_ctfs = match get_active_ctfs().await {
+        Ok(active_ctfs) => active_ctfs,
+        Err(why) => {
+            eprintln!("Error when fetching active CTFs for check ins... {}", why);
+            return;
+        }
+    };
+
+    for ctf in active_ctfs {
+        let stale_minutes = match ctf.stale_minutes {
+            Some(stale_minutes) if stale_minutes > 0 => stale_minutes,
+            _ => continue,
+        };
+
+        let workers = match get_ctf_workers(ctf.id).await {
+            Ok(workers) => workers,
+            Err(why) => {
+                eprintln!("Error when fetching workers for {}... {}", ctf.name, why);
+                continue;
+            }
+        };
+
+        let now = Local::now().naive_local();
+
+        for (worker, challenge) in workers {
+            match check_worker(&worker, stale_minutes, now) {
+                WorkerCheck::Prompt => {
+                    if let Err(why) = prompt_worker(&worker, &challenge, &ctf, http).await {
+                        eprintln!("Unable to check in with {}... {}", worker.name, why);
+                    }
+
+                    // Even if the DM failed, so they're taken off rather than asked every tick
+                    if let Err(why) = set_worker_prompted(worker.id).await {
+                        eprintln!("Error when marking {} as asked... {}", worker.name, why);
+                    }
+                }
+                WorkerCheck::Expire => {
+                    if let Err(why) = expire_worker(&worker, &challenge, http).await {
+                        eprintln!(
+                            "Error when taking {} off '{}'... {}",
+                            worker.name, challenge.name, why
+                        );
+                    } else {
+                        println!(
+                            "Took {} off '{}' after no answer",
+                            worker.name, challenge.name
+                        );
+                    }
+                }
+                WorkerCheck::Fresh => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn worker(confirmed_minutes_ago: i64, prompted_minutes_ago: Option<i64>) -> ChallengeWorker {
+        let now = NaiveDateTime::from_timestamp(100_000, 0);
+
+        ChallengeWorker {
+            id: 1,
+            challenge_id: 1,
+            name: "user".to_string(),
+            discord_id: 1,
+            claimed_at: now - Duration::minutes(confirmed_minutes_ago),
+            confirmed_at: now - Duration::minutes(confirmed_minutes_ago),
+            prompted_at: prompted_minutes_ago.map(|minutes| now - Duration::minutes(minutes)),
+        }
+    }
+
+    #[test]
+    fn test_check_worker() {
+        let now = NaiveDateTime::from_timestamp(100_000, 0);
+
+        assert_eq!(
+            check_worker(&worker(30, None), 120, now),
+            WorkerCheck::Fresh
+        );
+        assert_eq!(
+            check_worker(&worker(120, None), 120, now),
+            WorkerCheck::Prompt
+        );
+        assert_eq!(
+            check_worker(&worker(150, Some(30)), 120, now),
+            WorkerCheck::Fresh
+        );
+        assert_eq!(
+            check_worker(&worker(200, Some(CHECKIN_GRACE_MINUTES)), 120, now),
+            WorkerCheck::Expire
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, Local, NaiveDateTime};
+use failure::Error;
+use serenity::{
+    builder::CreateEmbed,
+    http::{Http, HttpError},
+    model::id::{ChannelId, GuildId, MessageId},
+};
+
+use ctfdb::{
+    ctfs::db::{get_challenges_for_ctfid, get_latest_scoreboard_status, set_ctf_status_message},
+    models::{Challenge, Ctf, Scoreboard},
+};
+
+use crate::commands::core::build_embed;
+use crate::discord_name_provider::ctf_working;
+use crate::templates::format_duration;
+
+/// Discord won't take an embed field longer than this.
+pub(crate) const MAX_FIELD_LENGTH: usize = 1024;
+
+/// Discord won't take an embed description longer than this.
+pub(crate) const MAX_DESCRIPTION_LENGTH: usize = 4096;
+
+/// How long is left until the end time, e.g. ``1d 4h 12m``.
+pub fn format_time_remaining(end_time: NaiveDateTime, now: NaiveDateTime) -> String {
+    let remaining = end_time - now;
+
+    if remaining <= Duration::zero() {
+        return "Ended".to_string();
+    }
+
+    format_duration(remaining)
+}
+
+/// Solved out of total challenges for each category, in alphabetical order.
+fn category_progress(challenges: &[Challenge]) -> Vec<(String, usize, usize)> {
+    let mut categories: Vec<(String, usize, usize)> = vec![];
+
+    for challenge in challenges {
+        let solved = challenge.solved as usize;
+
+        match categories
+            .iter_mut()
+            .find(|(category, _, _)| *category == challenge.category)
+        {
+            Some((_, solved_count, total)) => {
+                *solved_count += solved;
+                *total += 1;
+            }
+            None => categories.push((challenge.category.clone(), solved, 1)),
+        }
+    }
+
+    categories.sort();
+    categories
+}
+
+/// Joins lines up to the field limit, noting how many had to be left out.
+pub(crate) fn join_lines(lines: Vec<String>) -> String {
+    join_within(lines, "\n", MAX_FIELD_LENGTH)
+}
+
+/// Joins items up to the given length, noting how many had to be left out.
+pub(crate) fn join_within(items: Vec<String>, separator: &str, limit: usize) -> String {
+    let mut joined = String::new();
+
+    for (index, item) in items.iter().enumerate() {
+        let remaining = items.len() - index;
+        let more = format!("...and {} more", remaining);
+
+        if joined.len() + item.len() + more.len() + 2 * separator.len() > limit {
+            joined.push_str(&more);
+            return joined;
+        }
+
+        joined.push_str(item);
+        joined.push_str(separator);
+    }
+
+    joined.trim_end_matches(separator).to_string()
+}
+
+pub fn render_status_board(
+    ctf: &Ctf,
+    stats: Option<&Scoreboard>,
+    challenges: &[Challenge],
+    working: &HashMap<i32, String>,
+    now: NaiveDateTime,
+) -> CreateEmbed {
+    build_embed(|e| {
+        e.title(format!("📊 {} status", ctf.name));
+
+        match stats {
+            Some(stats) => e.description(format!(
+                "📈 Team position: {}, Total score: {}",
+                stats.position, stats.points
+            )),
+            None => e.description("📈 Waiting on the scoreboard..."),
+        };
+
+        if let Some(end_time) = ctf.end_time {
+            e.field(
+                "⏰ Time remaining",
+                format_time_remaining(end_time, now),
+                true,
+            );
+        }
+
+        let solved = challenges
+            .iter()
+            .filter(|challenge| challenge.solved)
+            .count();
+        e.field(
+            "🏴‍ Solved",
+            format!("{}/{}", solved, challenges.len()),
+            true,
+        );
+
+        let categories = category_progress(challenges);
+        if !categories.is_empty() {
+            e.field(
+                "📚 Categories",
+                join_lines(
+                    categories
+                        .into_iter()
+                        .map(|(category, solved, total)| {
+                            format!("{}: {}/{}", category, solved, total)
+                        })
+                        .collect(),
+                ),
+                false,
+            );
+        }
+
+        let working: Vec<String> = challenges
+            .iter()
+            .filter(|challenge| !challenge.solved)
+            .filter_map(|challenge| {
+                working
+                    .get(&challenge.id)
+                    .map(|working| format!("{}: {}", challenge.name, working))
+            })
+            .collect();
+
+        if !working.is_empty() {
+            e.field("🧰 Working", join_lines(working), false);
+        }
+
+        e.footer(|f| f.text(format!("Last updated {}", now.format("%a %b %e %T"))));
+    })
+}
+
+fn is_unknown_message(why: &serenity::Error) -> bool {
+    match why {
+        serenity::Error::Http(http_error) => matches!(
+            http_error.as_ref(),
+            HttpError::UnsuccessfulRequest(response) if response.status_code.as_u16() == 404
+        ),
+        _ => false,
+    }
+}
+
+/// Edits the CTF's pinned status board, posting and pinning a new one if there isn't one yet or it
+/// was deleted.
+pub async fn update_status_board(ctf: &Ctf, http: &Http, guild_id: GuildId) -> Result<(), Error> {
+    // Nowhere to post it
+    if ctf.channel_id == 0 {
+        return Ok(());
+    }
+
+    let channel_id = ChannelId(ctf.channel_id as u64);
+    let stats = get_latest_scoreboard_status(ctf.id).await.ok();
+    let challenges = get_challenges_for_ctfid(ctf.id).await?;
+    let challenge_ids: Vec<i32> = challenges.iter().map(|challenge| challenge.id).collect();
+    let working = ctf_working(http, Some(guild_id), &challenge_ids).await?;
+    let embed = render_status_board(
+        ctf,
+        stats.as_ref(),
+        &challenges,
+        &working,
+        Local::now().naive_local(),
+    );
+
+    if let Some(message_id) = ctf.status_message_id {
+        match channel_id
+            .edit_message(http, MessageId(message_id as u64), |m| {
+                m.embed(|e| {
+                    *e = embed.clone();
+                    e
+                })
+            })
+            .await
+        {
+            Ok(_) => return Ok(()),
+            Err(why) if is_unknown_message(&why) => {
+                println!(
+                    "Status board for {} was deleted, posting a new one",
+                    ctf.name
+                );
+            }
+            Err(why) => return Err(why.into()),
+        }
+    }
+
+    let message = channel_id
+        .send_message(http, |m| m.set_embed(embed))
+        .await?;
+    // Store it before pinning, so a missing permission doesn't post a new board every tick
+    set_ctf_status_message(ctf.id, message.id.0 as i64).await?;
+
+    if let Err(why) = message.pin(http).await {
+        eprintln!("Unable to pin status board for {}... {}", ctf.name, why);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_time_remaining() {
+        let now = NaiveDateTime::from_timestamp(0, 0);
+
+        assert_eq!(
+            format_time_remaining(now + Duration::minutes(26 * 60 + 5), now),
+            "1d 2h 5m"
+        );
+        assert_eq!(
+            format_time_remaining(now + Duration::minutes(90), now),
+            "1h 30m"
+        );
+        assert_eq!(
+            format_time_remaining(now - Duration::minutes(1), now),
+            "Ended"
+        );
+    }
+
+    #[test]
+    fn test_category_progress() {
+        let challenges = vec![
+            Challenge::sample("challenge", "web").solved_by("user"),
+            Challenge::sample("challenge", "crypto"),
+            Challenge::sample("challenge", "web"),
+        ];
+
+        assert_eq!(
+            category_progress(&challenges),
+            vec![("crypto".to_string(), 0, 1), ("web".to_string(), 1, 2)]
+        );
+    }
+
+    #[test]
+    fn test_join_lines() {
+        let lines = vec!["a".repeat(600), "b".repeat(600)];
+
+        assert_eq!(
+            join_lines(lines),
+            format!("{}\n...and 1 more", "a".repeat(600))
+        );
+    }
+
+    #[test]
+    fn test_join_within() {
+        let names = vec!["alice".to_string(), "bob".to_string(), "carol".to_string()];
+
+        assert_eq!(join_within(names.clone(), ", ", 100), "alice, bob, carol");
+        assert_eq!(join_within(names, ", ", 24), "alice, ...and 2 more");
+    }
+}
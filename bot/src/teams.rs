@@ -0,0 +1,196 @@
+use std::{env, sync::Arc};
+
+use failure::Error;
+use serenity::{
+    model::id::{ChannelId, RoleId},
+    prelude::TypeMapKey,
+};
+use tokio::sync::Mutex;
+
+use ctfdb::htb::structs::{HTBAPIConfig, HTBApi, HTBAuth};
+
+/// The name given to the team when ``HTB_TEAMS`` isn't set.
+pub const DEFAULT_TEAM_NAME: &str = "main";
+
+/// Everything needed to start polling a HTB team, read from the environment.
+pub struct HTBTeamConfig {
+    pub name: String,
+    pub api_config: HTBAPIConfig,
+    pub channel_id: ChannelId,
+    pub release_role: Option<RoleId>,
+}
+
+/// A HTB team that's being polled, along with the API instance logged in for it.
+#[derive(Clone)]
+pub struct HTBTeam {
+    pub name: String,
+    pub team_id: i32,
+    pub channel_id: ChannelId,
+    pub release_role: Option<RoleId>,
+    pub api: Arc<Mutex<HTBApi>>,
+}
+
+/// Shares the HTB teams between the pollers and commands, stored in the client's data.
+/// The first team is the primary team.
+pub struct HTBTeamsContainer;
+
+impl TypeMapKey for HTBTeamsContainer {
+    type Value = Vec<HTBTeam>;
+}
+
+/// Parses the ``HTB_TEAMS`` list, e.g. ``main,student``.
+pub fn parse_team_names(config: &str) -> Vec<String> {
+    config
+        .split(',')
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// The environment variables checked for a team's setting, in order. The primary team falls back
+/// to the unprefixed variables, so single team setups keep working as they are.
+fn team_env_keys(team: &str, key: &str, primary: bool) -> Vec<String> {
+    let mut keys = vec![format!("HTB_{}_{}", team.to_uppercase(), key)];
+
+    if primary {
+        keys.push(format!("HTB_{}", key));
+    }
+
+    keys
+}
+
+fn team_env_var(team: &str, key: &str, primary: bool) -> Option<String> {
+    team_env_keys(team, key, primary)
+        .into_iter()
+        .filter_map(|key| env::var(key).ok())
+        .find(|value| !value.is_empty())
+}
+
+fn load_team_config(name: String, primary: bool) -> Result<HTBTeamConfig, Error> {
+    let team_id = team_env_var(&name, "TEAM_ID", primary)
+        .ok_or_else(|| format_err!("No team ID found for HTB team '{}'", name))?
+        .parse::<i32>()
+        .map_err(|_| format_err!("The team ID for HTB team '{}' isn't a number!", name))?;
+
+    // Prefer an app token when one is configured, as it also works for accounts with 2FA.
+    let auth = match team_env_var(&name, "APP_TOKEN", primary) {
+        Some(token) => HTBAuth::AppToken(token),
+        None => HTBAuth::Credentials {
+            email: team_env_var(&name, "EMAIL", primary).ok_or_else(|| {
+                format_err!("No app token or email found for HTB team '{}'", name)
+            })?,
+            password: team_env_var(&name, "PASSWORD", primary).ok_or_else(|| {
+                format_err!("No app token or password found for HTB team '{}'", name)
+            })?,
+        },
+    };
+
+    let channel_id = team_env_var(&name, "CHANNEL_ID", primary)
+        .ok_or_else(|| format_err!("No channel ID found for HTB team '{}'", name))?
+        .parse::<u64>()
+        .map_err(|_| format_err!("The channel ID for HTB team '{}' isn't a number!", name))?;
+
+    // Optional, releases are still announced without a role to ping
+    let release_role = team_env_var(&name, "RELEASE_ROLE_ID", primary)
+        .and_then(|id| id.parse::<u64>().ok())
+        .map(RoleId);
+
+    Ok(HTBTeamConfig {
+        name,
+        api_config: HTBAPIConfig { auth, team_id },
+        channel_id: ChannelId(channel_id),
+        release_role,
+    })
+}
+
+/// Loads the HTB teams listed in ``HTB_TEAMS``, or a single team when it isn't set.
+pub fn load_htb_team_configs() -> Result<Vec<HTBTeamConfig>, Error> {
+    let mut names = parse_team_names(&env::var("HTB_TEAMS").unwrap_or_default());
+
+    if names.is_empty() {
+        names.push(
+            env::var("HTB_TEAM_NAME")
+                .map(|name| name.to_lowercase())
+                .unwrap_or_else(|_| DEFAULT_TEAM_NAME.to_string()),
+        );
+    }
+
+    names
+        .into_iter()
+        .enumerate()
+        .map(|(index, name)| load_team_config(name, index == 0))
+        .collect()
+}
+
+/// Splits a ``team=<name>`` selector out of a command's arguments.
+pub fn split_team_selector(args: Vec<String>) -> (Option<String>, Vec<String>) {
+    let mut selector = None;
+    let mut rest = vec![];
+
+    for arg in args {
+        match arg.strip_prefix("team=") {
+            Some(name) => selector = Some(name.to_lowercase()),
+            None => rest.push(arg),
+        }
+    }
+
+    (selector, rest)
+}
+
+/// Picks the team named by the selector. Without one, it's the team whose channel the command was
+/// used in, falling back to the primary team.
+pub fn select_team<'a>(
+    teams: &'a [HTBTeam],
+    selector: Option<&str>,
+    channel_id: ChannelId,
+) -> Option<&'a HTBTeam> {
+    match selector {
+        Some(name) => teams
+            .iter()
+            .find(|team| team.name.eq_ignore_ascii_case(name)),
+        None => teams
+            .iter()
+            .find(|team| team.channel_id == channel_id)
+            .or_else(|| teams.first()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_team_names() {
+        assert_eq!(
+            parse_team_names("main, Student,,"),
+            vec!["main".to_string(), "student".to_string()]
+        );
+        assert!(parse_team_names("").is_empty());
+    }
+
+    #[test]
+    fn test_team_env_keys() {
+        assert_eq!(
+            team_env_keys("main", "TEAM_ID", true),
+            vec!["HTB_MAIN_TEAM_ID".to_string(), "HTB_TEAM_ID".to_string()]
+        );
+        assert_eq!(
+            team_env_keys("student", "CHANNEL_ID", false),
+            vec!["HTB_STUDENT_CHANNEL_ID".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_split_team_selector() {
+        let args = vec![
+            "Lame".to_string(),
+            "team=Student".to_string(),
+            "retired".to_string(),
+        ];
+
+        let (selector, rest) = split_team_selector(args);
+
+        assert_eq!(selector, Some("student".to_string()));
+        assert_eq!(rest, vec!["Lame".to_string(), "retired".to_string()]);
+    }
+}
@@ -0,0 +1,263 @@
+use chrono::Duration;
+use serenity::model::id::GuildId;
+
+use ctfdb::announcements::{get_announcement_template, TemplateKind};
+
+/// Discord won't send an embed with a longer title than this.
+pub const MAX_TITLE_LENGTH: usize = 256;
+/// Discord won't send an embed with a longer description than this.
+pub const MAX_DESCRIPTION_LENGTH: usize = 4096;
+
+/// The placeholders templates can use, with what they're replaced by.
+pub const PLACEHOLDERS: [(&str, &str); 9] = [
+    ("solver", "Who solved it"),
+    ("challenge", "The challenge or machine's name"),
+    ("category", "The challenge's category"),
+    ("points", "How many points it's worth"),
+    ("position", "The team's position"),
+    ("score", "The team's score"),
+    ("flag", "🩸 for a blood, 🏴 otherwise"),
+    ("blood", "e.g. First blood, empty when it isn't one"),
+    (
+        "time_since_start",
+        "Time since the CTF started or the machine released",
+    ),
+];
+
+/// What a solve announcement's placeholders are filled in with.
+pub struct TemplateValues {
+    pub solver: String,
+    pub challenge: String,
+    pub category: String,
+    pub points: i32,
+    pub position: String,
+    pub score: String,
+    pub flag: &'static str,
+    pub blood: String,
+    pub time_since_start: String,
+}
+
+impl TemplateValues {
+    /// Made up values, for previewing a template.
+    pub fn sample() -> Self {
+        TemplateValues {
+            solver: "Solver".to_string(),
+            challenge: "Baby RSA".to_string(),
+            category: "Crypto".to_string(),
+            points: 100,
+            position: "3".to_string(),
+            score: "1337".to_string(),
+            flag: "🩸",
+            blood: "First blood".to_string(),
+            time_since_start: format_duration(Duration::minutes(150)),
+        }
+    }
+
+    fn get(&self, placeholder: &str) -> Option<String> {
+        let value = match placeholder {
+            "solver" => self.solver.clone(),
+            "challenge" => self.challenge.clone(),
+            "category" => self.category.clone(),
+            "points" => self.points.to_string(),
+            "position" => self.position.clone(),
+            "score" => self.score.clone(),
+            "flag" => self.flag.to_string(),
+            "blood" => self.blood.clone(),
+            "time_since_start" => self.time_since_start.clone(),
+            _ => return None,
+        };
+
+        Some(value)
+    }
+}
+
+/// A duration as days, hours and minutes, e.g. ``1d 4h 12m``.
+pub fn format_duration(duration: Duration) -> String {
+    let days = duration.num_days();
+    let hours = duration.num_hours() % 24;
+    let minutes = duration.num_minutes() % 60;
+
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, minutes)
+    } else {
+        format!("{}h {}m", hours, minutes)
+    }
+}
+
+/// Fills in a template's ``{placeholders}``, leaving any it doesn't know as they are. It's done in
+/// one pass, so a value that looks like a placeholder, e.g. a solver called ``{points}``, is kept
+/// as it is.
+pub fn render_template(template: &str, values: &TemplateValues) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let value = rest
+            .find('}')
+            .and_then(|end| values.get(&rest[1..end]).map(|value| (end, value)));
+
+        match value {
+            Some((end, value)) => {
+                rendered.push_str(&value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                rendered.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    rendered.push_str(rest);
+    rendered
+}
+
+fn truncate(text: String, max_length: usize) -> String {
+    if text.chars().count() <= max_length {
+        return text;
+    }
+
+    let mut truncated: String = text.chars().take(max_length - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Checks a template renders to something Discord will send, returning why it won't otherwise.
+pub fn check_template(title: &str, description: Option<&str>) -> Result<(), String> {
+    let values = TemplateValues::sample();
+    let rendered_title = render_template(title, &values);
+
+    if rendered_title.trim().is_empty() {
+        return Err("The title can't be empty!".to_string());
+    }
+
+    if rendered_title.chars().count() > MAX_TITLE_LENGTH {
+        return Err(format!(
+            "The title can't be longer than {} characters once it's filled in!",
+            MAX_TITLE_LENGTH
+        ));
+    }
+
+    if let Some(description) = description {
+        if render_template(description, &values).chars().count() > MAX_DESCRIPTION_LENGTH {
+            return Err(format!(
+                "The description can't be longer than {} characters once it's filled in!",
+                MAX_DESCRIPTION_LENGTH
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// The title and description announcements use when no template has been set.
+pub fn default_template(kind: TemplateKind) -> (&'static str, Option<&'static str>) {
+    match kind {
+        TemplateKind::CtfSolve => (
+            "🏴‍ {challenge} has been solved by {solver}‍",
+            Some("📈 New team position: {position}, Total score: {score}"),
+        ),
+        TemplateKind::HTBUserOwn => (
+            "{flag} User has been owned by {solver} on {challenge}",
+            None,
+        ),
+        TemplateKind::HTBRootOwn => (
+            "{flag} Root has been owned by {solver} on {challenge}",
+            None,
+        ),
+        TemplateKind::HTBChallengeSolve => ("{flag} {challenge} has been solved by {solver}", None),
+    }
+}
+
+/// Renders the title and description for an announcement, using the CTF's template, then the
+/// guild's, then the default.
+pub async fn render_announcement(
+    guild_id: GuildId,
+    ctf_id: Option<i32>,
+    kind: TemplateKind,
+    values: &TemplateValues,
+) -> (String, Option<String>) {
+    let (title, description) =
+        match get_announcement_template(guild_id.0 as i64, ctf_id, kind).await {
+            Ok(Some(template)) => (template.title, template.description),
+            Ok(None) => {
+                let (title, description) = default_template(kind);
+                (title.to_string(), description.map(str::to_string))
+            }
+            Err(why) => {
+                eprintln!(
+                    "Error loading announcement template, using the default... {}",
+                    why
+                );
+                let (title, description) = default_template(kind);
+                (title.to_string(), description.map(str::to_string))
+            }
+        };
+
+    // Real names can be longer than the sample ones templates are checked with
+    let mut rendered_title = render_template(&title, values);
+    if rendered_title.trim().is_empty() {
+        rendered_title = render_template(default_template(kind).0, values);
+    }
+
+    (
+        truncate(rendered_title, MAX_TITLE_LENGTH),
+        description.map(|description| {
+            truncate(
+                render_template(&description, values),
+                MAX_DESCRIPTION_LENGTH,
+            )
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_template() {
+        let values = TemplateValues::sample();
+
+        assert_eq!(
+            render_template(
+                "{flag} {challenge} ({points}) by {solver} {unknown}",
+                &values
+            ),
+            "🩸 Baby RSA (100) by Solver {unknown}"
+        );
+        assert_eq!(
+            render_template("Solved {time_since_start} in", &values),
+            "Solved 2h 30m in"
+        );
+
+        let values = TemplateValues {
+            solver: "{points}".to_string(),
+            ..TemplateValues::sample()
+        };
+        assert_eq!(
+            render_template("{{solver}} {solver} {points", &values),
+            "{{points}} {points} {points"
+        );
+    }
+
+    #[test]
+    fn test_check_template() {
+        assert!(check_template("{flag} {challenge}", Some("{blood}")).is_ok());
+        assert!(check_template("  ", None).is_err());
+        assert!(check_template(&"a".repeat(MAX_TITLE_LENGTH + 1), None).is_err());
+        assert!(
+            check_template("{challenge}", Some(&"a".repeat(MAX_DESCRIPTION_LENGTH + 1))).is_err()
+        );
+        assert_eq!(truncate("abcdef".to_string(), 4), "abc…");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::minutes(26 * 60 + 5)), "1d 2h 5m");
+        assert_eq!(format_duration(Duration::minutes(90)), "1h 30m");
+    }
+}
@@ -0,0 +1,2 @@
+DROP TABLE `htb_flag_captures`;
+DROP TABLE `htb_tracked_content`;
@@ -0,0 +1,20 @@
+CREATE TABLE `htb_tracked_content` (
+    `id` INT NOT NULL AUTO_INCREMENT,
+    `htb_id` INT NOT NULL,
+    `content_type` TEXT NOT NULL,
+    `name` TEXT NOT NULL,
+    `total_flags` INT NOT NULL,
+    PRIMARY KEY (`id`)
+) ENGINE = InnoDB;
+
+CREATE TABLE `htb_flag_captures` (
+    `id` INT NOT NULL AUTO_INCREMENT,
+    `content_id` INT NOT NULL,
+    `user_id` INT NOT NULL,
+    `username` TEXT NOT NULL,
+    `flag_title` TEXT NOT NULL,
+    `points` INT NOT NULL,
+    `announced` BOOLEAN NOT NULL,
+    `captured_time` DATETIME NOT NULL,
+    PRIMARY KEY (`id`)
+) ENGINE = InnoDB;
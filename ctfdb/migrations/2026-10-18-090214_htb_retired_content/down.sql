@@ -0,0 +1,4 @@
+ALTER TABLE `htb_challenges`
+    DROP COLUMN `retired`,
+    DROP COLUMN `retired_date`,
+    DROP COLUMN `os`;
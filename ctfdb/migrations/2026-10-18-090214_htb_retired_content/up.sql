@@ -0,0 +1,4 @@
+ALTER TABLE `htb_challenges`
+    ADD COLUMN `retired` BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN `retired_date` DATETIME NULL DEFAULT NULL,
+    ADD COLUMN `os` TEXT NULL;
@@ -0,0 +1 @@
+DROP TABLE `htb_member_snapshots`;
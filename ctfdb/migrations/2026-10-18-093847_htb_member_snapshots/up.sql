@@ -0,0 +1,12 @@
+CREATE TABLE `htb_member_snapshots` (
+    `entry_id` INT NOT NULL AUTO_INCREMENT,
+    `htb_id` INT NOT NULL,
+    `name` TEXT NOT NULL,
+    `rank` INT NOT NULL,
+    `rank_text` TEXT NOT NULL,
+    `points` INT NOT NULL,
+    `user_owns` INT NOT NULL,
+    `root_owns` INT NOT NULL,
+    `timestamp` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (`entry_id`)
+) ENGINE = InnoDB;
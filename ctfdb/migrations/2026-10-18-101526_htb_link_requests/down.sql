@@ -0,0 +1 @@
+DROP TABLE `htb_link_requests`;
@@ -0,0 +1,8 @@
+CREATE TABLE `htb_link_requests` (
+    `entry_id` INT NOT NULL AUTO_INCREMENT,
+    `htb_id` INT NOT NULL,
+    `discord_id` BIGINT NOT NULL,
+    `code` TEXT NOT NULL,
+    `created_at` DATETIME NOT NULL,
+    PRIMARY KEY (`entry_id`)
+) ENGINE = InnoDB;
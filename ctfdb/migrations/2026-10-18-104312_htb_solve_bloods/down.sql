@@ -0,0 +1,3 @@
+ALTER TABLE `htb_solves`
+    DROP COLUMN `blood_position`,
+    DROP COLUMN `team_first`;
@@ -0,0 +1,3 @@
+ALTER TABLE `htb_solves`
+    ADD COLUMN `blood_position` INT NULL,
+    ADD COLUMN `team_first` BOOL NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+DROP TABLE `htb_scheduled_releases`;
+
+ALTER TABLE `htb_challenges` DROP COLUMN `announced`;
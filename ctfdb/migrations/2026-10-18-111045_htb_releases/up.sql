@@ -0,0 +1,15 @@
+-- Everything we already know about has been seen, so only content added from now on gets announced
+ALTER TABLE `htb_challenges` ADD COLUMN `announced` BOOL NOT NULL DEFAULT TRUE;
+
+CREATE TABLE `htb_scheduled_releases` (
+    `id` INT NOT NULL AUTO_INCREMENT,
+    `htb_id` INT NOT NULL,
+    `name` TEXT NOT NULL,
+    `os` TEXT NOT NULL,
+    `difficulty` TEXT NOT NULL,
+    `avatar` TEXT NOT NULL,
+    `release_time` DATETIME NOT NULL,
+    `countdown_announced` BOOL NOT NULL DEFAULT FALSE,
+    `reminder_sent` BOOL NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (`id`)
+) ENGINE = InnoDB;
@@ -0,0 +1,13 @@
+ALTER TABLE `htb_challenges` ADD COLUMN `release_text` TEXT NOT NULL;
+UPDATE `htb_challenges` SET `release_text` = IFNULL(DATE_FORMAT(`release_date`, '%Y-%m-%dT%H:%i:%s.%fZ'), '');
+ALTER TABLE `htb_challenges` DROP COLUMN `release_date`;
+ALTER TABLE `htb_challenges` CHANGE `release_text` `release_date` TEXT NOT NULL;
+
+ALTER TABLE `htb_challenges` ADD COLUMN `points_text` TEXT NOT NULL;
+UPDATE `htb_challenges` SET `points_text` = CAST(`points` AS CHAR);
+ALTER TABLE `htb_challenges` DROP COLUMN `points`;
+ALTER TABLE `htb_challenges` CHANGE `points_text` `points` TEXT NOT NULL;
+
+UPDATE `htb_challenges` SET `challenge_category` = 100 WHERE `content_type` = 'machine';
+ALTER TABLE `htb_challenges` MODIFY `challenge_category` INT NOT NULL;
+ALTER TABLE `htb_challenges` DROP COLUMN `content_type`;
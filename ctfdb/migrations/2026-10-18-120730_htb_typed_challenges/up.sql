@@ -0,0 +1,16 @@
+-- Machines used to be stored with a magic category id of 100
+ALTER TABLE `htb_challenges` ADD COLUMN `content_type` ENUM('machine', 'challenge') NOT NULL DEFAULT 'challenge';
+UPDATE `htb_challenges` SET `content_type` = 'machine' WHERE `challenge_category` = 100;
+
+ALTER TABLE `htb_challenges` MODIFY `challenge_category` INT NULL;
+UPDATE `htb_challenges` SET `challenge_category` = NULL WHERE `content_type` = 'machine';
+
+ALTER TABLE `htb_challenges` ADD COLUMN `points_int` INT NOT NULL DEFAULT 0;
+UPDATE `htb_challenges` SET `points_int` = CAST(`points` AS SIGNED) WHERE `points` REGEXP '^[0-9]+$';
+ALTER TABLE `htb_challenges` DROP COLUMN `points`;
+ALTER TABLE `htb_challenges` CHANGE `points_int` `points` INT NOT NULL;
+
+ALTER TABLE `htb_challenges` ADD COLUMN `release_datetime` DATETIME NULL;
+UPDATE `htb_challenges` SET `release_datetime` = STR_TO_DATE(`release_date`, '%Y-%m-%dT%H:%i:%s.%fZ');
+ALTER TABLE `htb_challenges` DROP COLUMN `release_date`;
+ALTER TABLE `htb_challenges` CHANGE `release_datetime` `release_date` DATETIME NULL;
@@ -0,0 +1 @@
+ALTER TABLE `htb_user_id_mapping` DROP COLUMN `rank_id`;
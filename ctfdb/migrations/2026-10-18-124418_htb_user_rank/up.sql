@@ -0,0 +1 @@
+ALTER TABLE `htb_user_id_mapping` ADD COLUMN `rank_id` INT NULL;
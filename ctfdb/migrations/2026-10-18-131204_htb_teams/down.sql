@@ -0,0 +1,7 @@
+ALTER TABLE `htb_challenges` DROP COLUMN `team_id`;
+ALTER TABLE `htb_flag_captures` DROP COLUMN `team_id`;
+ALTER TABLE `htb_member_snapshots` DROP COLUMN `team_id`;
+ALTER TABLE `htb_scheduled_releases` DROP COLUMN `team_id`;
+ALTER TABLE `htb_solves` DROP COLUMN `team_id`;
+ALTER TABLE `htb_team_rank` DROP COLUMN `team_id`;
+ALTER TABLE `htb_tracked_content` DROP COLUMN `team_id`;
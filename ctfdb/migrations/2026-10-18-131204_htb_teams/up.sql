@@ -0,0 +1,8 @@
+-- Existing rows are claimed by the primary team when the bot starts
+ALTER TABLE `htb_challenges` ADD COLUMN `team_id` INT NOT NULL DEFAULT 0;
+ALTER TABLE `htb_flag_captures` ADD COLUMN `team_id` INT NOT NULL DEFAULT 0;
+ALTER TABLE `htb_member_snapshots` ADD COLUMN `team_id` INT NOT NULL DEFAULT 0;
+ALTER TABLE `htb_scheduled_releases` ADD COLUMN `team_id` INT NOT NULL DEFAULT 0;
+ALTER TABLE `htb_solves` ADD COLUMN `team_id` INT NOT NULL DEFAULT 0;
+ALTER TABLE `htb_team_rank` ADD COLUMN `team_id` INT NOT NULL DEFAULT 0;
+ALTER TABLE `htb_tracked_content` ADD COLUMN `team_id` INT NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE `challenges` DROP COLUMN `thread_id`;
@@ -0,0 +1,2 @@
+-- The Discord thread discussion for a challenge happens in, shared by a category when threads are per category
+ALTER TABLE `challenges` ADD COLUMN `thread_id` BIGINT NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE `ctfs` DROP COLUMN `end_time`;
+ALTER TABLE `ctfs` DROP COLUMN `status_message_id`;
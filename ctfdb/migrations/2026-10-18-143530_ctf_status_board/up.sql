@@ -0,0 +1,3 @@
+-- The pinned message kept up to date with the CTF's status, and when the CTF ends in the bot's local time
+ALTER TABLE `ctfs` ADD COLUMN `status_message_id` BIGINT NULL;
+ALTER TABLE `ctfs` ADD COLUMN `end_time` DATETIME NULL;
@@ -0,0 +1,3 @@
+DROP TABLE `announcement_templates`;
+
+ALTER TABLE `ctfs` DROP COLUMN `started_at`;
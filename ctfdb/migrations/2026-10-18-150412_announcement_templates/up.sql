@@ -0,0 +1,13 @@
+-- Existing CTFs count from when this runs, which is close enough for the time since start
+ALTER TABLE `ctfs` ADD COLUMN `started_at` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+-- Templates without a CTF apply to the whole guild
+CREATE TABLE `announcement_templates` (
+    `id` INT NOT NULL AUTO_INCREMENT,
+    `guild_id` BIGINT NOT NULL,
+    `ctf_id` INT NULL,
+    `kind` VARCHAR(32) NOT NULL,
+    `title` TEXT NOT NULL,
+    `description` TEXT NULL,
+    PRIMARY KEY (`id`)
+) ENGINE = InnoDB;
@@ -0,0 +1,11 @@
+-- Routes without a CTF apply to every CTF in the guild, HTB routes never have one
+CREATE TABLE `announcement_routes` (
+    `id` INT NOT NULL AUTO_INCREMENT,
+    `guild_id` BIGINT NOT NULL,
+    `ctf_id` INT NULL,
+    `source` VARCHAR(8) NOT NULL,
+    `category` VARCHAR(255) NOT NULL,
+    `channel_id` BIGINT NOT NULL,
+    `role_id` BIGINT NULL,
+    PRIMARY KEY (`id`)
+) ENGINE = InnoDB;
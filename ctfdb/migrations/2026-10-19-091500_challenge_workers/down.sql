@@ -0,0 +1,3 @@
+DROP TABLE `challenge_workers`;
+
+ALTER TABLE `ctfs` DROP COLUMN `stale_minutes`;
@@ -0,0 +1,14 @@
+-- Claims are never checked on for CTFs without a staleness window
+ALTER TABLE `ctfs` ADD COLUMN `stale_minutes` INT NULL;
+
+-- Claims made before this only live in `challenges`.`working`, so they're never checked on
+CREATE TABLE `challenge_workers` (
+    `id` INT NOT NULL AUTO_INCREMENT,
+    `challenge_id` INT NOT NULL,
+    `name` VARCHAR(255) NOT NULL,
+    `discord_id` BIGINT NOT NULL,
+    `claimed_at` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    `confirmed_at` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    `prompted_at` DATETIME NULL,
+    PRIMARY KEY (`id`)
+) ENGINE = InnoDB;
@@ -0,0 +1,17 @@
+ALTER TABLE `challenges` ADD COLUMN `working` TEXT NULL AFTER `solved`;
+ALTER TABLE `htb_challenges` ADD COLUMN `working` TEXT NULL AFTER `challenge_category`;
+
+-- Claims go back to being a list of names
+UPDATE `challenges` SET `working` = (
+    SELECT GROUP_CONCAT(`name` ORDER BY `claimed_at` SEPARATOR ', ')
+    FROM `challenge_workers`
+    WHERE `challenge_workers`.`challenge_id` = `challenges`.`id`
+);
+
+UPDATE `htb_challenges` SET `working` = (
+    SELECT GROUP_CONCAT(`name` ORDER BY `claimed_at` SEPARATOR ', ')
+    FROM `htb_challenge_workers`
+    WHERE `htb_challenge_workers`.`challenge_id` = `htb_challenges`.`id`
+);
+
+DROP TABLE `htb_challenge_workers`;
@@ -0,0 +1,47 @@
+-- `name` is only the last display name seen, for anything that can't ask Discord
+CREATE TABLE `htb_challenge_workers` (
+    `id` INT NOT NULL AUTO_INCREMENT,
+    `challenge_id` INT NOT NULL,
+    `discord_id` BIGINT NOT NULL,
+    `name` VARCHAR(255) NOT NULL,
+    `claimed_at` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (`id`)
+) ENGINE = InnoDB;
+
+-- Workers are tracked by Discord id now, so the names in `working` are matched back to whoever
+-- they belong to. That's anyone who's claimed a CTF challenge since claims were recorded in
+-- `challenge_workers`, or anyone who's linked the HTB account the name belongs to
+CREATE TEMPORARY TABLE `known_workers` AS
+SELECT `name`, MIN(`discord_id`) AS `discord_id`
+FROM (
+    SELECT `name`, `discord_id` FROM `challenge_workers`
+    UNION
+    SELECT `htb_solves`.`username`, `htb_user_id_mapping`.`discord_id`
+    FROM `htb_solves`
+    INNER JOIN `htb_user_id_mapping` ON `htb_user_id_mapping`.`htb_id` = `htb_solves`.`user_id`
+) AS `names`
+GROUP BY `name`;
+
+INSERT INTO `challenge_workers` (`challenge_id`, `name`, `discord_id`)
+SELECT DISTINCT `challenges`.`id`, `known_workers`.`name`, `known_workers`.`discord_id`
+FROM `challenges`
+INNER JOIN `known_workers`
+    ON FIND_IN_SET(`known_workers`.`name`, REPLACE(`challenges`.`working`, ', ', ',')) > 0
+WHERE `challenges`.`solved` = FALSE
+    AND NOT EXISTS (
+        SELECT 1 FROM `challenge_workers` AS `existing`
+        WHERE `existing`.`challenge_id` = `challenges`.`id`
+            AND `existing`.`discord_id` = `known_workers`.`discord_id`
+    );
+
+INSERT INTO `htb_challenge_workers` (`challenge_id`, `discord_id`, `name`)
+SELECT DISTINCT `htb_challenges`.`id`, `known_workers`.`discord_id`, `known_workers`.`name`
+FROM `htb_challenges`
+INNER JOIN `known_workers`
+    ON FIND_IN_SET(`known_workers`.`name`, REPLACE(`htb_challenges`.`working`, ', ', ',')) > 0;
+
+DROP TEMPORARY TABLE `known_workers`;
+
+-- Only names that were never tied to a Discord account by either of those are left behind
+ALTER TABLE `challenges` DROP COLUMN `working`;
+ALTER TABLE `htb_challenges` DROP COLUMN `working`;
@@ -0,0 +1,10 @@
+-- `discord_id` is empty for notes added from the dashboard, `author` is whoever it says wrote them
+CREATE TABLE `challenge_notes` (
+    `id` INT NOT NULL AUTO_INCREMENT,
+    `challenge_id` INT NOT NULL,
+    `discord_id` BIGINT NULL,
+    `author` VARCHAR(255) NOT NULL,
+    `content` TEXT NOT NULL,
+    `created_at` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (`id`)
+) ENGINE = InnoDB;
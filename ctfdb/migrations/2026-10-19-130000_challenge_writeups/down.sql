@@ -0,0 +1,3 @@
+DROP TABLE `challenge_writeups`;
+
+ALTER TABLE `challenges` DROP COLUMN `description`;
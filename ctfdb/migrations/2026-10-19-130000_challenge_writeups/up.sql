@@ -0,0 +1,14 @@
+-- Only fetched for challenges seen from now on, so earlier ones are exported without one
+ALTER TABLE `challenges` ADD COLUMN `description` TEXT NULL;
+
+-- Each member has one writeup per challenge, either a link or markdown they attached
+CREATE TABLE `challenge_writeups` (
+    `id` INT NOT NULL AUTO_INCREMENT,
+    `challenge_id` INT NOT NULL,
+    `discord_id` BIGINT NOT NULL,
+    `author` VARCHAR(255) NOT NULL,
+    `url` TEXT NULL,
+    `content` MEDIUMTEXT NULL,
+    `submitted_at` DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (`id`)
+) ENGINE = InnoDB;
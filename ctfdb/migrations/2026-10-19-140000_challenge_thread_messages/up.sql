@@ -0,0 +1,2 @@
+-- The message a challenge's thread is opened on, kept so a failed attempt retries the thread rather than posting again
+ALTER TABLE `challenges` ADD COLUMN `thread_message_id` BIGINT NULL;
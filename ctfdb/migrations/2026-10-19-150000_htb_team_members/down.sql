@@ -0,0 +1,3 @@
+DROP INDEX `htb_member_snapshots_member` ON `htb_member_snapshots`;
+
+DROP TABLE `htb_team_members`;
@@ -0,0 +1,14 @@
+-- Who's in each team as of the last poll, so members who leave drop off the leaderboard
+CREATE TABLE `htb_team_members` (
+    `id` INT NOT NULL AUTO_INCREMENT,
+    `team_id` INT NOT NULL,
+    `htb_id` INT NOT NULL,
+    PRIMARY KEY (`id`)
+) ENGINE = InnoDB;
+
+-- Everyone with a snapshot is assumed to still be in the team until the next poll says otherwise
+INSERT INTO `htb_team_members` (`team_id`, `htb_id`)
+SELECT DISTINCT `team_id`, `htb_id` FROM `htb_member_snapshots`;
+
+-- Finding each member's latest snapshot only has to look at theirs
+CREATE INDEX `htb_member_snapshots_member` ON `htb_member_snapshots` (`team_id`, `htb_id`, `entry_id`);
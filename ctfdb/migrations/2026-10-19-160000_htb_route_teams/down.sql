@@ -0,0 +1 @@
+ALTER TABLE `announcement_routes` DROP COLUMN `team_id`;
@@ -0,0 +1,2 @@
+-- HTB routes are for one team, so the other teams keep announcing in their own channel. Existing ones are handed to the primary team when the bot starts
+ALTER TABLE `announcement_routes` ADD COLUMN `team_id` INT NULL;
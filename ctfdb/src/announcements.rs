@@ -0,0 +1,308 @@
+use diesel::prelude::*;
+use diesel::{delete, insert_into, QueryDsl, RunQueryDsl};
+use failure::Error;
+
+use crate::get_pooled_connection;
+use crate::models::{AnnouncementRoute, AnnouncementTemplate};
+use crate::schema::announcement_routes::dsl as route_dsl;
+use crate::schema::announcement_templates::dsl as template_dsl;
+
+/// Which announcement a template is for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TemplateKind {
+    CtfSolve,
+    HTBUserOwn,
+    HTBRootOwn,
+    HTBChallengeSolve,
+}
+
+impl TemplateKind {
+    pub const KINDS: [TemplateKind; 4] = [
+        TemplateKind::CtfSolve,
+        TemplateKind::HTBUserOwn,
+        TemplateKind::HTBRootOwn,
+        TemplateKind::HTBChallengeSolve,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TemplateKind::CtfSolve => "ctf_solve",
+            TemplateKind::HTBUserOwn => "htb_user",
+            TemplateKind::HTBRootOwn => "htb_root",
+            TemplateKind::HTBChallengeSolve => "htb_challenge",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        TemplateKind::KINDS
+            .iter()
+            .copied()
+            .find(|candidate| candidate.as_str() == kind.to_lowercase())
+    }
+
+    /// The template for a HTB solve, going by its ``solve_type``.
+    pub fn from_htb_solve_type(solve_type: &str) -> Self {
+        match solve_type {
+            "user" => TemplateKind::HTBUserOwn,
+            "root" => TemplateKind::HTBRootOwn,
+            _ => TemplateKind::HTBChallengeSolve,
+        }
+    }
+}
+
+/// Finds the template for an announcement, preferring one set for the CTF over the guild's.
+pub async fn get_announcement_template(
+    guild_id: i64,
+    ctf_id: Option<i32>,
+    kind: TemplateKind,
+) -> Result<Option<AnnouncementTemplate>, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let mut templates = template_dsl::announcement_templates
+        .filter(template_dsl::guild_id.eq(guild_id))
+        .filter(template_dsl::kind.eq(kind.as_str()))
+        .load::<AnnouncementTemplate>(&connection)?;
+
+    // Guild wide templates sort last
+    templates.retain(|template| template.ctf_id.is_none() || template.ctf_id == ctf_id);
+    templates.sort_by_key(|template| template.ctf_id.is_none());
+
+    Ok(templates.into_iter().next())
+}
+
+/// Sets the template for an announcement, replacing any set before it.
+pub async fn set_announcement_template(
+    guild_id: i64,
+    ctf_id: Option<i32>,
+    kind: TemplateKind,
+    title: &str,
+    description: Option<&str>,
+) -> Result<(), Error> {
+    remove_announcement_template(guild_id, ctf_id, kind).await?;
+
+    let connection = get_pooled_connection().await?;
+
+    insert_into(template_dsl::announcement_templates)
+        .values((
+            template_dsl::guild_id.eq(guild_id),
+            template_dsl::ctf_id.eq(ctf_id),
+            template_dsl::kind.eq(kind.as_str()),
+            template_dsl::title.eq(title),
+            template_dsl::description.eq(description),
+        ))
+        .execute(&connection)?;
+
+    Ok(())
+}
+
+/// Removes a template, going back to the guild's (or the built in) one. Returns whether there was
+/// one to remove.
+pub async fn remove_announcement_template(
+    guild_id: i64,
+    ctf_id: Option<i32>,
+    kind: TemplateKind,
+) -> Result<bool, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let query = template_dsl::announcement_templates
+        .filter(template_dsl::guild_id.eq(guild_id))
+        .filter(template_dsl::kind.eq(kind.as_str()))
+        .into_boxed();
+
+    let query = match ctf_id {
+        Some(ctf_id) => query.filter(template_dsl::ctf_id.eq(ctf_id)),
+        None => query.filter(template_dsl::ctf_id.is_null()),
+    };
+
+    let ids = query.select(template_dsl::id).load::<i32>(&connection)?;
+
+    let removed = delete(template_dsl::announcement_templates.filter(template_dsl::id.eq_any(ids)))
+        .execute(&connection)?;
+
+    Ok(removed > 0)
+}
+
+/// Which poller a routed announcement comes from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RouteSource {
+    Ctf,
+    HTB,
+}
+
+impl RouteSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RouteSource::Ctf => "ctf",
+            RouteSource::HTB => "htb",
+        }
+    }
+
+    pub fn parse(source: &str) -> Option<Self> {
+        match source.to_lowercase().as_str() {
+            "ctf" => Some(RouteSource::Ctf),
+            "htb" => Some(RouteSource::HTB),
+            _ => None,
+        }
+    }
+}
+
+pub async fn get_announcement_routes(
+    guild_id: i64,
+    source: RouteSource,
+) -> Result<Vec<AnnouncementRoute>, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let routes = route_dsl::announcement_routes
+        .filter(route_dsl::guild_id.eq(guild_id))
+        .filter(route_dsl::source.eq(source.as_str()))
+        .order_by(route_dsl::category)
+        .load::<AnnouncementRoute>(&connection)?;
+
+    Ok(routes)
+}
+
+/// Routes a category's announcements to a channel, replacing any route set for it before. CTF
+/// routes can be for one CTF, HTB routes are for one team.
+pub async fn add_announcement_route(
+    guild_id: i64,
+    ctf_id: Option<i32>,
+    team_id: Option<i32>,
+    source: RouteSource,
+    category: &str,
+    channel_id: i64,
+    role_id: Option<i64>,
+) -> Result<(), Error> {
+    remove_announcement_route(guild_id, ctf_id, team_id, source, category).await?;
+
+    let connection = get_pooled_connection().await?;
+
+    insert_into(route_dsl::announcement_routes)
+        .values((
+            route_dsl::guild_id.eq(guild_id),
+            route_dsl::ctf_id.eq(ctf_id),
+            route_dsl::source.eq(source.as_str()),
+            route_dsl::category.eq(category),
+            route_dsl::channel_id.eq(channel_id),
+            route_dsl::role_id.eq(role_id),
+            route_dsl::team_id.eq(team_id),
+        ))
+        .execute(&connection)?;
+
+    Ok(())
+}
+
+/// Removes a category's route, sending its announcements back to the default channel. Returns
+/// whether there was one to remove.
+pub async fn remove_announcement_route(
+    guild_id: i64,
+    ctf_id: Option<i32>,
+    team_id: Option<i32>,
+    source: RouteSource,
+    category: &str,
+) -> Result<bool, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let query = route_dsl::announcement_routes
+        .filter(route_dsl::guild_id.eq(guild_id))
+        .filter(route_dsl::source.eq(source.as_str()))
+        .filter(route_dsl::category.eq(category))
+        .into_boxed();
+
+    let query = match ctf_id {
+        Some(ctf_id) => query.filter(route_dsl::ctf_id.eq(ctf_id)),
+        None => query.filter(route_dsl::ctf_id.is_null()),
+    };
+
+    let query = match team_id {
+        Some(team_id) => query.filter(route_dsl::team_id.eq(team_id)),
+        None => query.filter(route_dsl::team_id.is_null()),
+    };
+
+    let ids = query.select(route_dsl::id).load::<i32>(&connection)?;
+
+    let removed = delete(route_dsl::announcement_routes.filter(route_dsl::id.eq_any(ids)))
+        .execute(&connection)?;
+
+    Ok(removed > 0)
+}
+
+/// Finds the route for an announcement, trying each category in turn and preferring a route set
+/// for the CTF over the guild's.
+pub fn find_route<'a>(
+    routes: &'a [AnnouncementRoute],
+    ctf_id: Option<i32>,
+    categories: &[&str],
+) -> Option<&'a AnnouncementRoute> {
+    categories.iter().find_map(|category| {
+        let mut matching = routes
+            .iter()
+            .filter(|route| route.category.to_lowercase() == category.to_lowercase())
+            .filter(|route| route.ctf_id.is_none() || route.ctf_id == ctf_id);
+
+        let first = matching.next()?;
+        if first.ctf_id.is_some() {
+            return Some(first);
+        }
+
+        Some(
+            matching
+                .find(|route| route.ctf_id.is_some())
+                .unwrap_or(first),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(id: i32, ctf_id: Option<i32>, category: &str) -> AnnouncementRoute {
+        AnnouncementRoute {
+            id,
+            guild_id: 1,
+            ctf_id,
+            source: "ctf".to_string(),
+            category: category.to_string(),
+            channel_id: id as i64,
+            role_id: None,
+            team_id: None,
+        }
+    }
+
+    #[test]
+    fn test_find_route() {
+        let routes = vec![
+            route(1, None, "Web"),
+            route(2, Some(3), "web"),
+            route(3, None, "Pwn"),
+            route(4, Some(4), "crypto"),
+        ];
+
+        let found = |ctf_id, categories: &[&str]| {
+            find_route(&routes, ctf_id, categories).map(|route| route.id)
+        };
+
+        assert_eq!(found(Some(3), &["web"]), Some(2));
+        assert_eq!(found(Some(5), &["WEB"]), Some(1));
+        assert_eq!(found(Some(3), &["crypto"]), None);
+        assert_eq!(found(None, &["Misc", "pwn"]), Some(3));
+        assert_eq!(found(None, &["Misc"]), None);
+    }
+
+    #[test]
+    fn test_template_kind() {
+        for kind in TemplateKind::KINDS.iter() {
+            assert_eq!(TemplateKind::parse(kind.as_str()), Some(*kind));
+        }
+
+        assert_eq!(
+            TemplateKind::parse("HTB_ROOT"),
+            Some(TemplateKind::HTBRootOwn)
+        );
+        assert_eq!(TemplateKind::parse("blood"), None);
+        assert_eq!(
+            TemplateKind::from_htb_solve_type("challenge"),
+            TemplateKind::HTBChallengeSolve
+        );
+    }
+}
@@ -6,9 +6,9 @@ use crate::{
     ctfs::{
         db::get_ctf_id_from_name,
         structs::{
-            CTFDService, ChallengeProviderServiceConfig, ChallengeResponse, GetChallengesResponse,
-            GetTeamSolvesResponse, GetUserByIdResponse, MyTeamResponse, MyTeamResponseData,
-            TeamSolvesResponseData, UserResponseData,
+            CTFDService, ChallengeProviderServiceConfig, ChallengeResponse, GetChallengeResponse,
+            GetChallengesResponse, GetTeamSolvesResponse, GetUserByIdResponse, MyTeamResponse,
+            MyTeamResponseData, TeamSolvesResponseData, UserResponseData,
         },
     },
     ChallengeProvider,
@@ -39,6 +39,21 @@ impl ChallengeProvider for CTFDService {
         Ok(response.data)
     }
 
+    async fn challenge_description(
+        &self,
+        challenge: &ChallengeResponse,
+    ) -> Result<Option<String>, Error> {
+        let id = match challenge.id {
+            Some(id) => id,
+            None => return Ok(challenge.description.clone()),
+        };
+
+        let url = format!("{}/challenges/{}", &self.config.api_url, id);
+        let req = self.client.get(&url).send().await?;
+        let response = req.json::<GetChallengeResponse>().await?;
+        Ok(response.data.description)
+    }
+
     async fn get_team_solved_challenges(&self) -> Result<Vec<TeamSolvesResponseData>, Error> {
         let url = format!("{}/teams/me/solves", &self.config.api_url);
         let req = self.client.get(&url).send().await?;
@@ -1,19 +1,19 @@
-use std::collections::HashSet;
-
-use chrono::NaiveDateTime;
+use chrono::{Local, NaiveDateTime};
 use dashmap::DashMap;
 use diesel::prelude::*;
-use diesel::{insert_into, update, MysqlConnection, QueryDsl, RunQueryDsl};
+use diesel::{delete, insert_into, update, MysqlConnection, QueryDsl, RunQueryDsl};
 use failure::Error;
 use once_cell::sync::Lazy;
 
-use crate::models::{Challenge, Ctf, Scoreboard};
+use crate::models::{Challenge, ChallengeWorker, Ctf, Scoreboard};
+use crate::schema::challenge_workers::dsl as worker_dsl;
 use crate::schema::challenges::dsl as chall_dsl;
 use crate::schema::ctfs::dsl as ctf_dsl;
 use crate::schema::scoreboard::dsl as scoreboard_dsl;
 use crate::{get_pooled_connection, ChallengeProvider, PooledMysqlConnection};
 
 use super::ctfd::api::*;
+use super::recording::apply_recording_mode;
 use super::structs::*;
 
 pub type ChallengeProviderService = Box<dyn ChallengeProvider + Send + Sync>;
@@ -57,7 +57,8 @@ pub async fn add_active_ctf(
     };
 
     // Create & cache challenge provider service
-    let challenge_provider_service = new_ctfdservice(service_config).await;
+    let challenge_provider_service =
+        apply_recording_mode(new_ctfdservice(service_config).await, name)?;
 
     // Create & cache all challenges
     initial_create_all_challenges_in_db(&challenge_provider_service).await?;
@@ -77,6 +78,8 @@ pub async fn initial_create_all_challenges_in_db(
 
     let challenges = challenge_provider.get_challenges().await?;
     for challenge in challenges {
+        let description = fetch_description(challenge_provider, &challenge).await;
+
         insert_into(chall_dsl::challenges)
             .values((
                 chall_dsl::category.eq(challenge.category),
@@ -85,6 +88,7 @@ pub async fn initial_create_all_challenges_in_db(
                 chall_dsl::points.eq(challenge.value),
                 chall_dsl::solved.eq(false),
                 chall_dsl::announced_solve.eq(false),
+                chall_dsl::description.eq(description),
             ))
             .execute(&connection)?;
     }
@@ -92,6 +96,24 @@ pub async fn initial_create_all_challenges_in_db(
     Ok(())
 }
 
+/// The challenge's description for writeups. It's only fetched when the challenge is first seen,
+/// and not having it isn't worth failing over.
+async fn fetch_description(
+    challenge_provider: &ChallengeProviderService,
+    challenge: &ChallengeResponse,
+) -> Option<String> {
+    match challenge_provider.challenge_description(challenge).await {
+        Ok(description) => description,
+        Err(why) => {
+            eprintln!(
+                "Error when fetching the description of '{}'... {}",
+                challenge.name, why
+            );
+            None
+        }
+    }
+}
+
 pub async fn remove_active_ctf(name: &str) -> Result<(), Error> {
     let connection = get_pooled_connection().await?;
 
@@ -125,27 +147,122 @@ pub async fn get_ctf_id_from_name(name: &str) -> Option<i32> {
     Some(ctf.first()?.id)
 }
 
-fn update_working(
-    update_value: Option<&str>,
-    challenge_id: i32,
-    connection: &MysqlConnection,
-) -> Result<(), Error> {
-    update(chall_dsl::challenges)
-        .filter(chall_dsl::id.eq(challenge_id))
-        .set(chall_dsl::working.eq(update_value))
-        .execute(connection)?;
+pub async fn get_ctf_from_id(ctf_id: i32) -> Result<Ctf, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let ctfs = ctf_dsl::ctfs
+        .filter(ctf_dsl::id.eq(ctf_id))
+        .limit(1)
+        .load::<Ctf>(&connection)?;
+
+    match ctfs.into_iter().next() {
+        Some(ctf) => Ok(ctf),
+        None => Err(format_err!("No CTF exists for that id!")),
+    }
+}
+
+pub async fn get_ctf_for_channel(channel_id: i64) -> Result<Ctf, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let ctfs = ctf_dsl::ctfs
+        .filter(ctf_dsl::channel_id.eq(channel_id))
+        .filter(ctf_dsl::active.eq(true))
+        .limit(1)
+        .load::<Ctf>(&connection)?;
+
+    match ctfs.into_iter().next() {
+        Some(ctf) => Ok(ctf),
+        None => Err(format_err!("No CTF exists for that channel!")),
+    }
+}
+
+pub async fn set_ctf_status_message(ctf_id: i32, message_id: i64) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
+
+    update(ctf_dsl::ctfs)
+        .filter(ctf_dsl::id.eq(ctf_id))
+        .set(ctf_dsl::status_message_id.eq(message_id))
+        .execute(&connection)?;
+
     Ok(())
 }
 
-pub fn get_challenge_from_name(
+pub async fn set_ctf_end_time(name: &str, end_time: NaiveDateTime) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
+
+    let updated = update(ctf_dsl::ctfs)
+        .filter(ctf_dsl::name.eq(name))
+        .filter(ctf_dsl::active.eq(true))
+        .set(ctf_dsl::end_time.eq(end_time))
+        .execute(&connection)?;
+
+    if updated == 0 {
+        return Err(format_err!("No active CTF exists for that name!"));
+    }
+
+    Ok(())
+}
+
+/// Every challenge by that name in the active CTFs, along with its CTF. Names like "Welcome" come
+/// back every CTF, so finished CTFs are left out, and more than one can still be running.
+pub async fn find_active_challenges_by_name(name: &str) -> Result<Vec<(Challenge, Ctf)>, Error> {
+    let ctfs = get_active_ctfs().await?;
+    let ctf_ids: Vec<i32> = ctfs.iter().map(|ctf| ctf.id).collect();
+
+    let connection = get_pooled_connection().await?;
+    let challenges = chall_dsl::challenges
+        .filter(chall_dsl::name.eq(name))
+        .filter(chall_dsl::ctf_id.eq_any(ctf_ids))
+        .load::<Challenge>(&connection)?;
+
+    Ok(challenges
+        .into_iter()
+        .filter_map(|challenge| {
+            let ctf = ctfs.iter().find(|ctf| ctf.id == challenge.ctf_id)?.clone();
+            Some((challenge, ctf))
+        })
+        .collect())
+}
+
+/// Every challenge by that name in any CTF, running or ended, along with its CTF. For things
+/// done after a CTF is over, like writeups.
+pub async fn find_challenges_by_name_in_any_ctf(
     name: &str,
-    connection: &MysqlConnection,
-) -> Result<Vec<Challenge>, Error> {
+) -> Result<Vec<(Challenge, Ctf)>, Error> {
+    let connection = get_pooled_connection().await?;
+
     let challenges = chall_dsl::challenges
         .filter(chall_dsl::name.eq(name))
+        .load::<Challenge>(&connection)?;
+    let ctf_ids: Vec<i32> = challenges
+        .iter()
+        .map(|challenge| challenge.ctf_id)
+        .collect();
+    let ctfs = ctf_dsl::ctfs
+        .filter(ctf_dsl::id.eq_any(ctf_ids))
+        .load::<Ctf>(&connection)?;
+
+    Ok(challenges
+        .into_iter()
+        .filter_map(|challenge| {
+            let ctf = ctfs.iter().find(|ctf| ctf.id == challenge.ctf_id)?.clone();
+            Some((challenge, ctf))
+        })
+        .collect())
+}
+
+pub async fn get_challenge_from_id(challenge_id: i32) -> Result<Challenge, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let challenges = chall_dsl::challenges
+        .filter(chall_dsl::id.eq(challenge_id))
         .limit(1)
-        .load::<Challenge>(connection)?;
-    Ok(challenges)
+        .load::<Challenge>(&connection)?;
+
+    match challenges.into_iter().next() {
+        Some(challenge) => Ok(challenge),
+        None => Err(format_err!("No challenge exists with that id!")),
+    }
 }
 
 pub async fn search_for_challenge_by_name(name: &str) -> Result<Vec<Challenge>, Error> {
@@ -157,11 +274,14 @@ pub async fn search_for_challenge_by_name(name: &str) -> Result<Vec<Challenge>,
     Ok(challenges)
 }
 
+/// The challenges of the running CTF for the channel. Channels get reused, so ended CTFs that
+/// were bound to it are left out.
 pub async fn get_challenges_for_channel(channel_id: i64) -> Result<Vec<Challenge>, Error> {
     let connection = get_pooled_connection().await?;
 
     let ctf = ctf_dsl::ctfs
         .filter(ctf_dsl::channel_id.eq(channel_id))
+        .filter(ctf_dsl::active.eq(true))
         .limit(1)
         .load::<Ctf>(&connection)?;
 
@@ -223,84 +343,105 @@ pub async fn get_challenges_for_ctfid(ctf_id: i32) -> Result<Vec<Challenge>, Err
     return Err(format_err!("No CTF exists for that id!"));
 }
 
-pub async fn add_working(username: String, challenge_name: &str) -> Result<(), Error> {
-    let connection = get_pooled_connection()
-        .await
-        .expect("Error when getting pooled connection");
-
-    // First load the challenge by that name
-    let challenges = get_challenge_from_name(challenge_name, &connection)?;
-
-    if let Some(challenge) = challenges.first() {
-        let challenge_id = challenge.id;
-
-        match &challenge.working {
-            Some(working) => {
-                let mut split: Vec<String> = working.split(", ").map(str::to_string).collect();
-                if !split.contains(&username) {
-                    split.push(username);
-                    let update_value = split.join(", ");
-                    update_working(Some(&update_value), challenge_id, &connection)?;
-                }
-            }
-            None => {
-                let working = vec![username];
-                let update_value = working.join(", ");
-                update_working(Some(&update_value), challenge_id, &connection)?;
-            }
-        }
-    } else {
-        return Err(format_err!("No challenge exists under that name!"));
-    }
+/// Marks someone as working on the challenge, or keeps them on it if they already were.
+pub async fn add_working_by_id(
+    discord_id: i64,
+    name: &str,
+    challenge_id: i32,
+) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
 
-    Ok(())
+    record_worker(discord_id, name, challenge_id, &connection)
 }
 
-pub async fn remove_working(username: String, challenge_name: &str) -> Result<(), Error> {
+/// Takes someone off the challenge, erroring if they weren't working on it.
+pub async fn remove_working_by_id(discord_id: i64, challenge: &Challenge) -> Result<(), Error> {
     let connection = get_pooled_connection().await?;
 
-    // First load the challenge by that name
-    let challenges = get_challenge_from_name(challenge_name, &connection)?;
+    unrecord_worker(discord_id, challenge, &connection)
+}
 
-    if let Some(challenge) = challenges.first() {
-        return remove_working_from_challenge(username, challenge, &connection);
+fn unrecord_worker(
+    discord_id: i64,
+    challenge: &Challenge,
+    connection: &MysqlConnection,
+) -> Result<(), Error> {
+    let removed = delete(
+        worker_dsl::challenge_workers
+            .filter(worker_dsl::challenge_id.eq(challenge.id))
+            .filter(worker_dsl::discord_id.eq(discord_id)),
+    )
+    .execute(connection)?;
+
+    if removed == 0 {
+        return Err(format_err!("You weren't working on '{}'!", challenge.name));
     }
 
-    Err(format_err!("No challenge found by that name!"))
+    Ok(())
 }
 
-pub fn remove_working_from_challenge(
-    username: String,
-    challenge: &Challenge,
+/// Starts the staleness clock for someone working on a challenge, or restarts it if they were
+/// already on it. Their name is kept up to date for anything that can't ask Discord.
+fn record_worker(
+    discord_id: i64,
+    name: &str,
+    challenge_id: i32,
     connection: &MysqlConnection,
 ) -> Result<(), Error> {
-    let challenge_id = challenge.id;
+    let now = Local::now().naive_local();
 
-    if let Some(working) = &challenge.working {
-        let mut split: HashSet<String> = working.split(", ").map(str::to_string).collect();
+    let existing = worker_dsl::challenge_workers
+        .select(worker_dsl::id)
+        .filter(worker_dsl::challenge_id.eq(challenge_id))
+        .filter(worker_dsl::discord_id.eq(discord_id))
+        .load::<i32>(connection)?;
 
-        // Time to check if the user actually exists in here
-        if split.contains(&username) {
-            split.remove(&username);
+    if existing.is_empty() {
+        insert_into(worker_dsl::challenge_workers)
+            .values((
+                worker_dsl::challenge_id.eq(challenge_id),
+                worker_dsl::name.eq(name),
+                worker_dsl::discord_id.eq(discord_id),
+                worker_dsl::claimed_at.eq(now),
+                worker_dsl::confirmed_at.eq(now),
+            ))
+            .execute(connection)?;
+    } else {
+        update(worker_dsl::challenge_workers.filter(worker_dsl::id.eq_any(existing)))
+            .set((
+                worker_dsl::name.eq(name),
+                worker_dsl::confirmed_at.eq(now),
+                worker_dsl::prompted_at.eq(None::<NaiveDateTime>),
+            ))
+            .execute(connection)?;
+    }
 
-            if split.is_empty() {
-                update_working(None, challenge_id, connection)?;
-            } else {
-                let update_value = split.into_iter().collect::<Vec<String>>().join(", ");
-                update_working(Some(&update_value), challenge_id, connection)?;
-            }
-        }
+    Ok(())
+}
 
-        return Ok(());
-    }
+/// Takes a worker off their challenge, e.g. when they don't answer a check in.
+pub async fn remove_worker(worker_id: i32) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
 
-    Err(format_err!(
-        "Unable to remove {} as working on challenge {}",
-        username,
-        challenge.name
-    ))
+    delete(worker_dsl::challenge_workers.filter(worker_dsl::id.eq(worker_id)))
+        .execute(&connection)?;
+
+    Ok(())
 }
 
+/// Everyone working on any of the given challenges.
+pub async fn get_challenge_workers(challenge_ids: &[i32]) -> Result<Vec<ChallengeWorker>, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let workers = worker_dsl::challenge_workers
+        .filter(worker_dsl::challenge_id.eq_any(challenge_ids))
+        .order_by(worker_dsl::claimed_at)
+        .load::<ChallengeWorker>(&connection)?;
+
+    Ok(workers)
+}
+
+/// Records the solve, which ends everyone's claim on the challenge.
 pub async fn mark_solved(challenge: &Challenge) -> Result<(), Error> {
     let connection = get_pooled_connection().await?;
 
@@ -316,27 +457,167 @@ pub async fn mark_solved(challenge: &Challenge) -> Result<(), Error> {
         ))
         .execute(&connection)?;
 
-    if let Some(solver) = &challenge.solver {
-        return remove_working(solver.clone(), &challenge.name).await;
+    delete(worker_dsl::challenge_workers.filter(worker_dsl::challenge_id.eq(challenge_id)))
+        .execute(&connection)?;
+
+    Ok(())
+}
+
+/// Everyone working on the CTF's unsolved challenges, with the challenge they're on.
+pub async fn get_ctf_workers(ctf_id: i32) -> Result<Vec<(ChallengeWorker, Challenge)>, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let challenges = chall_dsl::challenges
+        .filter(chall_dsl::ctf_id.eq(ctf_id))
+        .filter(chall_dsl::solved.eq(false))
+        .load::<Challenge>(&connection)?;
+
+    let workers = worker_dsl::challenge_workers
+        .filter(
+            worker_dsl::challenge_id.eq_any(
+                challenges
+                    .iter()
+                    .map(|challenge| challenge.id)
+                    .collect::<Vec<i32>>(),
+            ),
+        )
+        .load::<ChallengeWorker>(&connection)?;
+
+    Ok(workers
+        .into_iter()
+        .filter_map(|worker| {
+            challenges
+                .iter()
+                .find(|challenge| challenge.id == worker.challenge_id)
+                .map(|challenge| (worker, challenge.clone()))
+        })
+        .collect())
+}
+
+pub async fn get_worker_from_id(worker_id: i32) -> Result<ChallengeWorker, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let workers = worker_dsl::challenge_workers
+        .filter(worker_dsl::id.eq(worker_id))
+        .limit(1)
+        .load::<ChallengeWorker>(&connection)?;
+
+    match workers.into_iter().next() {
+        Some(worker) => Ok(worker),
+        None => Err(format_err!("You're no longer working on that challenge!")),
+    }
+}
+
+/// Notes that the worker has been asked whether they're still on it.
+pub async fn set_worker_prompted(worker_id: i32) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
+
+    update(worker_dsl::challenge_workers.filter(worker_dsl::id.eq(worker_id)))
+        .set(worker_dsl::prompted_at.eq(Local::now().naive_local()))
+        .execute(&connection)?;
+
+    Ok(())
+}
+
+/// The worker says they're still on it, so the staleness clock starts again.
+pub async fn confirm_worker(worker_id: i32) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
+
+    update(worker_dsl::challenge_workers.filter(worker_dsl::id.eq(worker_id)))
+        .set((
+            worker_dsl::confirmed_at.eq(Local::now().naive_local()),
+            worker_dsl::prompted_at.eq(None::<NaiveDateTime>),
+        ))
+        .execute(&connection)?;
+
+    Ok(())
+}
+
+/// Sets how long someone can work on a challenge before being asked if they're still on it, or
+/// turns it off with ``None``.
+pub async fn set_ctf_stale_minutes(name: &str, stale_minutes: Option<i32>) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
+
+    let updated = update(ctf_dsl::ctfs)
+        .filter(ctf_dsl::name.eq(name))
+        .filter(ctf_dsl::active.eq(true))
+        .set(ctf_dsl::stale_minutes.eq(stale_minutes))
+        .execute(&connection)?;
+
+    if updated == 0 {
+        return Err(format_err!("No active CTF exists for that name!"));
     }
 
     Ok(())
 }
 
+/// Unsolved challenges still waiting on a thread. Solved ones are left out, as their thread would
+/// never be closed.
+pub async fn get_challenges_without_thread(ctf_id: i32) -> Result<Vec<Challenge>, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let challenges = chall_dsl::challenges
+        .filter(chall_dsl::ctf_id.eq(ctf_id))
+        .filter(chall_dsl::thread_id.is_null())
+        .filter(chall_dsl::solved.eq(false))
+        .load::<Challenge>(&connection)?;
+
+    Ok(challenges)
+}
+
+/// Finds the thread already opened for a category, when threads are shared by a category.
+pub async fn get_category_thread(ctf_id: i32, category: &str) -> Result<Option<i64>, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let thread_ids = chall_dsl::challenges
+        .select(chall_dsl::thread_id)
+        .filter(chall_dsl::ctf_id.eq(ctf_id))
+        .filter(chall_dsl::category.eq(category))
+        .filter(chall_dsl::thread_id.is_not_null())
+        .limit(1)
+        .load::<Option<i64>>(&connection)?;
+
+    Ok(thread_ids.into_iter().next().flatten())
+}
+
+pub async fn set_challenge_thread(challenge_id: i32, thread_id: i64) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
+
+    update(chall_dsl::challenges)
+        .filter(chall_dsl::id.eq(challenge_id))
+        .set(chall_dsl::thread_id.eq(thread_id))
+        .execute(&connection)?;
+
+    Ok(())
+}
+
+/// Keeps the message a thread is being opened on, so if opening the thread fails it's retried on
+/// the same message instead of posting the challenge again.
+pub async fn set_challenge_thread_message(challenge_id: i32, message_id: i64) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
+
+    update(chall_dsl::challenges)
+        .filter(chall_dsl::id.eq(challenge_id))
+        .set(chall_dsl::thread_message_id.eq(message_id))
+        .execute(&connection)?;
+
+    Ok(())
+}
+
 async fn load_active_ctfdservices() -> Result<(), Error> {
     let active_ctfs = get_active_ctfs().await?;
 
     // Load all active ctfs, transpose to service config and then load to cache
     for ctf in active_ctfs {
         let service_config = ChallengeProviderServiceConfig {
-            name: ctf.name,
+            name: ctf.name.clone(),
             base_url: ctf.base_url,
             api_url: ctf.api_url,
             api_key: ctf.api_key,
             service_type: ChallengeProviderServiceTypes::Ctfd, // Default as CTFd for now...
         };
 
-        let service = new_ctfdservice(service_config).await;
+        let service = apply_recording_mode(new_ctfdservice(service_config).await, &ctf.name)?;
 
         println!("Loading service into cache with id: {}", service.get_id());
 
@@ -390,12 +671,9 @@ pub async fn update_challenges_and_scores(
 
     let challenges = challenge_provider.get_challenges().await?;
     for challenge in challenges {
-        let is_new = ensure_challenge_exists_otherwise_add(
-            &challenge,
-            challenge_provider.get_id(),
-            &connection,
-        )
-        .await?;
+        let is_new =
+            ensure_challenge_exists_otherwise_add(&challenge, challenge_provider, &connection)
+                .await?;
 
         if !is_new {
             update(chall_dsl::challenges)
@@ -413,7 +691,7 @@ pub async fn update_challenges_and_scores(
 
 pub async fn ensure_challenge_exists_otherwise_add(
     challenge: &ChallengeResponse,
-    ctf_id: i32,
+    challenge_provider: &ChallengeProviderService,
     connection: &MysqlConnection,
 ) -> Result<bool, Error> {
     let challenges = chall_dsl::challenges
@@ -422,14 +700,17 @@ pub async fn ensure_challenge_exists_otherwise_add(
         .load::<Challenge>(connection)?;
 
     if challenges.is_empty() {
+        let description = fetch_description(challenge_provider, challenge).await;
+
         insert_into(chall_dsl::challenges)
             .values((
                 chall_dsl::category.eq(&challenge.category),
-                chall_dsl::ctf_id.eq(ctf_id),
+                chall_dsl::ctf_id.eq(challenge_provider.get_id()),
                 chall_dsl::name.eq(&challenge.name),
                 chall_dsl::points.eq(&challenge.value),
                 chall_dsl::solved.eq(false),
                 chall_dsl::announced_solve.eq(false),
+                chall_dsl::description.eq(description),
             ))
             .execute(connection)?;
 
@@ -0,0 +1,228 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use async_trait::async_trait;
+use failure::Error;
+
+use crate::{
+    ctfs::structs::{
+        ChallengeResponse, MyTeamResponseData, TeamSolvesResponseData, UserResponseData,
+    },
+    ChallengeProvider,
+};
+
+/// Something that happens to a [`FakeChallengeProvider`] at a given tick.
+#[derive(Debug, Clone)]
+pub enum FakeEvent {
+    /// Adds a new challenge, or replaces one with the same name and category (e.g. dynamic scoring).
+    Challenge(ChallengeResponse),
+    /// Records a solve by the team.
+    Solve(TeamSolvesResponseData),
+    /// Registers a user so that `user_from_id` can resolve them.
+    User(i32, UserResponseData),
+    /// Overrides the stats returned by `team_stats`.
+    TeamStats(MyTeamResponseData),
+}
+
+#[derive(Debug, Default)]
+struct FakeState {
+    tick: u32,
+    pending: Vec<(u32, FakeEvent)>,
+    challenges: Vec<ChallengeResponse>,
+    solves: Vec<TeamSolvesResponseData>,
+    users: HashMap<i32, UserResponseData>,
+    team_stats: Option<MyTeamResponseData>,
+}
+
+impl FakeState {
+    fn apply_due_events(&mut self) {
+        let tick = self.tick;
+        let (due, pending): (Vec<_>, Vec<_>) =
+            self.pending.drain(..).partition(|(at, _)| *at <= tick);
+        self.pending = pending;
+
+        for (_, event) in due {
+            match event {
+                FakeEvent::Challenge(challenge) => {
+                    self.challenges
+                        .retain(|c| c.name != challenge.name || c.category != challenge.category);
+                    self.challenges.push(challenge);
+                }
+                FakeEvent::Solve(solve) => self.solves.push(solve),
+                FakeEvent::User(id, user) => {
+                    self.users.insert(id, user);
+                }
+                FakeEvent::TeamStats(stats) => self.team_stats = Some(stats),
+            }
+        }
+    }
+}
+
+/// An in-memory [`ChallengeProvider`] whose challenges, solves and team stats are scripted over time.
+///
+/// Events are scheduled against a tick with [`FakeChallengeProvider::at`] and become visible once the
+/// provider has been advanced to that tick, which lets a test (or a local bot) walk through a CTF one
+/// poll at a time.
+pub struct FakeChallengeProvider {
+    id: i32,
+    state: Mutex<FakeState>,
+}
+
+impl FakeChallengeProvider {
+    pub fn new(id: i32) -> Self {
+        FakeChallengeProvider {
+            id,
+            state: Mutex::new(FakeState::default()),
+        }
+    }
+
+    /// Schedules an event for the given tick. Events for the current (or a past) tick apply immediately.
+    pub fn at(&self, tick: u32, event: FakeEvent) -> &Self {
+        let mut state = self.state.lock().unwrap();
+        state.pending.push((tick, event));
+        state.apply_due_events();
+        self
+    }
+
+    /// Moves the clock forward by one tick, applying any events that are now due.
+    pub fn advance(&self) -> u32 {
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        state.apply_due_events();
+        state.tick
+    }
+
+    pub fn tick(&self) -> u32 {
+        self.state.lock().unwrap().tick
+    }
+}
+
+#[async_trait]
+impl ChallengeProvider for FakeChallengeProvider {
+    fn get_id(&self) -> i32 {
+        self.id
+    }
+
+    async fn get_challenges(&self) -> Result<Vec<ChallengeResponse>, Error> {
+        Ok(self.state.lock().unwrap().challenges.clone())
+    }
+
+    async fn challenge_description(
+        &self,
+        challenge: &ChallengeResponse,
+    ) -> Result<Option<String>, Error> {
+        Ok(challenge.description.clone())
+    }
+
+    async fn get_team_solved_challenges(&self) -> Result<Vec<TeamSolvesResponseData>, Error> {
+        Ok(self.state.lock().unwrap().solves.clone())
+    }
+
+    async fn user_from_id(&self, id: i32) -> Result<UserResponseData, Error> {
+        match self.state.lock().unwrap().users.get(&id) {
+            Some(user) => Ok(user.clone()),
+            None => Err(format_err!("No user with id {} in fake provider", id)),
+        }
+    }
+
+    async fn team_stats(&self) -> Result<MyTeamResponseData, Error> {
+        let state = self.state.lock().unwrap();
+
+        if let Some(stats) = &state.team_stats {
+            return Ok(stats.clone());
+        }
+
+        // Without scripted stats, the score is whatever the team has solved so far.
+        let score = state.solves.iter().map(|solve| solve.challenge.value).sum();
+
+        Ok(MyTeamResponseData {
+            place: "1st".to_string(),
+            score,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn challenge(name: &str, value: i32) -> ChallengeResponse {
+        ChallengeResponse {
+            id: None,
+            name: name.to_string(),
+            value,
+            solves: None,
+            category: "Web".to_string(),
+            description: None,
+        }
+    }
+
+    fn solve(name: &str, value: i32, user: i32) -> TeamSolvesResponseData {
+        TeamSolvesResponseData {
+            date: "2021-05-13T11:01:54+00:00".to_string(),
+            team: 1,
+            challenge: challenge(name, value),
+            user,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_events_apply_when_due() {
+        let provider = FakeChallengeProvider::new(1);
+        provider
+            .at(0, FakeEvent::Challenge(challenge("Baby Web", 100)))
+            .at(2, FakeEvent::Solve(solve("Baby Web", 100, 7)));
+
+        assert_eq!(provider.get_challenges().await.unwrap().len(), 1);
+        assert!(provider
+            .get_team_solved_challenges()
+            .await
+            .unwrap()
+            .is_empty());
+
+        provider.advance();
+        assert!(provider
+            .get_team_solved_challenges()
+            .await
+            .unwrap()
+            .is_empty());
+
+        provider.advance();
+        assert_eq!(
+            provider.get_team_solved_challenges().await.unwrap().len(),
+            1
+        );
+        assert_eq!(provider.team_stats().await.unwrap().score, 100);
+    }
+
+    #[tokio::test]
+    async fn test_challenge_update_replaces_existing() {
+        let provider = FakeChallengeProvider::new(1);
+        provider
+            .at(0, FakeEvent::Challenge(challenge("Baby Web", 500)))
+            .at(1, FakeEvent::Challenge(challenge("Baby Web", 420)));
+
+        provider.advance();
+
+        let challenges = provider.get_challenges().await.unwrap();
+        assert_eq!(challenges.len(), 1);
+        assert_eq!(challenges[0].value, 420);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_user_errors() {
+        let provider = FakeChallengeProvider::new(1);
+        provider.at(
+            0,
+            FakeEvent::User(
+                7,
+                UserResponseData {
+                    name: "Craig".to_string(),
+                    score: 5,
+                },
+            ),
+        );
+
+        assert_eq!(provider.user_from_id(7).await.unwrap().name, "Craig");
+        assert!(provider.user_from_id(8).await.is_err());
+    }
+}
@@ -1,3 +1,5 @@
 pub mod ctfd;
 pub mod db;
+pub mod fake;
+pub mod recording;
 pub mod structs;
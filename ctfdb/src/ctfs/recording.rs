@@ -0,0 +1,322 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use async_trait::async_trait;
+use chrono::Local;
+use failure::Error;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    ctfs::{
+        db::ChallengeProviderService,
+        structs::{
+            ChallengeResponse, MyTeamResponseData, TeamSolvesResponseData, UserResponseData,
+        },
+    },
+    ChallengeProvider,
+};
+
+/// A single call made against a [`ChallengeProvider`], as stored on disk (one JSON object per line).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedCall {
+    pub call: String,
+    pub recorded_at: String,
+    pub response: Option<serde_json::Value>,
+    pub error: Option<String>,
+}
+
+enum Mode {
+    Record {
+        inner: ChallengeProviderService,
+        file: Mutex<File>,
+    },
+    Replay {
+        calls: Mutex<HashMap<String, VecDeque<RecordedCall>>>,
+    },
+}
+
+/// Wraps a [`ChallengeProvider`] and either records every response it gives to disk, or replays
+/// a previous recording without touching the network.
+///
+/// When replaying, calls are served in the order they were recorded. Once only one recorded
+/// response remains for a call it keeps being returned, mirroring an API that has stopped changing.
+pub struct RecordingChallengeProvider {
+    id: i32,
+    mode: Mode,
+}
+
+impl RecordingChallengeProvider {
+    pub fn record(inner: ChallengeProviderService, path: &Path) -> Result<Self, Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Each run starts a fresh recording, as replaying calls from several runs would mix them up
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)?;
+
+        Ok(RecordingChallengeProvider {
+            id: inner.get_id(),
+            mode: Mode::Record {
+                inner,
+                file: Mutex::new(file),
+            },
+        })
+    }
+
+    pub fn replay(id: i32, path: &Path) -> Result<Self, Error> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut calls: HashMap<String, VecDeque<RecordedCall>> = HashMap::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let recorded: RecordedCall = serde_json::from_str(&line)?;
+            calls
+                .entry(recorded.call.clone())
+                .or_insert_with(VecDeque::new)
+                .push_back(recorded);
+        }
+
+        Ok(RecordingChallengeProvider {
+            id,
+            mode: Mode::Replay {
+                calls: Mutex::new(calls),
+            },
+        })
+    }
+
+    fn inner(&self) -> Option<&ChallengeProviderService> {
+        match &self.mode {
+            Mode::Record { inner, .. } => Some(inner),
+            Mode::Replay { .. } => None,
+        }
+    }
+
+    fn store<T: Serialize>(&self, call: &str, result: Result<T, Error>) -> Result<T, Error> {
+        if let Mode::Record { file, .. } = &self.mode {
+            let recorded = RecordedCall {
+                call: call.to_string(),
+                recorded_at: Local::now().to_rfc3339(),
+                response: match &result {
+                    Ok(value) => Some(serde_json::to_value(value)?),
+                    Err(_) => None,
+                },
+                error: result.as_ref().err().map(|why| why.to_string()),
+            };
+
+            let mut file = file.lock().unwrap();
+            writeln!(file, "{}", serde_json::to_string(&recorded)?)?;
+        }
+
+        result
+    }
+
+    fn load<T: DeserializeOwned>(&self, call: &str) -> Result<T, Error> {
+        if let Mode::Replay { calls } = &self.mode {
+            let mut calls = calls.lock().unwrap();
+            let queue = calls
+                .get_mut(call)
+                .ok_or_else(|| format_err!("Nothing was recorded for call '{}'", call))?;
+
+            let recorded = if queue.len() > 1 {
+                queue.pop_front()
+            } else {
+                queue.front().cloned()
+            };
+
+            return match recorded {
+                Some(RecordedCall {
+                    response: Some(response),
+                    ..
+                }) => Ok(serde_json::from_value(response)?),
+                Some(RecordedCall { error, .. }) => Err(format_err!(
+                    "{}",
+                    error.unwrap_or_else(|| "Recorded call failed".to_string())
+                )),
+                None => Err(format_err!("Nothing was recorded for call '{}'", call)),
+            };
+        }
+
+        Err(format_err!("Provider is not in replay mode"))
+    }
+}
+
+#[async_trait]
+impl ChallengeProvider for RecordingChallengeProvider {
+    fn get_id(&self) -> i32 {
+        self.id
+    }
+
+    async fn get_challenges(&self) -> Result<Vec<ChallengeResponse>, Error> {
+        match self.inner() {
+            Some(inner) => self.store("get_challenges", inner.get_challenges().await),
+            None => self.load("get_challenges"),
+        }
+    }
+
+    async fn challenge_description(
+        &self,
+        challenge: &ChallengeResponse,
+    ) -> Result<Option<String>, Error> {
+        let call = format!("challenge_description/{}", challenge.name);
+        match self.inner() {
+            Some(inner) => self.store(&call, inner.challenge_description(challenge).await),
+            None => self.load(&call),
+        }
+    }
+
+    async fn get_team_solved_challenges(&self) -> Result<Vec<TeamSolvesResponseData>, Error> {
+        match self.inner() {
+            Some(inner) => self.store(
+                "get_team_solved_challenges",
+                inner.get_team_solved_challenges().await,
+            ),
+            None => self.load("get_team_solved_challenges"),
+        }
+    }
+
+    async fn user_from_id(&self, id: i32) -> Result<UserResponseData, Error> {
+        let call = format!("user_from_id/{}", id);
+        match self.inner() {
+            Some(inner) => self.store(&call, inner.user_from_id(id).await),
+            None => self.load(&call),
+        }
+    }
+
+    async fn team_stats(&self) -> Result<MyTeamResponseData, Error> {
+        match self.inner() {
+            Some(inner) => self.store("team_stats", inner.team_stats().await),
+            None => self.load("team_stats"),
+        }
+    }
+}
+
+/// Applies the recording mode configured through `CTF_RECORDING_MODE` (`record` or `replay`) to a
+/// freshly created challenge provider. Recordings live in `CTF_RECORDING_DIR` (default `recordings`),
+/// one file per CTF.
+pub fn apply_recording_mode(
+    service: ChallengeProviderService,
+    ctf_name: &str,
+) -> Result<ChallengeProviderService, Error> {
+    let mode = match env::var("CTF_RECORDING_MODE") {
+        Ok(mode) => mode.to_lowercase(),
+        Err(_) => return Ok(service),
+    };
+
+    let dir = env::var("CTF_RECORDING_DIR").unwrap_or_else(|_| "recordings".to_owned());
+    let path = recording_path_for(&dir, ctf_name);
+
+    let wrapped = match mode.as_str() {
+        "record" => RecordingChallengeProvider::record(service, &path),
+        "replay" => RecordingChallengeProvider::replay(service.get_id(), &path),
+        _ => {
+            eprintln!("Unknown CTF_RECORDING_MODE '{}', ignoring...", mode);
+            return Ok(service);
+        }
+    };
+
+    // The original service has been moved in by now, so rather than silently polling a live CTF
+    // when a replay was asked for, the CTF isn't loaded at all
+    let wrapped = wrapped.map_err(|why| {
+        format_err!(
+            "Unable to {} challenge provider for '{}' at {}: {}",
+            mode,
+            ctf_name,
+            path.display(),
+            why
+        )
+    })?;
+
+    println!(
+        "Using {} mode for '{}' with {}",
+        mode,
+        ctf_name,
+        path.display()
+    );
+
+    Ok(Box::new(wrapped))
+}
+
+fn recording_path_for(dir: &str, ctf_name: &str) -> PathBuf {
+    let file_name: String = ctf_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    PathBuf::from(dir).join(format!("{}.jsonl", file_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ctfs::fake::{FakeChallengeProvider, FakeEvent};
+
+    #[test]
+    fn test_recording_path_for() {
+        assert_eq!(
+            recording_path_for("recordings", "DUCTF 2021"),
+            PathBuf::from("recordings/DUCTF_2021.jsonl")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let path = env::temp_dir().join(format!("ctfdb-recording-{}.jsonl", std::process::id()));
+        // Left over from a previous run, recording again should replace it
+        fs::write(&path, "not a recorded call\n").unwrap();
+
+        let fake = FakeChallengeProvider::new(3);
+        fake.at(
+            0,
+            FakeEvent::Challenge(ChallengeResponse {
+                id: Some(7),
+                name: "Baby Pwn".to_string(),
+                value: 100,
+                solves: Some(2),
+                category: "Pwn".to_string(),
+                description: Some("Overflow the buffer".to_string()),
+            }),
+        );
+
+        let recorder = RecordingChallengeProvider::record(Box::new(fake), &path).unwrap();
+        let recorded = recorder.get_challenges().await.unwrap();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(
+            recorder.challenge_description(&recorded[0]).await.unwrap(),
+            Some("Overflow the buffer".to_string())
+        );
+        assert!(recorder.user_from_id(1).await.is_err());
+        drop(recorder);
+
+        let replay = RecordingChallengeProvider::replay(3, &path).unwrap();
+        assert_eq!(replay.get_id(), 3);
+
+        let challenges = replay.get_challenges().await.unwrap();
+        assert_eq!(challenges[0].name, "Baby Pwn");
+        assert_eq!(
+            replay.challenge_description(&challenges[0]).await.unwrap(),
+            Some("Overflow the buffer".to_string())
+        );
+
+        // The last response keeps being served once the recording runs out.
+        assert_eq!(replay.get_challenges().await.unwrap().len(), 1);
+
+        assert!(replay.user_from_id(1).await.is_err());
+        assert!(replay.team_stats().await.is_err());
+
+        let _ = fs::remove_file(&path);
+    }
+}
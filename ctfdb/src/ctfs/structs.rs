@@ -1,5 +1,5 @@
 use reqwest::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug)]
 pub enum ChallengeProviderServiceTypes {
@@ -25,12 +25,28 @@ pub struct GetChallengesResponse {
     pub data: Vec<ChallengeResponse>,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct ChallengeResponse {
+    /// Older recordings don't have it.
+    #[serde(default)]
+    pub id: Option<i32>,
     pub name: String,
     pub value: i32,
     pub solves: Option<i32>,
     pub category: String,
+    /// CTFd only gives this when asked for the one challenge.
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetChallengeResponse {
+    pub data: ChallengeDetailData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChallengeDetailData {
+    pub description: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -38,7 +54,7 @@ pub struct GetTeamSolvesResponse {
     pub data: Vec<TeamSolvesResponseData>,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct TeamSolvesResponseData {
     pub date: String,
     pub team: i32,
@@ -51,7 +67,7 @@ pub struct GetUserByIdResponse {
     pub data: UserResponseData,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UserResponseData {
     pub name: String,
     pub score: i32,
@@ -62,7 +78,7 @@ pub struct MyTeamResponse {
     pub data: MyTeamResponseData,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MyTeamResponseData {
     pub place: String,
     pub score: i32,
@@ -1,10 +1,11 @@
 use std::time::Duration;
 
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
 use failure::Error;
 use reqwest::{Client, ClientBuilder};
 use serde_json::json;
 
-use crate::{create_reqwest_client, jwt_still_valid};
+use crate::{create_reqwest_client, jwt_expires_within, jwt_still_valid};
 
 use super::structs::*;
 
@@ -16,7 +17,10 @@ pub async fn new_htbapi_instance(config: HTBAPIConfig) -> Result<HTBApi, Error>
         .build()
         .expect("Error when creating login reqwest client");
 
-    let token = login_and_get_token(&config, &login_client).await?;
+    let token = match &config.auth {
+        HTBAuth::Credentials { .. } => login_and_get_token(&config, &login_client).await?,
+        HTBAuth::AppToken(token) => token.clone(),
+    };
 
     let client = create_reqwest_client(&token, "Bearer");
 
@@ -26,14 +30,23 @@ pub async fn new_htbapi_instance(config: HTBAPIConfig) -> Result<HTBApi, Error>
         config,
         client,
         jwt,
+        expiry_warning_sent: false,
     })
 }
 
 async fn login_and_get_token(config: &HTBAPIConfig, client: &Client) -> Result<String, Error> {
+    let (email, password) = match &config.auth {
+        HTBAuth::Credentials { email, password } => (email, password),
+        HTBAuth::AppToken(_) => {
+            return Err(format_err!(
+                "Unable to log in to HTB when using an app token"
+            ))
+        }
+    };
+
     let url = format!("{}/login", API_URL);
 
-    let login_post_data =
-        json!({"email": config.email, "password": config.password, "remember": true});
+    let login_post_data = json!({"email": email, "password": password, "remember": true});
 
     let login_response = client
         .post(&url)
@@ -73,6 +86,58 @@ impl HTBApi {
         Ok(active_machines)
     }
 
+    /// Lists machines that have been scheduled but not released yet.
+    pub async fn list_unreleased_machines(&self) -> Result<ListUnreleasedMachines, Error> {
+        let url = format!("{}/machine/unreleased", API_URL);
+
+        let unreleased_machines = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .json::<ListUnreleasedMachines>()
+            .await?;
+        Ok(unreleased_machines)
+    }
+
+    pub async fn list_retired_machines(
+        &self,
+        page: i32,
+    ) -> Result<Paginated<ListActiveMachinesData>, Error> {
+        let url = format!(
+            "{}/machine/list/retired/paginated?page={}&per_page=100",
+            API_URL, page
+        );
+
+        let retired_machines = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .json::<Paginated<ListActiveMachinesData>>()
+            .await?;
+        Ok(retired_machines)
+    }
+
+    pub async fn list_retired_challenges(
+        &self,
+        page: i32,
+    ) -> Result<Paginated<ListActiveChallengesData>, Error> {
+        let url = format!(
+            "{}/challenge/list/retired/paginated?page={}&per_page=100",
+            API_URL, page
+        );
+
+        let retired_challenges = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .json::<Paginated<ListActiveChallengesData>>()
+            .await?;
+        Ok(retired_challenges)
+    }
+
     pub async fn list_team_members(&self) -> Result<Vec<ListTeamMembersData>, Error> {
         let url = format!("{}/team/members/{}", API_URL, &self.config.team_id);
 
@@ -174,8 +239,90 @@ impl HTBApi {
         Ok(user_overview)
     }
 
+    /// Gets the earliest owns of a machine or challenge, used to work out bloods.
+    pub async fn get_top_owns(&self, object_type: &str, id: i32) -> Result<Vec<TopOwnData>, Error> {
+        let url = format!("{}/{}/owns/top/{}", API_URL, object_type, id);
+
+        let top_owns = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .json::<TopOwns>()
+            .await?;
+
+        Ok(top_owns.info)
+    }
+
+    pub async fn list_prolabs(&self) -> Result<ListProLabs, Error> {
+        let url = format!("{}/prolabs", API_URL);
+
+        let prolabs = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .json::<ListProLabs>()
+            .await?;
+
+        Ok(prolabs)
+    }
+
+    pub async fn list_fortresses(&self) -> Result<ListFortresses, Error> {
+        let url = format!("{}/fortresses", API_URL);
+
+        let fortresses = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .json::<ListFortresses>()
+            .await?;
+
+        Ok(fortresses)
+    }
+
+    pub async fn list_endgames(&self) -> Result<ListEndgames, Error> {
+        let url = format!("{}/endgames", API_URL);
+
+        let endgames = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .json::<ListEndgames>()
+            .await?;
+
+        Ok(endgames)
+    }
+
+    pub async fn get_content_flags(
+        &self,
+        content_type: TrackedContentType,
+        content_id: i32,
+    ) -> Result<ListContentFlags, Error> {
+        let url = format!("{}/{}/{}/flags", API_URL, content_type.as_str(), content_id);
+
+        let flags = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .json::<ListContentFlags>()
+            .await?;
+
+        Ok(flags)
+    }
+
     pub async fn handle_token_renewal(&mut self) -> Result<(), Error> {
         if !jwt_still_valid(&self.jwt) {
+            if let HTBAuth::AppToken(_) = &self.config.auth {
+                return Err(format_err!(
+                    "HTB app token expired at {}, a new one needs to be generated!",
+                    self.token_expiry().format("%a %b %e %T")
+                ));
+            }
+
             let token = login_and_get_token(&self.config, &self.client).await?;
 
             self.jwt = parse_jwt(&token)?;
@@ -184,4 +331,16 @@ impl HTBApi {
 
         Ok(())
     }
+
+    /// Whether the current token is a non-renewable app token that expires within the given window.
+    pub fn app_token_expires_within(&self, window: chrono::Duration) -> bool {
+        match self.config.auth {
+            HTBAuth::AppToken(_) => jwt_expires_within(&self.jwt, window),
+            HTBAuth::Credentials { .. } => false,
+        }
+    }
+
+    pub fn token_expiry(&self) -> DateTime<Local> {
+        Local.from_utc_datetime(&NaiveDateTime::from_timestamp(self.jwt.exp, 0))
+    }
 }
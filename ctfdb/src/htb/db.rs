@@ -1,33 +1,56 @@
-use std::collections::HashSet;
+use std::collections::{hash_map::RandomState, HashSet};
+use std::hash::{BuildHasher, Hash, Hasher};
 
-use chrono::{Local, NaiveDateTime};
+use chrono::{Local, NaiveDateTime, Utc};
 use dashmap::DashMap;
-use diesel::{insert_into, prelude::*, update};
+use diesel::{delete, insert_into, prelude::*, update};
 use diesel::{QueryDsl, RunQueryDsl};
 use failure::Error;
 use once_cell::sync::Lazy;
+use tokio::sync::Mutex;
 
-use crate::htb::structs::SolveToAnnounce;
+use crate::announcements::RouteSource;
+use crate::htb::structs::{FlagCaptureToAnnounce, SolveToAnnounce, TrackedContentType};
 use crate::models::HTBSolve;
-use crate::models::{HTBRank, HTBUserMapping};
+use crate::models::{
+    HTBChallengeWorker, HTBFlagCapture, HTBLinkRequest, HTBMemberSnapshot, HTBRank,
+    HTBScheduledRelease, HTBTrackedContent, HTBUserMapping,
+};
 use crate::{
-    get_pooled_connection, models::HTBChallenge, schema::htb_challenges::dsl as htb_dsl,
-    schema::htb_solves::dsl as htb_solve_dsl, schema::htb_team_rank::dsl as htb_rank_dsl,
+    get_pooled_connection, models::HTBChallenge, schema::announcement_routes::dsl as route_dsl,
+    schema::htb_challenge_workers::dsl as htb_worker_dsl, schema::htb_challenges::dsl as htb_dsl,
+    schema::htb_flag_captures::dsl as htb_flag_dsl, schema::htb_link_requests::dsl as htb_link_dsl,
+    schema::htb_member_snapshots::dsl as htb_member_dsl,
+    schema::htb_scheduled_releases::dsl as htb_release_dsl,
+    schema::htb_solves::dsl as htb_solve_dsl, schema::htb_team_members::dsl as htb_team_member_dsl,
+    schema::htb_team_rank::dsl as htb_rank_dsl,
+    schema::htb_tracked_content::dsl as htb_content_dsl,
     schema::htb_user_id_mapping::dsl as htb_user_mapping_dsl,
 };
 use crate::{DiscordNameProvider, PooledMysqlConnection};
 
-use super::structs::{GetRecentTeamActivityData, HTBApi, ListActiveChallengesData, RankStats};
+use super::structs::{
+    blood_position, build_leaderboard, parse_htb_date, span_rank_history,
+    GetRecentTeamActivityData, HTBApi, HTBContentData, HTBContentType, HTBSearchFilter,
+    LeaderboardEntry, RankStats, UserData,
+};
 
 pub static CATEGORY_CACHE: Lazy<DashMap<i32, String>> = Lazy::new(DashMap::new);
 
 pub async fn map_htb_response_to_challenge(
+    team_id: i32,
     connection: &PooledMysqlConnection,
     challenge: &GetRecentTeamActivityData,
 ) -> Result<HTBChallenge, Error> {
+    // Machines and challenges can share an ID, the object type tells them apart
+    let content_type = HTBContentType::from_content_type(&challenge.object_type)
+        .ok_or_else(|| format_err!("Unknown HTB content type '{}'", challenge.object_type))?;
+
     let result = htb_dsl::htb_challenges
+        .filter(htb_dsl::team_id.eq(team_id))
         .filter(htb_dsl::htb_id.eq(challenge.id))
         .filter(htb_dsl::name.eq(&challenge.name))
+        .filter(htb_dsl::content_type.eq(content_type))
         .limit(1)
         .load::<HTBChallenge>(connection)?;
 
@@ -38,79 +61,124 @@ pub async fn map_htb_response_to_challenge(
     Err(format_err!("Failed to map challenge! {:#?}", challenge))
 }
 
-pub fn update_working(
-    update_value: Option<&str>,
-    challenge_id: i32,
-    connection: &MysqlConnection,
-) -> Result<(), Error> {
-    update(htb_dsl::htb_challenges)
-        .filter(htb_dsl::htb_id.eq(challenge_id))
-        .set(htb_dsl::working.eq(update_value))
-        .execute(connection)?;
-    Ok(())
-}
-
 pub fn get_challenge_from_name(
+    team_id: i32,
     name: &str,
     connection: &MysqlConnection,
 ) -> Result<Vec<HTBChallenge>, Error> {
     let challenges = htb_dsl::htb_challenges
+        .filter(htb_dsl::team_id.eq(team_id))
         .filter(htb_dsl::name.eq(name))
         .limit(1)
         .load::<HTBChallenge>(connection)?;
     Ok(challenges)
 }
 
+/// Machines and challenges can share an ID, so the content type is needed to tell them apart.
 pub fn get_challenge_from_id_with_connection(
+    team_id: i32,
     id: i32,
+    content_type: HTBContentType,
     connection: &MysqlConnection,
 ) -> Result<Vec<HTBChallenge>, Error> {
     let challenges = htb_dsl::htb_challenges
+        .filter(htb_dsl::team_id.eq(team_id))
         .filter(htb_dsl::htb_id.eq(id))
+        .filter(htb_dsl::content_type.eq(content_type))
         .limit(1)
         .load::<HTBChallenge>(connection)?;
     Ok(challenges)
 }
 
-pub async fn get_challenge_from_id(id: i32) -> Result<Vec<HTBChallenge>, Error> {
+pub async fn get_challenge_from_id(
+    team_id: i32,
+    id: i32,
+    content_type: HTBContentType,
+) -> Result<Vec<HTBChallenge>, Error> {
     let connection = get_pooled_connection().await?;
-    get_challenge_from_id_with_connection(id, &connection)
+    get_challenge_from_id_with_connection(team_id, id, content_type, &connection)
 }
 
-pub async fn search_for_challenge_by_name(name: &str) -> Result<Vec<HTBChallenge>, Error> {
+pub async fn search_for_challenge_by_name(
+    team_id: i32,
+    name: &str,
+) -> Result<Vec<HTBChallenge>, Error> {
     let connection = get_pooled_connection().await?;
     let search = format!("%{}%", name);
     let challenges = htb_dsl::htb_challenges
+        .filter(htb_dsl::team_id.eq(team_id))
         .filter(htb_dsl::name.like(search))
         .load::<HTBChallenge>(&connection)?;
     Ok(challenges)
 }
 
-pub async fn add_working(username: String, challenge_name: &str) -> Result<(), Error> {
+pub async fn search_challenges(
+    team_id: i32,
+    filter: &HTBSearchFilter,
+) -> Result<Vec<HTBChallenge>, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let mut query = htb_dsl::htb_challenges
+        .filter(htb_dsl::team_id.eq(team_id))
+        .into_boxed();
+
+    if let Some(name) = &filter.name {
+        query = query.filter(htb_dsl::name.like(format!("%{}%", name)));
+    }
+
+    if let Some(retired) = filter.retired {
+        query = query.filter(htb_dsl::retired.eq(retired));
+    }
+
+    if let Some(difficulty) = &filter.difficulty {
+        query = query.filter(htb_dsl::difficulty.eq(difficulty));
+    }
+
+    if let Some(os) = &filter.os {
+        query = query.filter(htb_dsl::os.eq(os));
+    }
+
+    let challenges = query
+        .order(htb_dsl::name.asc())
+        .load::<HTBChallenge>(&connection)?;
+    Ok(challenges)
+}
+
+pub async fn add_working(
+    team_id: i32,
+    discord_id: i64,
+    name: &str,
+    challenge_name: &str,
+) -> Result<(), Error> {
     let connection = get_pooled_connection()
         .await
         .expect("Error when getting pooled connection");
 
     // First load the challenge by that name
-    let challenges = get_challenge_from_name(challenge_name, &connection)?;
+    let challenges = get_challenge_from_name(team_id, challenge_name, &connection)?;
 
     if let Some(challenge) = challenges.first() {
-        let challenge_id = challenge.htb_id;
-
-        match &challenge.working {
-            Some(working) => {
-                let mut split: Vec<String> = working.split(", ").map(str::to_string).collect();
-                if !split.contains(&username) {
-                    split.push(username);
-                    let update_value = split.join(", ");
-                    update_working(Some(&update_value), challenge_id, &connection)?;
-                }
-            }
-            None => {
-                let working = vec![username];
-                let update_value = working.join(", ");
-                update_working(Some(&update_value), challenge_id, &connection)?;
-            }
+        let existing = htb_worker_dsl::htb_challenge_workers
+            .select(htb_worker_dsl::id)
+            .filter(htb_worker_dsl::challenge_id.eq(challenge.id))
+            .filter(htb_worker_dsl::discord_id.eq(discord_id))
+            .load::<i32>(&connection)?;
+
+        if existing.is_empty() {
+            insert_into(htb_worker_dsl::htb_challenge_workers)
+                .values((
+                    htb_worker_dsl::challenge_id.eq(challenge.id),
+                    htb_worker_dsl::discord_id.eq(discord_id),
+                    htb_worker_dsl::name.eq(name),
+                ))
+                .execute(&connection)?;
+        } else {
+            // Keep the name fresh for anything that can't ask Discord
+            update(
+                htb_worker_dsl::htb_challenge_workers.filter(htb_worker_dsl::id.eq_any(existing)),
+            )
+            .set(htb_worker_dsl::name.eq(name))
+            .execute(&connection)?;
         }
     } else {
         return Err(format_err!("No challenge exists under that name!"));
@@ -119,42 +187,45 @@ pub async fn add_working(username: String, challenge_name: &str) -> Result<(), E
     Ok(())
 }
 
-pub async fn remove_working(username: String, challenge_name: &str) -> Result<(), Error> {
+pub async fn remove_working(
+    team_id: i32,
+    discord_id: i64,
+    challenge_name: &str,
+) -> Result<(), Error> {
     let connection = get_pooled_connection().await?;
 
     // First load the challenge by that name
-    let challenges = get_challenge_from_name(challenge_name, &connection)?;
+    let challenges = get_challenge_from_name(team_id, challenge_name, &connection)?;
     if let Some(challenge) = challenges.first() {
-        return remove_working_from_challenge(username, challenge, &connection);
+        let removed = delete(
+            htb_worker_dsl::htb_challenge_workers
+                .filter(htb_worker_dsl::challenge_id.eq(challenge.id))
+                .filter(htb_worker_dsl::discord_id.eq(discord_id)),
+        )
+        .execute(&connection)?;
+
+        if removed == 0 {
+            return Err(format_err!("You weren't working on '{}'!", challenge.name));
+        }
+
+        return Ok(());
     }
 
     Err(format_err!("No challenge with that name found!"))
 }
 
-pub fn remove_working_from_challenge(
-    username: String,
-    challenge: &HTBChallenge,
-    connection: &MysqlConnection,
-) -> Result<(), Error> {
-    let challenge_id = challenge.htb_id;
-
-    if let Some(working) = &challenge.working {
-        let mut split: HashSet<String> = working.split(", ").map(str::to_string).collect();
-
-        // Time to check if the user actually exists in here
-        if split.contains(&username) {
-            split.remove(&username);
+/// Everyone working on any of the given challenges.
+pub async fn get_htb_challenge_workers(
+    challenge_ids: &[i32],
+) -> Result<Vec<HTBChallengeWorker>, Error> {
+    let connection = get_pooled_connection().await?;
 
-            if split.is_empty() {
-                update_working(None, challenge_id, connection)?;
-            } else {
-                let update_value = split.into_iter().collect::<Vec<String>>().join(", ");
-                update_working(Some(&update_value), challenge_id, connection)?;
-            }
-        }
-    }
+    let workers = htb_worker_dsl::htb_challenge_workers
+        .filter(htb_worker_dsl::challenge_id.eq_any(challenge_ids))
+        .order_by(htb_worker_dsl::claimed_at)
+        .load::<HTBChallengeWorker>(&connection)?;
 
-    Ok(())
+    Ok(workers)
 }
 
 pub async fn process_new_solves(
@@ -162,26 +233,42 @@ pub async fn process_new_solves(
     discord_name_provider: &dyn DiscordNameProvider,
 ) -> Result<(), Error> {
     let connection = get_pooled_connection().await?;
-    let recent_solves = api.get_recent_team_activity().await?;
+    let mut recent_solves = api.get_recent_team_activity().await?;
+
+    // The feed is newest first, oldest first makes sure the team's first own is the one flagged as such.
+    recent_solves.sort_by(|a, b| a.date.cmp(&b.date));
 
     for solve in recent_solves {
-        if let Ok(challenge) = map_htb_response_to_challenge(&connection, &solve).await {
+        // Pro Labs, Fortresses and Endgames show up in the same feed, but as flags rather than owns.
+        if let Some(content_type) = TrackedContentType::from_object_type(&solve.object_type) {
+            if let Err(why) = process_flag_capture(
+                api.config.team_id,
+                content_type,
+                &solve,
+                discord_name_provider,
+                &connection,
+            )
+            .await
+            {
+                eprintln!("HTB: Error when processing flag capture... {}", why);
+            }
+            continue;
+        }
+
+        if let Ok(challenge) =
+            map_htb_response_to_challenge(api.config.team_id, &connection, &solve).await
+        {
             if !is_challenge_solved_and_not_announced_for_user(
+                api.config.team_id,
                 solve.user.id,
                 challenge.htb_id,
                 &solve.solve_type,
                 &connection,
             ) {
                 let solve_user = &solve.user;
+                let solver_name = resolve_solver_name(solve_user, discord_name_provider).await;
 
-                // Try convert the HTB ID to a Discord user, otherwise just use their HTB username.
-                let solver_name = match get_discord_id_for(solve_user.id).await {
-                    Ok(discord_id) => discord_name_provider
-                        .name_for_id(discord_id)
-                        .await
-                        .unwrap_or_else(|| solve_user.name.clone()),
-                    Err(_) => solve_user.name.clone(),
-                };
+                let blood_position = get_blood_position(api, &solve).await;
 
                 println!(
                     "HTB: Adding solve for user {}, challenge: {}",
@@ -189,11 +276,13 @@ pub async fn process_new_solves(
                 );
 
                 add_challenge_solved_for_user(
+                    api.config.team_id,
                     solve_user.id,
                     &solver_name,
                     &solve.date,
-                    solve.id,
+                    &challenge,
                     &solve.solve_type,
+                    blood_position,
                     &connection,
                 )?;
             }
@@ -203,13 +292,268 @@ pub async fn process_new_solves(
     Ok(())
 }
 
-pub async fn get_solves_to_announce() -> Result<Vec<SolveToAnnounce>, Error> {
+/// Works out whether the solve was a first, second or third blood, going off HTB's top owns.
+async fn get_blood_position(api: &HTBApi, solve: &GetRecentTeamActivityData) -> Option<i32> {
+    if solve.first_blood {
+        return Some(1);
+    }
+
+    match api.get_top_owns(&solve.object_type, solve.id).await {
+        Ok(owns) => blood_position(&owns, solve.user.id, &solve.solve_type),
+        Err(why) => {
+            eprintln!(
+                "HTB: Error when getting top owns for {} {}... {}",
+                solve.object_type, solve.id, why
+            );
+            None
+        }
+    }
+}
+
+/// Try convert the HTB ID to a Discord user, otherwise just use their HTB username.
+async fn resolve_solver_name(
+    user: &UserData,
+    discord_name_provider: &dyn DiscordNameProvider,
+) -> String {
+    match get_discord_id_for(user.id).await {
+        Ok(discord_id) => discord_name_provider
+            .name_for_id(discord_id)
+            .await
+            .unwrap_or_else(|| user.name.clone()),
+        Err(_) => user.name.clone(),
+    }
+}
+
+async fn process_flag_capture(
+    team_id: i32,
+    content_type: TrackedContentType,
+    activity: &GetRecentTeamActivityData,
+    discord_name_provider: &dyn DiscordNameProvider,
+    connection: &MysqlConnection,
+) -> Result<(), Error> {
+    let flag_title = match &activity.flag_title {
+        Some(flag_title) => flag_title,
+        None => return Err(format_err!("No flag title for activity {:#?}", activity)),
+    };
+
+    let content = match get_tracked_content(team_id, content_type, activity.id, connection)? {
+        Some(content) => content,
+        None => {
+            return Err(format_err!(
+                "{} '{}' isn't tracked yet",
+                content_type.display_name(),
+                activity.name
+            ))
+        }
+    };
+
+    let existing = htb_flag_dsl::htb_flag_captures
+        .filter(htb_flag_dsl::content_id.eq(content.id))
+        .filter(htb_flag_dsl::flag_title.eq(flag_title))
+        .load::<HTBFlagCapture>(connection)?;
+
+    if existing
+        .iter()
+        .any(|capture| capture.user_id == activity.user.id)
+    {
+        return Ok(());
+    }
+
+    // Flags are announced for the team, so only the first member to capture one gets announced.
+    // Everyone's captures are still kept.
+    let announced = !existing.is_empty();
+
+    let solver_name = resolve_solver_name(&activity.user, discord_name_provider).await;
+    let captured_time = NaiveDateTime::parse_from_str(&activity.date, "%Y-%m-%dT%H:%M:%S.%Z")?;
+
+    println!(
+        "HTB: Adding flag capture for user {}, {}: {}",
+        activity.user.name, content.name, flag_title
+    );
+
+    insert_into(htb_flag_dsl::htb_flag_captures)
+        .values((
+            htb_flag_dsl::content_id.eq(content.id),
+            htb_flag_dsl::user_id.eq(activity.user.id),
+            htb_flag_dsl::username.eq(solver_name),
+            htb_flag_dsl::flag_title.eq(flag_title),
+            htb_flag_dsl::points.eq(activity.points),
+            htb_flag_dsl::announced.eq(announced),
+            htb_flag_dsl::captured_time.eq(captured_time),
+            htb_flag_dsl::team_id.eq(team_id),
+        ))
+        .execute(connection)?;
+
+    Ok(())
+}
+
+pub fn get_tracked_content(
+    team_id: i32,
+    content_type: TrackedContentType,
+    htb_id: i32,
+    connection: &MysqlConnection,
+) -> Result<Option<HTBTrackedContent>, Error> {
+    let content = htb_content_dsl::htb_tracked_content
+        .filter(htb_content_dsl::team_id.eq(team_id))
+        .filter(htb_content_dsl::content_type.eq(content_type))
+        .filter(htb_content_dsl::htb_id.eq(htb_id))
+        .limit(1)
+        .load::<HTBTrackedContent>(connection)?;
+
+    Ok(content.into_iter().next())
+}
+
+/// Counts the distinct flags the team has captured on the given content.
+pub fn count_flags_captured(content_id: i32, connection: &MysqlConnection) -> Result<i64, Error> {
+    let titles = htb_flag_dsl::htb_flag_captures
+        .filter(htb_flag_dsl::content_id.eq(content_id))
+        .select(htb_flag_dsl::flag_title)
+        .load::<String>(connection)?;
+
+    Ok(titles.into_iter().collect::<HashSet<String>>().len() as i64)
+}
+
+/// Returns every tracked Pro Lab, Fortress and Endgame along with the number of flags the team has captured.
+pub async fn get_tracked_content_progress(
+    team_id: i32,
+) -> Result<Vec<(HTBTrackedContent, i64)>, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let mut progress = vec![];
+
+    for content in htb_content_dsl::htb_tracked_content
+        .filter(htb_content_dsl::team_id.eq(team_id))
+        .load::<HTBTrackedContent>(&connection)?
+    {
+        let captured = count_flags_captured(content.id, &connection)?;
+        progress.push((content, captured));
+    }
+
+    Ok(progress)
+}
+
+pub async fn get_flag_captures_to_announce(
+    team_id: i32,
+) -> Result<Vec<FlagCaptureToAnnounce>, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let captures = htb_flag_dsl::htb_flag_captures
+        .filter(htb_flag_dsl::team_id.eq(team_id))
+        .filter(htb_flag_dsl::announced.eq(false))
+        .order(htb_flag_dsl::captured_time.asc())
+        .load::<HTBFlagCapture>(&connection)?;
+
+    let mut to_announce = vec![];
+
+    for capture in captures {
+        let content = htb_content_dsl::htb_tracked_content
+            .filter(htb_content_dsl::id.eq(capture.content_id))
+            .limit(1)
+            .load::<HTBTrackedContent>(&connection)?;
+
+        if let Some(content) = content.into_iter().next() {
+            let flags_captured = count_flags_captured(content.id, &connection)?;
+
+            to_announce.push(FlagCaptureToAnnounce {
+                capture_id: capture.id,
+                solver: capture.username,
+                flag_title: capture.flag_title,
+                points: capture.points,
+                content,
+                flags_captured,
+            });
+        }
+    }
+
+    Ok(to_announce)
+}
+
+pub async fn set_flag_capture_announced(capture_id: i32) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
+
+    update(htb_flag_dsl::htb_flag_captures)
+        .filter(htb_flag_dsl::id.eq(capture_id))
+        .set(htb_flag_dsl::announced.eq(true))
+        .execute(&connection)?;
+
+    Ok(())
+}
+
+/// Syncs the list of Pro Labs, Fortresses and Endgames so that flag captures can be mapped to them.
+pub async fn update_htb_tracked_content(htb_api: &HTBApi) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
+
+    let mut content = vec![];
+
+    for lab in htb_api.list_prolabs().await?.data.labs {
+        content.push((
+            TrackedContentType::ProLab,
+            lab.id,
+            lab.name,
+            Some(lab.pro_flags_count),
+        ));
+    }
+
+    for fortress in htb_api.list_fortresses().await?.data.into_values() {
+        content.push((
+            TrackedContentType::Fortress,
+            fortress.id,
+            fortress.name,
+            Some(fortress.number_of_flags),
+        ));
+    }
+
+    for endgame in htb_api.list_endgames().await?.data {
+        content.push((TrackedContentType::Endgame, endgame.id, endgame.name, None));
+    }
+
+    for (content_type, htb_id, name, total_flags) in content {
+        if get_tracked_content(htb_api.config.team_id, content_type, htb_id, &connection)?.is_some()
+        {
+            continue;
+        }
+
+        // Endgames don't list how many flags they have, so we have to ask for them.
+        let total_flags = match total_flags {
+            Some(total_flags) => total_flags,
+            None => htb_api
+                .get_content_flags(content_type, htb_id)
+                .await?
+                .data
+                .len() as i32,
+        };
+
+        println!(
+            "HTB: Found a {} that we haven't got, adding now...",
+            content_type.display_name()
+        );
+
+        insert_into(htb_content_dsl::htb_tracked_content)
+            .values((
+                htb_content_dsl::htb_id.eq(htb_id),
+                htb_content_dsl::content_type.eq(content_type),
+                htb_content_dsl::name.eq(name),
+                htb_content_dsl::total_flags.eq(total_flags),
+                htb_content_dsl::team_id.eq(htb_api.config.team_id),
+            ))
+            .execute(&connection)?;
+    }
+
+    Ok(())
+}
+
+pub async fn get_solves_to_announce(team_id: i32) -> Result<Vec<SolveToAnnounce>, Error> {
     let connection = get_pooled_connection().await?;
 
     let mut solves_to_announce = vec![];
 
-    for solve in get_unannounced_solves(&connection)? {
-        let challenges = get_challenge_from_id_with_connection(solve.challenge_id, &connection)?;
+    for solve in get_unannounced_solves(team_id, &connection)? {
+        let challenges = get_challenge_from_id_with_connection(
+            team_id,
+            solve.challenge_id,
+            HTBContentType::from_solve_type(&solve.solve_type),
+            &connection,
+        )?;
 
         if !challenges.is_empty() {
             let solve_to_announce = SolveToAnnounce {
@@ -217,6 +561,8 @@ pub async fn get_solves_to_announce() -> Result<Vec<SolveToAnnounce>, Error> {
                 user_id: solve.user_id,
                 solve_type: solve.solve_type,
                 challenge: challenges[0].clone(),
+                blood_position: solve.blood_position,
+                team_first: solve.team_first,
             };
 
             solves_to_announce.push(solve_to_announce);
@@ -227,29 +573,35 @@ pub async fn get_solves_to_announce() -> Result<Vec<SolveToAnnounce>, Error> {
 }
 
 pub fn get_solves_for_user(
+    team_id: i32,
     user_id: i32,
     connection: &PooledMysqlConnection,
 ) -> Result<Vec<HTBSolve>, Error> {
     let solves = htb_solve_dsl::htb_solves
+        .filter(htb_solve_dsl::team_id.eq(team_id))
         .filter(htb_solve_dsl::user_id.eq(user_id))
         .load::<HTBSolve>(connection)?;
     Ok(solves)
 }
 
-pub async fn get_solves_for_username(username: &str) -> Result<Vec<HTBSolve>, Error> {
+pub async fn get_solves_for_username(team_id: i32, username: &str) -> Result<Vec<HTBSolve>, Error> {
     let connection = get_pooled_connection().await?;
     let solves = htb_solve_dsl::htb_solves
+        .filter(htb_solve_dsl::team_id.eq(team_id))
         .filter(htb_solve_dsl::username.eq(username))
         .load::<HTBSolve>(&connection)?;
     Ok(solves)
 }
 
-pub async fn get_solving_users_for_challenge(challenge_id: i32) -> Result<Vec<String>, Error> {
+pub async fn get_solving_users_for_challenge(
+    team_id: i32,
+    challenge_id: i32,
+) -> Result<Vec<String>, Error> {
     let mut solving_users = vec![];
 
     let connection = get_pooled_connection().await?;
 
-    let solves = get_solves_for_challenge(challenge_id, &connection)?;
+    let solves = get_solves_for_challenge(team_id, challenge_id, &connection)?;
 
     if solves.is_empty() {
         return Err(format_err!("No solves for this challenge found!"));
@@ -266,83 +618,102 @@ pub async fn get_solving_users_for_challenge(challenge_id: i32) -> Result<Vec<St
 }
 
 pub fn get_solves_for_challenge(
+    team_id: i32,
     challenge_id: i32,
     connection: &PooledMysqlConnection,
 ) -> Result<Vec<HTBSolve>, Error> {
     let solves = htb_solve_dsl::htb_solves
+        .filter(htb_solve_dsl::team_id.eq(team_id))
         .filter(htb_solve_dsl::challenge_id.eq(challenge_id))
         .load::<HTBSolve>(connection)?;
     Ok(solves)
 }
 
-pub fn get_unannounced_solves(connection: &PooledMysqlConnection) -> Result<Vec<HTBSolve>, Error> {
+pub fn get_unannounced_solves(
+    team_id: i32,
+    connection: &PooledMysqlConnection,
+) -> Result<Vec<HTBSolve>, Error> {
     let solves = htb_solve_dsl::htb_solves
+        .filter(htb_solve_dsl::team_id.eq(team_id))
         .filter(htb_solve_dsl::announced.eq(false))
         .load::<HTBSolve>(connection)?;
     Ok(solves)
 }
 
 pub fn add_challenge_solved_for_user(
+    team_id: i32,
     user_id: i32,
     username: &str,
     solve_date: &str,
-    challenge_id: i32,
+    challenge: &HTBChallenge,
     solve_type: &str,
+    blood_position: Option<i32>,
     connection: &MysqlConnection,
 ) -> Result<(), Error> {
-    let challenges = get_challenge_from_id_with_connection(challenge_id, connection)?;
+    let solved_time = NaiveDateTime::parse_from_str(solve_date, "%Y-%m-%dT%H:%M:%S.%Z")?;
 
-    if !challenges.is_empty() {
-        let challenge = &challenges[0];
-        let solved_time = NaiveDateTime::parse_from_str(solve_date, "%Y-%m-%dT%H:%M:%S.%Z")?;
+    // User and root owns on machines each get their own team first
+    let previous_owns: i64 = htb_solve_dsl::htb_solves
+        .filter(htb_solve_dsl::team_id.eq(team_id))
+        .filter(htb_solve_dsl::challenge_id.eq(challenge.htb_id))
+        .filter(htb_solve_dsl::solve_type.eq(solve_type))
+        .count()
+        .get_result(connection)?;
 
-        insert_into(htb_solve_dsl::htb_solves)
-            .values((
-                htb_solve_dsl::user_id.eq(user_id),
-                htb_solve_dsl::username.eq(username),
-                htb_solve_dsl::challenge_id.eq(challenge.htb_id),
-                htb_solve_dsl::announced.eq(false),
-                htb_solve_dsl::solve_type.eq(solve_type),
-                htb_solve_dsl::solved_time.eq(solved_time),
-            ))
-            .execute(connection)?;
+    insert_into(htb_solve_dsl::htb_solves)
+        .values((
+            htb_solve_dsl::user_id.eq(user_id),
+            htb_solve_dsl::username.eq(username),
+            htb_solve_dsl::challenge_id.eq(challenge.htb_id),
+            htb_solve_dsl::announced.eq(false),
+            htb_solve_dsl::solve_type.eq(solve_type),
+            htb_solve_dsl::solved_time.eq(solved_time),
+            htb_solve_dsl::blood_position.eq(blood_position),
+            htb_solve_dsl::team_first.eq(previous_owns == 0),
+            htb_solve_dsl::team_id.eq(team_id),
+        ))
+        .execute(connection)?;
 
-        // Remove user as working once they have solved
-        return remove_working_from_challenge(username.to_string(), challenge, connection);
-    }
+    // Remove user as working once they have solved, if we know who they are on Discord
+    let discord_ids = htb_user_mapping_dsl::htb_user_id_mapping
+        .select(htb_user_mapping_dsl::discord_id)
+        .filter(htb_user_mapping_dsl::htb_id.eq(user_id))
+        .load::<i64>(connection)?;
 
-    Err(format_err!("No challenge by that ID was found!"))
+    delete(
+        htb_worker_dsl::htb_challenge_workers
+            .filter(htb_worker_dsl::challenge_id.eq(challenge.id))
+            .filter(htb_worker_dsl::discord_id.eq_any(discord_ids)),
+    )
+    .execute(connection)?;
+
+    Ok(())
 }
 
-pub async fn add_challenge_announced_for_user(
-    solve: &SolveToAnnounce,
-    challenge_id: i32,
-) -> Result<(), Error> {
+pub async fn add_challenge_announced_for_user(solve: &SolveToAnnounce) -> Result<(), Error> {
     let connection = get_pooled_connection().await?;
-    let challenges = get_challenge_from_id_with_connection(challenge_id, &connection)?;
-
-    if !challenges.is_empty() {
-        let challenge = &challenges[0];
 
-        update(htb_solve_dsl::htb_solves)
-            .filter(htb_solve_dsl::user_id.eq(solve.user_id))
-            .filter(htb_solve_dsl::challenge_id.eq(challenge.htb_id))
-            .set(htb_solve_dsl::announced.eq(true))
-            .execute(&connection)?;
-
-        return Ok(());
-    }
+    // The solve type keeps a machine's user and root owns, and a challenge with the same ID, apart
+    update(htb_solve_dsl::htb_solves)
+        .filter(htb_solve_dsl::team_id.eq(solve.challenge.team_id))
+        .filter(htb_solve_dsl::user_id.eq(solve.user_id))
+        .filter(htb_solve_dsl::challenge_id.eq(solve.challenge.htb_id))
+        .filter(htb_solve_dsl::solve_type.eq(&solve.solve_type))
+        .set(htb_solve_dsl::announced.eq(true))
+        .execute(&connection)?;
 
-    Err(format_err!("No challenge by that ID was found!"))
+    Ok(())
 }
 
 pub fn is_challenge_solved_and_not_announced_for_user(
+    team_id: i32,
     user_id: i32,
     challenge_id: i32,
     solve_type: &str,
     connection: &MysqlConnection,
 ) -> bool {
     if let Ok(solves) = htb_solve_dsl::htb_solves
+        .filter(htb_solve_dsl::team_id.eq(team_id))
         .filter(htb_solve_dsl::user_id.eq(user_id))
         .filter(htb_solve_dsl::challenge_id.eq(challenge_id))
         .filter(htb_solve_dsl::solve_type.eq(solve_type))
@@ -359,38 +730,105 @@ pub fn is_challenge_solved_and_not_announced_for_user(
 pub async fn update_htb_challenges_and_scores(htb_api: &HTBApi) -> Result<(), Error> {
     let connection = get_pooled_connection().await?;
 
+    // Don't announce everything that's already out the first time the bot runs
+    let known_content: i64 = htb_dsl::htb_challenges
+        .filter(htb_dsl::team_id.eq(htb_api.config.team_id))
+        .count()
+        .get_result(&connection)?;
+    let announce = known_content > 0;
+
     let challenges = htb_api.list_active_challenges().await?;
     for challenge in challenges.challenges {
         // We don't need to update the score for challenges, they are static
-        ensure_challenge_exists_otherwise_add(&challenge, &connection).await?;
+        ensure_challenge_exists_otherwise_add(
+            htb_api.config.team_id,
+            &challenge.into(),
+            false,
+            announce,
+            &connection,
+        )
+        .await?;
     }
 
     let machines = htb_api.list_active_machines().await?.info;
     for machine in machines {
-        let machine_points = format!("{}", machine.points);
-        let mapped_to_challenge_data = ListActiveChallengesData {
-            id: machine.id,
-            name: machine.name,
-            difficulty: machine.difficulty,
-            points: machine_points,
-            release_date: machine.release,
-            challenge_category_id: 100,
-            machine_avatar: Some(machine.avatar),
-        };
+        ensure_challenge_exists_otherwise_add(
+            htb_api.config.team_id,
+            &machine.into(),
+            false,
+            announce,
+            &connection,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Pages through every retired machine and challenge, adding any we don't have and flagging
+/// anything we've been tracking as active that has since been retired. There are a lot of pages,
+/// so the API is only held for each request, letting commands use it in between.
+pub async fn update_htb_retired_catalogue(htb_api: &Mutex<HTBApi>) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
+    let team_id = htb_api.lock().await.config.team_id;
+
+    let mut page = 1;
+    loop {
+        let retired_machines = htb_api.lock().await.list_retired_machines(page).await?;
+        for machine in retired_machines.data {
+            ensure_challenge_exists_otherwise_add(
+                team_id,
+                &machine.into(),
+                true,
+                false,
+                &connection,
+            )
+            .await?;
+        }
 
-        ensure_challenge_exists_otherwise_add(&mapped_to_challenge_data, &connection).await?;
+        if retired_machines.meta.current_page >= retired_machines.meta.last_page {
+            break;
+        }
+        page += 1;
+    }
+
+    let mut page = 1;
+    loop {
+        let retired_challenges = htb_api.lock().await.list_retired_challenges(page).await?;
+        for challenge in retired_challenges.data {
+            ensure_challenge_exists_otherwise_add(
+                team_id,
+                &challenge.into(),
+                true,
+                false,
+                &connection,
+            )
+            .await?;
+        }
+
+        if retired_challenges.meta.current_page >= retired_challenges.meta.last_page {
+            break;
+        }
+        page += 1;
     }
 
     Ok(())
 }
 
+/// Adds the challenge if we don't have it yet, returning whether it was added. New content is
+/// queued up to be announced when `announce` is set.
 pub async fn ensure_challenge_exists_otherwise_add(
-    challenge: &ListActiveChallengesData,
+    team_id: i32,
+    challenge: &HTBContentData,
+    retired: bool,
+    announce: bool,
     connection: &MysqlConnection,
 ) -> Result<bool, Error> {
     let challenges = htb_dsl::htb_challenges
+        .filter(htb_dsl::team_id.eq(team_id))
         .filter(htb_dsl::htb_id.eq(&challenge.id))
         .filter(htb_dsl::name.eq(&challenge.name))
+        .filter(htb_dsl::content_type.eq(challenge.content_type))
         .limit(1)
         .load::<HTBChallenge>(connection)?;
 
@@ -401,36 +839,192 @@ pub async fn ensure_challenge_exists_otherwise_add(
                 htb_dsl::htb_id.eq(challenge.id),
                 htb_dsl::name.eq(&challenge.name),
                 htb_dsl::difficulty.eq(&challenge.difficulty),
-                htb_dsl::points.eq(&challenge.points),
-                htb_dsl::release_date.eq(&challenge.release_date),
-                htb_dsl::challenge_category.eq(&challenge.challenge_category_id),
+                htb_dsl::points.eq(challenge.points),
+                htb_dsl::release_date.eq(challenge.release_date),
+                htb_dsl::challenge_category.eq(challenge.challenge_category),
+                htb_dsl::content_type.eq(challenge.content_type),
                 htb_dsl::machine_avatar.eq(&challenge.machine_avatar),
+                htb_dsl::retired.eq(retired),
+                htb_dsl::retired_date.eq(challenge.retired_date),
+                htb_dsl::os.eq(&challenge.os),
+                htb_dsl::announced.eq(!announce),
+                htb_dsl::team_id.eq(team_id),
             ))
             .execute(connection)?;
 
         return Ok(true);
     }
 
+    if retired && !challenges[0].retired {
+        println!("HTB: {} has been retired, updating now...", challenge.name);
+        update(htb_dsl::htb_challenges)
+            .filter(htb_dsl::id.eq(challenges[0].id))
+            .set((
+                htb_dsl::retired.eq(true),
+                htb_dsl::retired_date.eq(challenge.retired_date),
+            ))
+            .execute(connection)?;
+    }
+
     Ok(false)
 }
 
-pub async fn insert_rank_into_db(rank_stats: &RankStats) -> Result<(), Error> {
+pub async fn get_new_content_to_announce(team_id: i32) -> Result<Vec<HTBChallenge>, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let challenges = htb_dsl::htb_challenges
+        .filter(htb_dsl::team_id.eq(team_id))
+        .filter(htb_dsl::announced.eq(false))
+        .load::<HTBChallenge>(&connection)?;
+
+    Ok(challenges)
+}
+
+pub async fn set_content_announced(id: i32) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
+
+    update(htb_dsl::htb_challenges)
+        .filter(htb_dsl::id.eq(id))
+        .set(htb_dsl::announced.eq(true))
+        .execute(&connection)?;
+
+    Ok(())
+}
+
+/// Keeps our list of scheduled machine releases in line with HTB's.
+pub async fn update_htb_scheduled_releases(htb_api: &HTBApi) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
+
+    let unreleased = htb_api.list_unreleased_machines().await?.data;
+
+    for machine in &unreleased {
+        let release_time = match parse_htb_date(&machine.release) {
+            Some(release_time) => release_time,
+            None => {
+                eprintln!(
+                    "HTB: Unable to parse release date '{}' for {}",
+                    machine.release, machine.name
+                );
+                continue;
+            }
+        };
+
+        let existing = htb_release_dsl::htb_scheduled_releases
+            .filter(htb_release_dsl::team_id.eq(htb_api.config.team_id))
+            .filter(htb_release_dsl::htb_id.eq(machine.id))
+            .load::<HTBScheduledRelease>(&connection)?;
+
+        match existing.first() {
+            None => {
+                println!("HTB: Found scheduled release for {}", machine.name);
+                insert_into(htb_release_dsl::htb_scheduled_releases)
+                    .values((
+                        htb_release_dsl::htb_id.eq(machine.id),
+                        htb_release_dsl::name.eq(&machine.name),
+                        htb_release_dsl::os.eq(&machine.os),
+                        htb_release_dsl::difficulty.eq(&machine.difficulty_text),
+                        htb_release_dsl::avatar.eq(&machine.avatar),
+                        htb_release_dsl::release_time.eq(release_time),
+                        htb_release_dsl::team_id.eq(htb_api.config.team_id),
+                    ))
+                    .execute(&connection)?;
+            }
+            // Releases do get pushed back, so make sure the reminder goes out at the right time
+            Some(release) if release.release_time != release_time => {
+                update(htb_release_dsl::htb_scheduled_releases)
+                    .filter(htb_release_dsl::id.eq(release.id))
+                    .set((
+                        htb_release_dsl::release_time.eq(release_time),
+                        htb_release_dsl::reminder_sent.eq(false),
+                    ))
+                    .execute(&connection)?;
+            }
+            Some(_) => {}
+        }
+    }
+
+    // Anything no longer on the list has either been released or pulled
+    let unreleased_ids: Vec<i32> = unreleased.iter().map(|machine| machine.id).collect();
+    delete(htb_release_dsl::htb_scheduled_releases)
+        .filter(htb_release_dsl::team_id.eq(htb_api.config.team_id))
+        .filter(htb_release_dsl::htb_id.ne_all(unreleased_ids))
+        .execute(&connection)?;
+
+    Ok(())
+}
+
+pub async fn get_releases_to_announce(team_id: i32) -> Result<Vec<HTBScheduledRelease>, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let releases = htb_release_dsl::htb_scheduled_releases
+        .filter(htb_release_dsl::team_id.eq(team_id))
+        .filter(htb_release_dsl::countdown_announced.eq(false))
+        .load::<HTBScheduledRelease>(&connection)?;
+
+    Ok(releases)
+}
+
+/// Gets the scheduled releases that are due within the given window and haven't been reminded about.
+pub async fn get_releases_to_remind(
+    team_id: i32,
+    window: chrono::Duration,
+) -> Result<Vec<HTBScheduledRelease>, Error> {
+    let connection = get_pooled_connection().await?;
+
+    // HTB release times are in UTC
+    let now = Utc::now().naive_utc();
+
+    let releases = htb_release_dsl::htb_scheduled_releases
+        .filter(htb_release_dsl::team_id.eq(team_id))
+        .filter(htb_release_dsl::reminder_sent.eq(false))
+        .filter(htb_release_dsl::release_time.gt(now))
+        .filter(htb_release_dsl::release_time.le(now + window))
+        .load::<HTBScheduledRelease>(&connection)?;
+
+    Ok(releases)
+}
+
+pub async fn set_release_countdown_announced(id: i32) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
+
+    update(htb_release_dsl::htb_scheduled_releases)
+        .filter(htb_release_dsl::id.eq(id))
+        .set(htb_release_dsl::countdown_announced.eq(true))
+        .execute(&connection)?;
+
+    Ok(())
+}
+
+pub async fn set_release_reminder_sent(id: i32) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
+
+    update(htb_release_dsl::htb_scheduled_releases)
+        .filter(htb_release_dsl::id.eq(id))
+        .set(htb_release_dsl::reminder_sent.eq(true))
+        .execute(&connection)?;
+
+    Ok(())
+}
+
+pub async fn insert_rank_into_db(team_id: i32, rank_stats: &RankStats) -> Result<(), Error> {
     let connection = get_pooled_connection().await?;
 
     insert_into(htb_rank_dsl::htb_team_rank)
         .values((
             htb_rank_dsl::rank.eq(&rank_stats.data.rank),
             htb_rank_dsl::points.eq(&rank_stats.data.points),
+            htb_rank_dsl::team_id.eq(team_id),
         ))
         .execute(&connection)?;
 
     Ok(())
 }
 
-pub async fn get_latest_rank_from_db() -> Result<HTBRank, Error> {
+pub async fn get_latest_rank_from_db(team_id: i32) -> Result<HTBRank, Error> {
     let connection = get_pooled_connection().await?;
 
     let solves = htb_rank_dsl::htb_team_rank
+        .filter(htb_rank_dsl::team_id.eq(team_id))
         .order(htb_rank_dsl::entry_id.desc())
         .limit(1)
         .load::<HTBRank>(&connection)?;
@@ -442,10 +1036,174 @@ pub async fn get_latest_rank_from_db() -> Result<HTBRank, Error> {
             rank: 0,
             points: 0,
             timestamp: Local::now().naive_local(),
+            team_id,
         }),
     }
 }
 
+/// Gets the team's rank history, oldest first, optionally limited to a time range. The history
+/// covers the whole range even when the rank didn't change during it, see `span_rank_history`.
+pub async fn get_rank_history(
+    team_id: i32,
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+) -> Result<Vec<HTBRank>, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let mut query = htb_rank_dsl::htb_team_rank
+        .filter(htb_rank_dsl::team_id.eq(team_id))
+        .into_boxed();
+
+    if let Some(from) = from {
+        query = query.filter(htb_rank_dsl::timestamp.ge(from));
+    }
+
+    if let Some(to) = to {
+        query = query.filter(htb_rank_dsl::timestamp.le(to));
+    }
+
+    let history = query
+        .order(htb_rank_dsl::timestamp.asc())
+        .load::<HTBRank>(&connection)?;
+
+    let before = match from {
+        Some(from) => htb_rank_dsl::htb_team_rank
+            .filter(htb_rank_dsl::team_id.eq(team_id))
+            .filter(htb_rank_dsl::timestamp.lt(from))
+            .order(htb_rank_dsl::timestamp.desc())
+            .limit(1)
+            .load::<HTBRank>(&connection)?
+            .into_iter()
+            .next(),
+        None => None,
+    };
+
+    let now = Local::now().naive_local();
+    let end = to.map_or(now, |to| to.min(now));
+
+    Ok(span_rank_history(history, before, from, end))
+}
+
+/// Stores a snapshot of every team member, skipping anyone whose stats haven't changed since their last one.
+/// Anyone no longer in the team is forgotten, so they drop off the leaderboard.
+pub async fn update_team_member_snapshots(htb_api: &HTBApi) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
+    let team_id = htb_api.config.team_id;
+    let members = htb_api.list_team_members().await?;
+    let member_ids: Vec<i32> = members.iter().map(|member| member.id).collect();
+
+    set_team_members(team_id, &member_ids, &connection)?;
+    let latest = get_latest_member_snapshots(team_id, &member_ids, None, &connection)?;
+
+    for member in members {
+        let unchanged = latest.iter().any(|snapshot| {
+            snapshot.htb_id == member.id
+                && snapshot.rank == member.rank
+                && snapshot.points == member.points
+                && snapshot.user_owns == member.user_owns
+                && snapshot.root_owns == member.root_owns
+        });
+
+        if unchanged {
+            continue;
+        }
+
+        insert_into(htb_member_dsl::htb_member_snapshots)
+            .values((
+                htb_member_dsl::htb_id.eq(member.id),
+                htb_member_dsl::name.eq(&member.name),
+                htb_member_dsl::rank.eq(member.rank),
+                htb_member_dsl::rank_text.eq(&member.rank_text),
+                htb_member_dsl::points.eq(member.points),
+                htb_member_dsl::user_owns.eq(member.user_owns),
+                htb_member_dsl::root_owns.eq(member.root_owns),
+                htb_member_dsl::team_id.eq(team_id),
+            ))
+            .execute(&connection)?;
+    }
+
+    Ok(())
+}
+
+/// Records who's in the team right now.
+fn set_team_members(
+    team_id: i32,
+    member_ids: &[i32],
+    connection: &MysqlConnection,
+) -> Result<(), Error> {
+    delete(
+        htb_team_member_dsl::htb_team_members
+            .filter(htb_team_member_dsl::team_id.eq(team_id))
+            .filter(diesel::dsl::not(
+                htb_team_member_dsl::htb_id.eq_any(member_ids),
+            )),
+    )
+    .execute(connection)?;
+
+    let known = htb_team_member_dsl::htb_team_members
+        .select(htb_team_member_dsl::htb_id)
+        .filter(htb_team_member_dsl::team_id.eq(team_id))
+        .load::<i32>(connection)?;
+
+    for htb_id in member_ids.iter().filter(|htb_id| !known.contains(htb_id)) {
+        insert_into(htb_team_member_dsl::htb_team_members)
+            .values((
+                htb_team_member_dsl::team_id.eq(team_id),
+                htb_team_member_dsl::htb_id.eq(htb_id),
+            ))
+            .execute(connection)?;
+    }
+
+    Ok(())
+}
+
+/// Each of the given members' latest snapshot, optionally only looking at those taken at or
+/// before the given time.
+fn get_latest_member_snapshots(
+    team_id: i32,
+    member_ids: &[i32],
+    before: Option<NaiveDateTime>,
+    connection: &MysqlConnection,
+) -> Result<Vec<HTBMemberSnapshot>, Error> {
+    let latest = diesel::dsl::max(htb_member_dsl::entry_id);
+    let members = htb_member_dsl::htb_member_snapshots
+        .filter(htb_member_dsl::team_id.eq(team_id))
+        .filter(htb_member_dsl::htb_id.eq_any(member_ids));
+
+    let entry_ids = match before {
+        Some(before) => members
+            .filter(htb_member_dsl::timestamp.le(before))
+            .group_by(htb_member_dsl::htb_id)
+            .select(latest)
+            .load::<Option<i32>>(connection)?,
+        None => members
+            .group_by(htb_member_dsl::htb_id)
+            .select(latest)
+            .load::<Option<i32>>(connection)?,
+    };
+    let entry_ids: Vec<i32> = entry_ids.into_iter().flatten().collect();
+
+    Ok(htb_member_dsl::htb_member_snapshots
+        .filter(htb_member_dsl::entry_id.eq_any(entry_ids))
+        .load::<HTBMemberSnapshot>(connection)?)
+}
+
+/// Internal team standings, with movement compared to a week ago.
+pub async fn get_team_leaderboard(team_id: i32) -> Result<Vec<LeaderboardEntry>, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let week_ago = Local::now().naive_local() - chrono::Duration::days(7);
+    let member_ids = htb_team_member_dsl::htb_team_members
+        .select(htb_team_member_dsl::htb_id)
+        .filter(htb_team_member_dsl::team_id.eq(team_id))
+        .load::<i32>(&connection)?;
+
+    let latest = get_latest_member_snapshots(team_id, &member_ids, None, &connection)?;
+    let baseline = get_latest_member_snapshots(team_id, &member_ids, Some(week_ago), &connection)?;
+
+    Ok(build_leaderboard(&latest, &baseline))
+}
+
 pub async fn get_discord_id_for(htb_id: i32) -> Result<i64, Error> {
     let connection = get_pooled_connection().await?;
 
@@ -497,6 +1255,170 @@ pub async fn set_discord_id_for(htb_id: i32, discord_id: i64) -> Result<(), Erro
     }
 }
 
+/// How long a link code can be used for before the user needs to request a new one.
+const LINK_REQUEST_VALID_MINUTES: i64 = 60;
+
+fn generate_link_code(discord_id: i64) -> String {
+    // RandomState is seeded randomly per instance, so this is unpredictable without pulling in a rand crate.
+    let mut hasher = RandomState::new().build_hasher();
+    discord_id.hash(&mut hasher);
+    Local::now().timestamp_nanos().hash(&mut hasher);
+
+    format!("ctfd-rs-{:08x}", hasher.finish() as u32)
+}
+
+/// Starts linking the given HTB account to a discord user, returning the code they need to put in their HTB profile.
+pub async fn create_link_request(htb_id: i32, discord_id: i64) -> Result<String, Error> {
+    let connection = get_pooled_connection().await?;
+
+    // Only the latest request for a user is valid
+    delete(htb_link_dsl::htb_link_requests)
+        .filter(htb_link_dsl::discord_id.eq(discord_id))
+        .execute(&connection)?;
+
+    let code = generate_link_code(discord_id);
+
+    insert_into(htb_link_dsl::htb_link_requests)
+        .values((
+            htb_link_dsl::htb_id.eq(htb_id),
+            htb_link_dsl::discord_id.eq(discord_id),
+            htb_link_dsl::code.eq(&code),
+            htb_link_dsl::created_at.eq(Local::now().naive_local()),
+        ))
+        .execute(&connection)?;
+
+    Ok(code)
+}
+
+pub async fn get_link_request_for(discord_id: i64) -> Result<Option<HTBLinkRequest>, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let valid_since =
+        Local::now().naive_local() - chrono::Duration::minutes(LINK_REQUEST_VALID_MINUTES);
+
+    let requests = htb_link_dsl::htb_link_requests
+        .filter(htb_link_dsl::discord_id.eq(discord_id))
+        .filter(htb_link_dsl::created_at.gt(valid_since))
+        .load::<HTBLinkRequest>(&connection)?;
+
+    Ok(requests.into_iter().next())
+}
+
+/// Checks that the code from the user's pending link request is in their HTB profile, and links the accounts if it is.
+///
+/// Returns the HTB name of the linked account.
+pub async fn verify_link_request(discord_id: i64, htb_api: &HTBApi) -> Result<String, Error> {
+    let request = get_link_request_for(discord_id).await?.ok_or_else(|| {
+        format_err!("No pending link found, it may have expired. Use ``!htb link <htb id>`` to get a new code.")
+    })?;
+
+    let profile = htb_api.get_user_overview(request.htb_id).await?.profile;
+
+    let has_code = profile
+        .description
+        .as_deref()
+        .map_or(false, |description| description.contains(&request.code));
+
+    if !has_code {
+        return Err(format_err!(
+            "Couldn't find ``{}`` in the profile description of {}, it can take a minute for HTB to update.",
+            request.code,
+            profile.name
+        ));
+    }
+
+    set_discord_id_for(request.htb_id, discord_id).await?;
+
+    let connection = get_pooled_connection().await?;
+    delete(htb_link_dsl::htb_link_requests)
+        .filter(htb_link_dsl::discord_id.eq(discord_id))
+        .execute(&connection)?;
+
+    Ok(profile.name)
+}
+
+/// Removes every HTB account linked to the given discord user, returning how many were removed.
+pub async fn remove_discord_id_mappings(discord_id: i64) -> Result<usize, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let removed = delete(htb_user_mapping_dsl::htb_user_id_mapping)
+        .filter(htb_user_mapping_dsl::discord_id.eq(discord_id))
+        .execute(&connection)?;
+
+    Ok(removed)
+}
+
+pub async fn set_rank_id_for(htb_id: i32, rank_id: i32) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
+
+    update(htb_user_mapping_dsl::htb_user_id_mapping)
+        .filter(htb_user_mapping_dsl::htb_id.eq(htb_id))
+        .set(htb_user_mapping_dsl::rank_id.eq(rank_id))
+        .execute(&connection)?;
+
+    Ok(())
+}
+
+pub async fn get_all_user_mappings() -> Result<Vec<HTBUserMapping>, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let mappings = htb_user_mapping_dsl::htb_user_id_mapping
+        .order(htb_user_mapping_dsl::htb_id.asc())
+        .load::<HTBUserMapping>(&connection)?;
+
+    Ok(mappings)
+}
+
+/// The HTB teams that have a rank recorded, rows not yet claimed by a team aren't counted.
+pub async fn get_htb_team_ids() -> Result<Vec<i32>, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let team_ids = htb_rank_dsl::htb_team_rank
+        .select(htb_rank_dsl::team_id)
+        .filter(htb_rank_dsl::team_id.ne(0))
+        .distinct()
+        .order(htb_rank_dsl::team_id.asc())
+        .load::<i32>(&connection)?;
+
+    Ok(team_ids)
+}
+
+/// Hands any HTB rows and routes stored before teams were configurable over to the given team.
+pub async fn claim_unassigned_htb_rows(team_id: i32) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
+
+    update(htb_dsl::htb_challenges.filter(htb_dsl::team_id.eq(0)))
+        .set(htb_dsl::team_id.eq(team_id))
+        .execute(&connection)?;
+    update(htb_solve_dsl::htb_solves.filter(htb_solve_dsl::team_id.eq(0)))
+        .set(htb_solve_dsl::team_id.eq(team_id))
+        .execute(&connection)?;
+    update(htb_rank_dsl::htb_team_rank.filter(htb_rank_dsl::team_id.eq(0)))
+        .set(htb_rank_dsl::team_id.eq(team_id))
+        .execute(&connection)?;
+    update(htb_member_dsl::htb_member_snapshots.filter(htb_member_dsl::team_id.eq(0)))
+        .set(htb_member_dsl::team_id.eq(team_id))
+        .execute(&connection)?;
+    update(htb_content_dsl::htb_tracked_content.filter(htb_content_dsl::team_id.eq(0)))
+        .set(htb_content_dsl::team_id.eq(team_id))
+        .execute(&connection)?;
+    update(htb_flag_dsl::htb_flag_captures.filter(htb_flag_dsl::team_id.eq(0)))
+        .set(htb_flag_dsl::team_id.eq(team_id))
+        .execute(&connection)?;
+    update(htb_release_dsl::htb_scheduled_releases.filter(htb_release_dsl::team_id.eq(0)))
+        .set(htb_release_dsl::team_id.eq(team_id))
+        .execute(&connection)?;
+    update(
+        route_dsl::announcement_routes
+            .filter(route_dsl::source.eq(RouteSource::HTB.as_str()))
+            .filter(route_dsl::team_id.is_null()),
+    )
+    .set(route_dsl::team_id.eq(team_id))
+    .execute(&connection)?;
+
+    Ok(())
+}
+
 #[tokio::main]
 pub async fn load_categories_to_cache(htb_api: &HTBApi) -> Result<(), Error> {
     let challenge_categories_response = htb_api.get_challenge_categories().await?;
@@ -505,8 +1427,19 @@ pub async fn load_categories_to_cache(htb_api: &HTBApi) -> Result<(), Error> {
         CATEGORY_CACHE.insert(category.id, category.name);
     }
 
-    // Machines don't have a category, so we add one with an ID that wont collide
-    CATEGORY_CACHE.insert(100, "Machine".to_owned());
-
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_link_code() {
+        let code = generate_link_code(123456789);
+
+        assert!(code.starts_with("ctfd-rs-"));
+        assert_eq!(code.len(), "ctfd-rs-".len() + 8);
+        assert_ne!(code, generate_link_code(123456789));
+    }
+}
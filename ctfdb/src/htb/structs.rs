@@ -1,9 +1,17 @@
+use std::collections::HashMap;
+use std::io::Write;
+
 use base64::decode;
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+use diesel::deserialize::{self, FromSql};
+use diesel::mysql::Mysql;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
 use failure::Error;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
-use crate::models::HTBChallenge;
+use crate::models::{HTBChallenge, HTBMemberSnapshot, HTBRank, HTBTrackedContent};
 
 // All information from https://github.com/Propolisa/htb-api-docs
 
@@ -37,6 +45,10 @@ pub struct UserOverview {
 pub struct UserOverviewData {
     pub id: i32,
     pub name: String,
+    pub description: Option<String>,
+    pub rank: Option<String>,
+    /// Goes up with each rank, so it can be used to spot promotions.
+    pub rank_id: Option<i32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -57,6 +69,47 @@ pub struct GetRecentTeamActivityData {
     pub name: String,
     pub points: i32,
     pub challenge_category: Option<String>,
+    pub flag_title: Option<String>,
+    #[serde(default)]
+    pub first_blood: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TopOwns {
+    pub info: Vec<TopOwnData>,
+}
+
+/// An entry in the list of the earliest owns of a machine or challenge.
+#[derive(Debug, Deserialize)]
+pub struct TopOwnData {
+    pub id: i32,
+    pub name: String,
+    pub own_date: String,
+    /// `user` or `root` for machines, missing for challenges.
+    #[serde(rename = "type")]
+    pub own_type: Option<String>,
+}
+
+/// How many of the earliest owns count as a blood.
+pub const BLOOD_POSITIONS: usize = 3;
+
+/// Works out whether the given user's own was a first, second or third blood.
+pub fn blood_position(owns: &[TopOwnData], user_id: i32, solve_type: &str) -> Option<i32> {
+    let mut matching: Vec<&TopOwnData> = owns
+        .iter()
+        .filter(|own| {
+            own.own_type
+                .as_deref()
+                .map_or(true, |own_type| own_type == solve_type)
+        })
+        .collect();
+    matching.sort_by(|a, b| a.own_date.cmp(&b.own_date));
+
+    matching
+        .iter()
+        .take(BLOOD_POSITIONS)
+        .position(|own| own.id == user_id)
+        .map(|position| position as i32 + 1)
 }
 
 #[derive(Debug, Deserialize)]
@@ -103,6 +156,108 @@ pub struct ListActiveChallengesData {
     pub release_date: String,
     pub challenge_category_id: i32,
     pub machine_avatar: Option<String>,
+    pub os: Option<String>,
+    pub retired_date: Option<String>,
+}
+
+/// Whether a row in `htb_challenges` is a machine or a challenge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[sql_type = "Text"]
+pub enum HTBContentType {
+    Machine,
+    Challenge,
+}
+
+impl HTBContentType {
+    /// The value stored in the `content_type` column, which is also HTB's `object_type` in activity feeds.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HTBContentType::Machine => "machine",
+            HTBContentType::Challenge => "challenge",
+        }
+    }
+
+    pub fn from_content_type(content_type: &str) -> Option<HTBContentType> {
+        match content_type {
+            "machine" => Some(HTBContentType::Machine),
+            "challenge" => Some(HTBContentType::Challenge),
+            _ => None,
+        }
+    }
+
+    /// What a solve was on, going by its ``solve_type``. Machines are owned as user or root,
+    /// challenges are just solved.
+    pub fn from_solve_type(solve_type: &str) -> HTBContentType {
+        match solve_type {
+            "challenge" => HTBContentType::Challenge,
+            _ => HTBContentType::Machine,
+        }
+    }
+}
+
+impl ToSql<Text, Mysql> for HTBContentType {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Mysql>) -> serialize::Result {
+        <str as ToSql<Text, Mysql>>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, Mysql> for HTBContentType {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let content_type = <String as FromSql<Text, Mysql>>::from_sql(bytes)?;
+
+        HTBContentType::from_content_type(&content_type)
+            .ok_or_else(|| format!("Unknown HTB content type '{}'", content_type).into())
+    }
+}
+
+/// A machine or challenge from any of HTB's listings, normalised so it can be stored in `htb_challenges`.
+#[derive(Debug, Clone)]
+pub struct HTBContentData {
+    pub id: i32,
+    pub name: String,
+    pub difficulty: String,
+    pub points: i32,
+    pub release_date: Option<NaiveDateTime>,
+    pub content_type: HTBContentType,
+    pub challenge_category: Option<i32>,
+    pub machine_avatar: Option<String>,
+    pub os: Option<String>,
+    pub retired_date: Option<NaiveDateTime>,
+}
+
+impl From<ListActiveChallengesData> for HTBContentData {
+    fn from(challenge: ListActiveChallengesData) -> Self {
+        HTBContentData {
+            id: challenge.id,
+            name: challenge.name,
+            difficulty: challenge.difficulty,
+            // HTB sends challenge points as a string
+            points: challenge.points.parse().unwrap_or(0),
+            release_date: parse_htb_date(&challenge.release_date),
+            content_type: HTBContentType::Challenge,
+            challenge_category: Some(challenge.challenge_category_id),
+            machine_avatar: challenge.machine_avatar,
+            os: challenge.os,
+            retired_date: challenge.retired_date.as_deref().and_then(parse_htb_date),
+        }
+    }
+}
+
+impl From<ListActiveMachinesData> for HTBContentData {
+    fn from(machine: ListActiveMachinesData) -> Self {
+        HTBContentData {
+            id: machine.id,
+            name: machine.name,
+            difficulty: machine.difficulty,
+            points: machine.points,
+            release_date: parse_htb_date(&machine.release),
+            content_type: HTBContentType::Machine,
+            challenge_category: None,
+            machine_avatar: Some(machine.avatar),
+            os: Some(machine.os),
+            retired_date: machine.retired_date.as_deref().and_then(parse_htb_date),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -119,6 +274,35 @@ pub struct ListActiveMachinesData {
     pub points: i32,
     pub release: String,
     pub avatar: String,
+    pub os: String,
+    pub retired_date: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListUnreleasedMachines {
+    pub data: Vec<UnreleasedMachineData>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnreleasedMachineData {
+    pub id: i32,
+    pub name: String,
+    pub os: String,
+    pub avatar: String,
+    pub release: String,
+    pub difficulty_text: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Paginated<T> {
+    pub data: Vec<T>,
+    pub meta: PaginationMeta,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PaginationMeta {
+    pub current_page: i32,
+    pub last_page: i32,
 }
 
 #[derive(Debug, Deserialize)]
@@ -153,18 +337,335 @@ pub struct RankStatsData {
     pub points: i32,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ListProLabs {
+    pub data: ListProLabsData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListProLabsData {
+    pub labs: Vec<ProLabData>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProLabData {
+    pub id: i32,
+    pub name: String,
+    pub pro_flags_count: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListFortresses {
+    pub data: HashMap<String, FortressData>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FortressData {
+    pub id: i32,
+    pub name: String,
+    pub number_of_flags: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListEndgames {
+    pub data: Vec<EndgameData>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EndgameData {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListContentFlags {
+    pub data: Vec<ContentFlagData>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContentFlagData {
+    pub id: i32,
+    pub title: String,
+    pub points: i32,
+}
+
+/// HTB content that is tracked by flags captured rather than user/root owns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[sql_type = "Text"]
+pub enum TrackedContentType {
+    ProLab,
+    Fortress,
+    Endgame,
+}
+
+impl TrackedContentType {
+    pub const ALL: [TrackedContentType; 3] = [
+        TrackedContentType::ProLab,
+        TrackedContentType::Fortress,
+        TrackedContentType::Endgame,
+    ];
+
+    /// The name used by HTB for both the API path and the `object_type` in activity feeds.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrackedContentType::ProLab => "prolab",
+            TrackedContentType::Fortress => "fortress",
+            TrackedContentType::Endgame => "endgame",
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            TrackedContentType::ProLab => "Pro Lab",
+            TrackedContentType::Fortress => "Fortress",
+            TrackedContentType::Endgame => "Endgame",
+        }
+    }
+
+    pub fn from_object_type(object_type: &str) -> Option<TrackedContentType> {
+        TrackedContentType::ALL
+            .iter()
+            .find(|content_type| content_type.as_str() == object_type)
+            .copied()
+    }
+}
+
+impl ToSql<Text, Mysql> for TrackedContentType {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Mysql>) -> serialize::Result {
+        <str as ToSql<Text, Mysql>>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, Mysql> for TrackedContentType {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let content_type = <String as FromSql<Text, Mysql>>::from_sql(bytes)?;
+
+        TrackedContentType::from_object_type(&content_type)
+            .ok_or_else(|| format!("Unknown tracked content type '{}'", content_type).into())
+    }
+}
+
+#[derive(Debug)]
+pub struct FlagCaptureToAnnounce {
+    pub capture_id: i32,
+    pub solver: String,
+    pub flag_title: String,
+    pub points: i32,
+    pub content: HTBTrackedContent,
+    pub flags_captured: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeaderboardEntry {
+    pub htb_id: i32,
+    pub name: String,
+    pub rank: i32,
+    pub rank_text: String,
+    pub points: i32,
+    pub user_owns: i32,
+    pub root_owns: i32,
+    pub points_gained: i32,
+    pub owns_gained: i32,
+}
+
+/// How finely to group the team's rank history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RankBucket {
+    Hour,
+    Day,
+    Week,
+}
+
+impl RankBucket {
+    pub fn from_name(name: &str) -> Option<RankBucket> {
+        match name.to_lowercase().as_str() {
+            "hour" => Some(RankBucket::Hour),
+            "day" => Some(RankBucket::Day),
+            "week" => Some(RankBucket::Week),
+            _ => None,
+        }
+    }
+
+    /// The start of the bucket the given time falls in. Weeks start on Monday.
+    pub fn start_of(&self, time: NaiveDateTime) -> NaiveDateTime {
+        match self {
+            RankBucket::Hour => time.date().and_hms(time.hour(), 0, 0),
+            RankBucket::Day => time.date().and_hms(0, 0, 0),
+            RankBucket::Week => {
+                let days_since_monday = time.weekday().num_days_from_monday() as i64;
+                (time.date() - Duration::days(days_since_monday)).and_hms(0, 0, 0)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RankHistoryPoint {
+    pub timestamp: NaiveDateTime,
+    pub rank: i32,
+    pub points: i32,
+}
+
+/// Stretches the team's rank history (oldest first) over a whole period. The rank from before the
+/// period still held when it started, and the latest rank holds until it ends.
+pub fn span_rank_history(
+    mut history: Vec<HTBRank>,
+    before: Option<HTBRank>,
+    from: Option<NaiveDateTime>,
+    end: NaiveDateTime,
+) -> Vec<HTBRank> {
+    if let (Some(mut before), Some(from)) = (before, from) {
+        if history.first().map_or(true, |first| first.timestamp > from) {
+            before.timestamp = from;
+            history.insert(0, before);
+        }
+    }
+
+    if let Some(last) = history.last() {
+        if last.timestamp < end {
+            let mut latest = last.clone();
+            latest.timestamp = end;
+            history.push(latest);
+        }
+    }
+
+    history
+}
+
+/// Groups the rank history (oldest first) into buckets, keeping where the team ended up in each one.
+/// Without a bucket every change is kept.
+pub fn bucket_rank_history(
+    history: &[HTBRank],
+    bucket: Option<RankBucket>,
+) -> Vec<RankHistoryPoint> {
+    let mut points: Vec<RankHistoryPoint> = vec![];
+
+    for entry in history {
+        let timestamp = match bucket {
+            Some(bucket) => bucket.start_of(entry.timestamp),
+            None => entry.timestamp,
+        };
+
+        let point = RankHistoryPoint {
+            timestamp,
+            rank: entry.rank,
+            points: entry.points,
+        };
+
+        match points.last_mut() {
+            Some(last) if bucket.is_some() && last.timestamp == timestamp => *last = point,
+            _ => points.push(point),
+        }
+    }
+
+    points
+}
+
+/// Builds the internal standings from every member's latest snapshot, comparing against their
+/// baseline snapshot (e.g. from a week ago) to work out how much they've moved.
+/// Members without a baseline are compared against themselves, so they haven't moved.
+pub fn build_leaderboard(
+    latest: &[HTBMemberSnapshot],
+    baseline: &[HTBMemberSnapshot],
+) -> Vec<LeaderboardEntry> {
+    let mut entries: Vec<LeaderboardEntry> = latest
+        .iter()
+        .map(|snapshot| {
+            let base = baseline
+                .iter()
+                .find(|base| base.htb_id == snapshot.htb_id)
+                .unwrap_or(snapshot);
+
+            LeaderboardEntry {
+                htb_id: snapshot.htb_id,
+                name: snapshot.name.clone(),
+                rank: snapshot.rank,
+                rank_text: snapshot.rank_text.clone(),
+                points: snapshot.points,
+                user_owns: snapshot.user_owns,
+                root_owns: snapshot.root_owns,
+                points_gained: snapshot.points - base.points,
+                owns_gained: (snapshot.user_owns + snapshot.root_owns)
+                    - (base.user_owns + base.root_owns),
+            }
+        })
+        .collect();
+
+    entries.sort_by(|a, b| b.points.cmp(&a.points).then_with(|| a.name.cmp(&b.name)));
+    entries
+}
+
 #[derive(Debug)]
 pub struct SolveToAnnounce {
     pub solver: String,
     pub user_id: i32,
     pub solve_type: String,
     pub challenge: HTBChallenge,
+    pub blood_position: Option<i32>,
+    pub team_first: bool,
+}
+
+/// How the bot authenticates against HTB.
+#[derive(Debug)]
+pub enum HTBAuth {
+    /// Logs in with email & password, renewing the token whenever it expires. Doesn't work with 2FA.
+    Credentials { email: String, password: String },
+    /// A long-lived app token generated from the HTB profile settings. It can't be renewed by the bot.
+    AppToken(String),
+}
+
+/// Filters for searching the HTB challenge & machine catalogue, every filter is optional.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct HTBSearchFilter {
+    pub name: Option<String>,
+    pub retired: Option<bool>,
+    pub difficulty: Option<String>,
+    pub os: Option<String>,
+}
+
+impl HTBSearchFilter {
+    /// Builds a filter from command style arguments, e.g. `["Lame", "retired", "os=linux"]`.
+    /// Anything that isn't `active`, `retired` or a `key=value` pair is treated as the name.
+    pub fn from_args(args: &[String]) -> HTBSearchFilter {
+        let mut filter = HTBSearchFilter::default();
+        let mut name_parts = vec![];
+
+        for arg in args {
+            let lowercase = arg.to_lowercase();
+
+            if lowercase == "active" {
+                filter.retired = Some(false);
+            } else if lowercase == "retired" {
+                filter.retired = Some(true);
+            } else if let Some(difficulty) = lowercase.strip_prefix("difficulty=") {
+                filter.difficulty = Some(difficulty.to_string());
+            } else if let Some(os) = lowercase.strip_prefix("os=") {
+                filter.os = Some(os.to_string());
+            } else {
+                name_parts.push(arg.as_str());
+            }
+        }
+
+        if !name_parts.is_empty() {
+            filter.name = Some(name_parts.join(" "));
+        }
+
+        filter
+    }
+
+    /// Parses the `status` value used by the rest-api, `active` or `retired`.
+    pub fn retired_from_status(status: &str) -> Option<bool> {
+        match status.to_lowercase().as_str() {
+            "active" => Some(false),
+            "retired" => Some(true),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct HTBAPIConfig {
-    pub email: String,
-    pub password: String,
+    pub auth: HTBAuth,
     pub team_id: i32,
 }
 
@@ -173,6 +674,7 @@ pub struct HTBApi {
     pub config: HTBAPIConfig,
     pub client: Client,
     pub jwt: JWTClaims,
+    pub expiry_warning_sent: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -180,6 +682,15 @@ pub struct JWTClaims {
     pub exp: i64,
 }
 
+/// Parses the various date formats HTB hands out, e.g. `2017-05-26T19:00:00.000000Z` or `2021-06-26 17:00:00`.
+pub fn parse_htb_date(date: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%.fZ")
+        .or_else(|_| NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%.f"))
+        .or_else(|_| NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S"))
+        .or_else(|_| NaiveDate::parse_from_str(date, "%Y-%m-%d").map(|d| d.and_hms(0, 0, 0)))
+        .ok()
+}
+
 pub fn parse_jwt(token: &str) -> Result<JWTClaims, Error> {
     let b64url = token.split('.').collect::<Vec<_>>()[1];
     let buffer = b64url.replace("/-/g", "+").replace("/_/g", "/");
@@ -217,6 +728,99 @@ mod tests {
         assert_eq!(recent_data[1].name, "Ophiuchi".to_string());
         assert_eq!(recent_data[1].object_type, "machine".to_string());
         assert!(recent_data[1].challenge_category.is_none());
+        assert!(!recent_data[1].first_blood);
+    }
+
+    #[test]
+    fn test_content_data_from_listings() {
+        let data = read_file_to_string("list_challenges.json");
+        let challenge = serde_json::from_str::<ListActiveChallenges>(&data)
+            .unwrap()
+            .challenges
+            .remove(0);
+        let challenge = HTBContentData::from(challenge);
+
+        assert_eq!(challenge.points, 80);
+        assert_eq!(challenge.content_type, HTBContentType::Challenge);
+        assert!(challenge.challenge_category.is_some());
+        assert_eq!(
+            challenge.release_date,
+            Some(NaiveDate::from_ymd(2017, 6, 30).and_hms(19, 0, 0))
+        );
+
+        let data = read_file_to_string("list_machines.json");
+        let machine = serde_json::from_str::<ListActiveMachines>(&data)
+            .unwrap()
+            .info
+            .remove(0);
+        let machine = HTBContentData::from(machine);
+
+        assert_eq!(machine.points, 50);
+        assert_eq!(machine.content_type, HTBContentType::Machine);
+        assert!(machine.challenge_category.is_none());
+        assert!(machine.release_date.is_some());
+    }
+
+    #[test]
+    fn test_content_type_round_trip() {
+        for content_type in &[HTBContentType::Machine, HTBContentType::Challenge] {
+            assert_eq!(
+                HTBContentType::from_content_type(content_type.as_str()),
+                Some(*content_type)
+            );
+        }
+        assert_eq!(HTBContentType::from_content_type("prolab"), None);
+        assert_eq!(
+            HTBContentType::from_solve_type("root"),
+            HTBContentType::Machine
+        );
+        assert_eq!(
+            HTBContentType::from_solve_type("challenge"),
+            HTBContentType::Challenge
+        );
+    }
+
+    #[test]
+    fn test_deserialise_list_unreleased_machines() {
+        let data = read_file_to_string("list_unreleased_machines.json");
+
+        let unreleased: ListUnreleasedMachines = serde_json::from_str(&data).unwrap();
+
+        assert_eq!(unreleased.data.len(), 2);
+        assert_eq!(unreleased.data[0].name, "Secret");
+        assert_eq!(unreleased.data[1].difficulty_text, "Hard");
+        assert!(parse_htb_date(&unreleased.data[0].release).is_some());
+    }
+
+    #[test]
+    fn test_deserialise_machine_top_owns() {
+        let data = read_file_to_string("machine_top_owns.json");
+
+        let top_owns: TopOwns = serde_json::from_str(&data).unwrap();
+
+        assert_eq!(top_owns.info.len(), 6);
+        assert_eq!(top_owns.info[0].own_type, Some("user".to_string()));
+    }
+
+    #[test]
+    fn test_blood_position() {
+        let data = read_file_to_string("machine_top_owns.json");
+        let owns = serde_json::from_str::<TopOwns>(&data).unwrap().info;
+
+        assert_eq!(blood_position(&owns, 1190, "user"), Some(1));
+        assert_eq!(blood_position(&owns, 66487, "user"), Some(2));
+        assert_eq!(blood_position(&owns, 66487, "root"), Some(2));
+        // Fourth user own isn't a blood
+        assert_eq!(blood_position(&owns, 4, "user"), None);
+        assert_eq!(blood_position(&owns, 508037, "root"), Some(1));
+
+        let challenge_owns = vec![TopOwnData {
+            id: 7,
+            name: "Craig".to_string(),
+            own_date: "2021-06-24T22:25:48.000000Z".to_string(),
+            own_type: None,
+        }];
+        assert_eq!(blood_position(&challenge_owns, 7, "challenge"), Some(1));
     }
 
     #[test]
@@ -227,6 +831,9 @@ mod tests {
         let profile = recent_data.profile;
 
         assert_eq!(508037, profile.id);
+        assert!(profile.description.is_none());
+        assert_eq!(profile.rank, Some("Script Kiddie".to_string()));
+        assert_eq!(profile.rank_id, Some(2));
     }
 
     #[test]
@@ -307,6 +914,276 @@ mod tests {
         assert_eq!(team_stats.data.points, 101);
     }
 
+    #[test]
+    fn test_deserialise_list_prolabs() {
+        let data = read_file_to_string("list_prolabs.json");
+
+        let prolabs: ListProLabs = serde_json::from_str(&data).unwrap();
+        let labs = prolabs.data.labs;
+
+        assert_eq!(labs.len(), 2);
+        assert_eq!(labs[1].name, "Offshore");
+        assert_eq!(labs[1].pro_flags_count, 38);
+    }
+
+    #[test]
+    fn test_deserialise_list_fortresses() {
+        let data = read_file_to_string("list_fortresses.json");
+
+        let fortresses: ListFortresses = serde_json::from_str(&data).unwrap();
+        let jet = &fortresses.data["1"];
+
+        assert_eq!(fortresses.data.len(), 2);
+        assert_eq!(jet.name, "Jet");
+        assert_eq!(jet.number_of_flags, 11);
+    }
+
+    #[test]
+    fn test_deserialise_list_endgames() {
+        let data = read_file_to_string("list_endgames.json");
+
+        let endgames: ListEndgames = serde_json::from_str(&data).unwrap();
+
+        assert_eq!(endgames.data[0].id, 1);
+        assert_eq!(endgames.data[0].name, "P.O.O.");
+    }
+
+    #[test]
+    fn test_deserialise_content_flags() {
+        let data = read_file_to_string("content_flags.json");
+
+        let flags: ListContentFlags = serde_json::from_str(&data).unwrap();
+
+        assert_eq!(flags.data.len(), 2);
+        assert_eq!(flags.data[1].title, "Going Deeper");
+    }
+
+    #[test]
+    fn test_deserialise_recent_activity_flags() {
+        let data = read_file_to_string("recent_activity_flags.json");
+
+        let recent_data: Vec<GetRecentTeamActivityData> = serde_json::from_str(&data).unwrap();
+
+        assert_eq!(recent_data[0].flag_title, Some("Connect".to_string()));
+        assert_eq!(
+            TrackedContentType::from_object_type(&recent_data[0].object_type),
+            Some(TrackedContentType::Fortress)
+        );
+        assert_eq!(
+            TrackedContentType::from_object_type(&recent_data[1].object_type),
+            Some(TrackedContentType::ProLab)
+        );
+        assert_eq!(TrackedContentType::from_object_type("machine"), None);
+    }
+
+    #[test]
+    fn test_deserialise_list_retired_machines() {
+        let data = read_file_to_string("list_retired_machines.json");
+
+        let retired_machines: Paginated<ListActiveMachinesData> =
+            serde_json::from_str(&data).unwrap();
+
+        assert_eq!(retired_machines.data.len(), 2);
+        assert_eq!(retired_machines.data[1].os, "Windows");
+        assert_eq!(
+            retired_machines.data[0].retired_date,
+            Some("2017-05-26T19:00:00.000000Z".to_string())
+        );
+        assert_eq!(retired_machines.meta.last_page, 3);
+    }
+
+    #[test]
+    fn test_deserialise_list_retired_challenges() {
+        let data = read_file_to_string("list_retired_challenges.json");
+
+        let retired_challenges: Paginated<ListActiveChallengesData> =
+            serde_json::from_str(&data).unwrap();
+
+        assert_eq!(retired_challenges.data[0].name, "Find The Easy Pass");
+        assert!(retired_challenges.data[0].retired_date.is_some());
+        assert_eq!(retired_challenges.meta.current_page, 1);
+    }
+
+    #[test]
+    fn test_search_filter_from_args() {
+        let args: Vec<String> = vec!["Lame", "retired", "os=Linux", "difficulty=easy"]
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+
+        let filter = HTBSearchFilter::from_args(&args);
+
+        assert_eq!(
+            filter,
+            HTBSearchFilter {
+                name: Some("Lame".to_string()),
+                retired: Some(true),
+                difficulty: Some("easy".to_string()),
+                os: Some("linux".to_string()),
+            }
+        );
+
+        assert_eq!(HTBSearchFilter::from_args(&[]), HTBSearchFilter::default());
+        assert_eq!(HTBSearchFilter::retired_from_status("Active"), Some(false));
+        assert_eq!(HTBSearchFilter::retired_from_status("all"), None);
+    }
+
+    #[test]
+    fn test_parse_htb_date() {
+        let expected = NaiveDate::from_ymd(2017, 5, 26).and_hms(19, 0, 0);
+
+        assert_eq!(
+            parse_htb_date("2017-05-26T19:00:00.000000Z"),
+            Some(expected)
+        );
+        assert_eq!(parse_htb_date("2017-05-26 19:00:00"), Some(expected));
+        assert_eq!(
+            parse_htb_date("2017-05-26"),
+            Some(NaiveDate::from_ymd(2017, 5, 26).and_hms(0, 0, 0))
+        );
+        assert_eq!(parse_htb_date("soon"), None);
+    }
+
+    fn snapshot(htb_id: i32, name: &str, points: i32, owns: i32) -> HTBMemberSnapshot {
+        HTBMemberSnapshot {
+            entry_id: 0,
+            htb_id,
+            name: name.to_string(),
+            rank: 1000,
+            rank_text: "Hacker".to_string(),
+            points,
+            user_owns: owns,
+            root_owns: owns,
+            timestamp: NaiveDate::from_ymd(2021, 7, 1).and_hms(0, 0, 0),
+            team_id: 0,
+        }
+    }
+
+    fn rank(entry_id: i32, rank: i32, points: i32, timestamp: &str) -> HTBRank {
+        HTBRank {
+            entry_id,
+            rank,
+            points,
+            timestamp: parse_htb_date(timestamp).unwrap(),
+            team_id: 0,
+        }
+    }
+
+    #[test]
+    fn test_rank_bucket_start_of() {
+        // A Thursday
+        let time = parse_htb_date("2021-10-14 15:42:10").unwrap();
+
+        assert_eq!(
+            RankBucket::Hour.start_of(time),
+            parse_htb_date("2021-10-14 15:00:00").unwrap()
+        );
+        assert_eq!(
+            RankBucket::Day.start_of(time),
+            parse_htb_date("2021-10-14").unwrap()
+        );
+        assert_eq!(
+            RankBucket::Week.start_of(time),
+            parse_htb_date("2021-10-11").unwrap()
+        );
+        assert_eq!(RankBucket::from_name("Day"), Some(RankBucket::Day));
+        assert_eq!(RankBucket::from_name("month"), None);
+    }
+
+    #[test]
+    fn test_span_rank_history() {
+        let from = parse_htb_date("2021-10-14 12:00:00").unwrap();
+        let end = parse_htb_date("2021-10-15 12:00:00").unwrap();
+
+        let spanned = span_rank_history(
+            vec![rank(2, 110, 320, "2021-10-14 18:30:00")],
+            Some(rank(1, 120, 300, "2021-10-10 09:00:00")),
+            Some(from),
+            end,
+        );
+        assert_eq!(
+            spanned
+                .iter()
+                .map(|entry| (entry.entry_id, entry.rank, entry.timestamp))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, 120, from),
+                (2, 110, parse_htb_date("2021-10-14 18:30:00").unwrap()),
+                (2, 110, end),
+            ]
+        );
+
+        // Nothing changed during the period, so the rank from before it holds throughout
+        let unchanged = span_rank_history(
+            vec![],
+            Some(rank(1, 120, 300, "2021-10-10 09:00:00")),
+            Some(from),
+            end,
+        );
+        assert_eq!(unchanged.len(), 2);
+
+        assert!(span_rank_history(vec![], None, Some(from), end).is_empty());
+    }
+
+    #[test]
+    fn test_bucket_rank_history() {
+        let history = vec![
+            rank(1, 120, 300, "2021-10-14 09:00:00"),
+            rank(2, 110, 320, "2021-10-14 18:30:00"),
+            rank(3, 105, 330, "2021-10-15 10:00:00"),
+        ];
+
+        let raw = bucket_rank_history(&history, None);
+        assert_eq!(raw.len(), 3);
+
+        let daily = bucket_rank_history(&history, Some(RankBucket::Day));
+        assert_eq!(
+            daily,
+            vec![
+                RankHistoryPoint {
+                    timestamp: parse_htb_date("2021-10-14").unwrap(),
+                    rank: 110,
+                    points: 320,
+                },
+                RankHistoryPoint {
+                    timestamp: parse_htb_date("2021-10-15").unwrap(),
+                    rank: 105,
+                    points: 330,
+                },
+            ]
+        );
+
+        let weekly = bucket_rank_history(&history, Some(RankBucket::Week));
+        assert_eq!(weekly.len(), 1);
+        assert_eq!(weekly[0].rank, 105);
+    }
+
+    #[test]
+    fn test_build_leaderboard() {
+        let latest = vec![
+            snapshot(1, "wulfgarpro", 58, 10),
+            snapshot(2, "MasterAge", 67, 12),
+            snapshot(3, "Jordyn", 3, 1),
+        ];
+        let baseline = vec![
+            snapshot(1, "wulfgarpro", 40, 8),
+            snapshot(2, "MasterAge", 67, 12),
+        ];
+
+        let leaderboard = build_leaderboard(&latest, &baseline);
+
+        assert_eq!(leaderboard[0].name, "MasterAge");
+        assert_eq!(leaderboard[0].points_gained, 0);
+
+        assert_eq!(leaderboard[1].name, "wulfgarpro");
+        assert_eq!(leaderboard[1].points_gained, 18);
+        assert_eq!(leaderboard[1].owns_gained, 4);
+
+        // No baseline, so no movement
+        assert_eq!(leaderboard[2].name, "Jordyn");
+        assert_eq!(leaderboard[2].points_gained, 0);
+    }
+
     #[test]
     fn test_deserialise_jwt() {
         let token = read_file_to_string("jwt.txt");
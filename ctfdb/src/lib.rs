@@ -28,10 +28,13 @@ use htb::structs::JWTClaims;
 
 use crate::r2d2::PooledConnection;
 
+pub mod announcements;
 pub mod ctfs;
 pub mod htb;
 pub mod models;
+pub mod notes;
 pub mod schema;
+pub mod writeups;
 
 type MysqlConnectionPool = r2d2::Pool<ConnectionManager<MysqlConnection>>;
 type PooledMysqlConnection = PooledConnection<ConnectionManager<MysqlConnection>>;
@@ -85,6 +88,10 @@ pub fn create_reqwest_client(api_key: &str, token_type: &str) -> Client {
 pub trait ChallengeProvider {
     fn get_id(&self) -> i32;
     async fn get_challenges(&self) -> Result<Vec<ChallengeResponse>, Error>;
+    async fn challenge_description(
+        &self,
+        challenge: &ChallengeResponse,
+    ) -> Result<Option<String>, Error>;
     async fn get_team_solved_challenges(&self) -> Result<Vec<TeamSolvesResponseData>, Error>;
     async fn user_from_id(&self, id: i32) -> Result<UserResponseData, Error>;
     async fn team_stats(&self) -> Result<MyTeamResponseData, Error>;
@@ -106,6 +113,12 @@ pub fn jwt_still_valid(jwt: &JWTClaims) -> bool {
     true
 }
 
+pub fn jwt_expires_within(jwt: &JWTClaims, window: chrono::Duration) -> bool {
+    let local: DateTime<Local> = Local::now();
+
+    local.timestamp() + window.num_seconds() > jwt.exp
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::Duration;
@@ -131,4 +144,16 @@ mod tests {
 
         assert!(!jwt_still_valid(&jwt));
     }
+
+    #[test]
+    fn test_jwt_expires_within() {
+        let local: DateTime<Local> = Local::now();
+
+        let jwt = JWTClaims {
+            exp: (local + Duration::days(3)).timestamp(),
+        };
+
+        assert!(jwt_expires_within(&jwt, Duration::days(7)));
+        assert!(!jwt_expires_within(&jwt, Duration::days(1)));
+    }
 }
@@ -1,6 +1,8 @@
 use chrono::NaiveDateTime;
 
-#[derive(Debug, Queryable)]
+use crate::htb::structs::{HTBContentType, TrackedContentType};
+
+#[derive(Debug, Queryable, Clone)]
 pub struct Ctf {
     pub id: i32,
     pub name: String,
@@ -9,6 +11,10 @@ pub struct Ctf {
     pub api_key: String,
     pub channel_id: i64,
     pub active: bool,
+    pub status_message_id: Option<i64>,
+    pub end_time: Option<NaiveDateTime>,
+    pub started_at: NaiveDateTime,
+    pub stale_minutes: Option<i32>,
 }
 
 #[derive(Debug, Queryable, Clone)]
@@ -18,11 +24,61 @@ pub struct Challenge {
     pub name: String,
     pub category: String,
     pub solved: bool,
-    pub working: Option<String>,
     pub solver: Option<String>,
     pub points: i32,
     pub solved_time: Option<NaiveDateTime>,
     pub announced_solve: bool,
+    pub thread_id: Option<i64>,
+    pub description: Option<String>,
+    pub thread_message_id: Option<i64>,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl Ctf {
+    /// A running CTF for tests, change whatever the test cares about.
+    pub fn sample(id: i32, name: &str) -> Self {
+        Ctf {
+            id,
+            name: name.to_string(),
+            base_url: "https://ctf.example".to_string(),
+            api_url: "https://ctf.example/api/v1".to_string(),
+            api_key: String::new(),
+            channel_id: 1,
+            active: true,
+            status_message_id: None,
+            end_time: None,
+            started_at: NaiveDateTime::from_timestamp(0, 0),
+            stale_minutes: None,
+        }
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl Challenge {
+    /// An unsolved 100 point challenge for tests, change whatever the test cares about.
+    pub fn sample(name: &str, category: &str) -> Self {
+        Challenge {
+            id: 1,
+            ctf_id: 1,
+            name: name.to_string(),
+            category: category.to_string(),
+            solved: false,
+            solver: None,
+            points: 100,
+            solved_time: None,
+            announced_solve: false,
+            thread_id: None,
+            description: None,
+            thread_message_id: None,
+        }
+    }
+
+    pub fn solved_by(mut self, solver: &str) -> Self {
+        self.solved = true;
+        self.solver = Some(solver.to_string());
+        self.announced_solve = true;
+        self
+    }
 }
 
 #[derive(Debug, Queryable, Clone)]
@@ -40,11 +96,31 @@ pub struct HTBChallenge {
     pub htb_id: i32,
     pub name: String,
     pub difficulty: String,
-    pub points: String,
-    pub release_date: String,
-    pub challenge_category: i32,
-    pub working: Option<String>,
+    pub points: i32,
+    pub release_date: Option<NaiveDateTime>,
+    pub challenge_category: Option<i32>,
     pub machine_avatar: Option<String>,
+    pub retired: bool,
+    pub retired_date: Option<NaiveDateTime>,
+    pub os: Option<String>,
+    pub announced: bool,
+    pub content_type: HTBContentType,
+    pub team_id: i32,
+}
+
+impl HTBChallenge {
+    pub fn is_machine(&self) -> bool {
+        self.content_type == HTBContentType::Machine
+    }
+}
+
+#[derive(Debug, Queryable, Clone)]
+pub struct HTBChallengeWorker {
+    pub id: i32,
+    pub challenge_id: i32,
+    pub discord_id: i64,
+    pub name: String,
+    pub claimed_at: NaiveDateTime,
 }
 
 #[derive(Debug, Queryable, Clone)]
@@ -56,6 +132,9 @@ pub struct HTBSolve {
     pub solve_type: String,
     pub announced: bool,
     pub solved_time: NaiveDateTime,
+    pub blood_position: Option<i32>,
+    pub team_first: bool,
+    pub team_id: i32,
 }
 
 #[derive(Debug, Queryable, Clone)]
@@ -64,6 +143,7 @@ pub struct HTBRank {
     pub rank: i32,
     pub points: i32,
     pub timestamp: NaiveDateTime,
+    pub team_id: i32,
 }
 
 #[derive(Debug, Queryable)]
@@ -71,4 +151,119 @@ pub struct HTBUserMapping {
     pub entry_id: i32,
     pub htb_id: i32,
     pub discord_id: i64,
+    pub rank_id: Option<i32>,
+}
+
+#[derive(Debug, Queryable, Clone)]
+pub struct HTBLinkRequest {
+    pub entry_id: i32,
+    pub htb_id: i32,
+    pub discord_id: i64,
+    pub code: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Queryable, Clone)]
+pub struct HTBScheduledRelease {
+    pub id: i32,
+    pub htb_id: i32,
+    pub name: String,
+    pub os: String,
+    pub difficulty: String,
+    pub avatar: String,
+    pub release_time: NaiveDateTime,
+    pub countdown_announced: bool,
+    pub reminder_sent: bool,
+    pub team_id: i32,
+}
+
+#[derive(Debug, Queryable, Clone)]
+pub struct HTBTrackedContent {
+    pub id: i32,
+    pub htb_id: i32,
+    pub content_type: TrackedContentType,
+    pub name: String,
+    pub total_flags: i32,
+    pub team_id: i32,
+}
+
+#[derive(Debug, Queryable, Clone)]
+pub struct HTBFlagCapture {
+    pub id: i32,
+    pub content_id: i32,
+    pub user_id: i32,
+    pub username: String,
+    pub flag_title: String,
+    pub points: i32,
+    pub announced: bool,
+    pub captured_time: NaiveDateTime,
+    pub team_id: i32,
+}
+
+#[derive(Debug, Queryable, Clone)]
+pub struct HTBMemberSnapshot {
+    pub entry_id: i32,
+    pub htb_id: i32,
+    pub name: String,
+    pub rank: i32,
+    pub rank_text: String,
+    pub points: i32,
+    pub user_owns: i32,
+    pub root_owns: i32,
+    pub timestamp: NaiveDateTime,
+    pub team_id: i32,
+}
+
+#[derive(Debug, Queryable, Clone)]
+pub struct ChallengeWorker {
+    pub id: i32,
+    pub challenge_id: i32,
+    pub name: String,
+    pub discord_id: i64,
+    pub claimed_at: NaiveDateTime,
+    pub confirmed_at: NaiveDateTime,
+    pub prompted_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Queryable, Clone)]
+pub struct ChallengeNote {
+    pub id: i32,
+    pub challenge_id: i32,
+    pub discord_id: Option<i64>,
+    pub author: String,
+    pub content: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Queryable, Clone)]
+pub struct ChallengeWriteup {
+    pub id: i32,
+    pub challenge_id: i32,
+    pub discord_id: i64,
+    pub author: String,
+    pub url: Option<String>,
+    pub content: Option<String>,
+    pub submitted_at: NaiveDateTime,
+}
+
+#[derive(Debug, Queryable, Clone)]
+pub struct AnnouncementRoute {
+    pub id: i32,
+    pub guild_id: i64,
+    pub ctf_id: Option<i32>,
+    pub source: String,
+    pub category: String,
+    pub channel_id: i64,
+    pub role_id: Option<i64>,
+    pub team_id: Option<i32>,
+}
+
+#[derive(Debug, Queryable, Clone)]
+pub struct AnnouncementTemplate {
+    pub id: i32,
+    pub guild_id: i64,
+    pub ctf_id: Option<i32>,
+    pub kind: String,
+    pub title: String,
+    pub description: Option<String>,
 }
@@ -0,0 +1,78 @@
+use diesel::prelude::*;
+use diesel::{insert_into, QueryDsl, RunQueryDsl};
+use failure::Error;
+
+use crate::get_pooled_connection;
+use crate::models::ChallengeNote;
+use crate::schema::challenge_notes::dsl as note_dsl;
+
+/// The longest note that can be added, so a list of them still fits in an embed.
+pub const MAX_NOTE_LENGTH: usize = 1000;
+
+/// Trims a note, refusing it if there's nothing left or it's too long.
+pub fn clean_note(content: &str) -> Result<&str, Error> {
+    let content = content.trim();
+
+    if content.is_empty() {
+        return Err(format_err!("The note is empty!"));
+    }
+
+    if content.chars().count() > MAX_NOTE_LENGTH {
+        return Err(format_err!(
+            "Notes can't be longer than {} characters!",
+            MAX_NOTE_LENGTH
+        ));
+    }
+
+    Ok(content)
+}
+
+/// Every note on a challenge, oldest first.
+pub async fn get_challenge_notes(challenge_id: i32) -> Result<Vec<ChallengeNote>, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let notes = note_dsl::challenge_notes
+        .filter(note_dsl::challenge_id.eq(challenge_id))
+        .order_by((note_dsl::created_at, note_dsl::id))
+        .load::<ChallengeNote>(&connection)?;
+
+    Ok(notes)
+}
+
+/// Adds a note to a challenge, the Discord id is empty for notes from the dashboard.
+pub async fn add_challenge_note(
+    challenge_id: i32,
+    discord_id: Option<i64>,
+    author: &str,
+    content: &str,
+) -> Result<(), Error> {
+    let content = clean_note(content)?;
+    let connection = get_pooled_connection().await?;
+
+    insert_into(note_dsl::challenge_notes)
+        .values((
+            note_dsl::challenge_id.eq(challenge_id),
+            note_dsl::discord_id.eq(discord_id),
+            note_dsl::author.eq(author),
+            note_dsl::content.eq(content),
+        ))
+        .execute(&connection)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_note() {
+        assert_eq!(
+            clean_note("  found the key in /etc  ").unwrap(),
+            "found the key in /etc"
+        );
+        assert!(clean_note("   ").is_err());
+        assert!(clean_note(&"a".repeat(MAX_NOTE_LENGTH)).is_ok());
+        assert!(clean_note(&"a".repeat(MAX_NOTE_LENGTH + 1)).is_err());
+    }
+}
@@ -1,3 +1,62 @@
+table! {
+    announcement_routes (id) {
+        id -> Integer,
+        guild_id -> Bigint,
+        ctf_id -> Nullable<Integer>,
+        source -> Varchar,
+        category -> Varchar,
+        channel_id -> Bigint,
+        role_id -> Nullable<Bigint>,
+        team_id -> Nullable<Integer>,
+    }
+}
+
+table! {
+    announcement_templates (id) {
+        id -> Integer,
+        guild_id -> Bigint,
+        ctf_id -> Nullable<Integer>,
+        kind -> Varchar,
+        title -> Text,
+        description -> Nullable<Text>,
+    }
+}
+
+table! {
+    challenge_notes (id) {
+        id -> Integer,
+        challenge_id -> Integer,
+        discord_id -> Nullable<Bigint>,
+        author -> Varchar,
+        content -> Text,
+        created_at -> Datetime,
+    }
+}
+
+table! {
+    challenge_workers (id) {
+        id -> Integer,
+        challenge_id -> Integer,
+        name -> Varchar,
+        discord_id -> Bigint,
+        claimed_at -> Datetime,
+        confirmed_at -> Datetime,
+        prompted_at -> Nullable<Datetime>,
+    }
+}
+
+table! {
+    challenge_writeups (id) {
+        id -> Integer,
+        challenge_id -> Integer,
+        discord_id -> Bigint,
+        author -> Varchar,
+        url -> Nullable<Text>,
+        content -> Nullable<Text>,
+        submitted_at -> Datetime,
+    }
+}
+
 table! {
     challenges (id) {
         id -> Integer,
@@ -5,11 +64,13 @@ table! {
         name -> Text,
         category -> Text,
         solved -> Bool,
-        working -> Nullable<Text>,
         solver -> Nullable<Text>,
         points -> Integer,
         solved_time -> Nullable<Datetime>,
         announced_solve -> Bool,
+        thread_id -> Nullable<Bigint>,
+        description -> Nullable<Text>,
+        thread_message_id -> Nullable<Bigint>,
     }
 }
 
@@ -22,6 +83,20 @@ table! {
         api_key -> Text,
         channel_id -> Bigint,
         active -> Bool,
+        status_message_id -> Nullable<Bigint>,
+        end_time -> Nullable<Datetime>,
+        started_at -> Datetime,
+        stale_minutes -> Nullable<Integer>,
+    }
+}
+
+table! {
+    htb_challenge_workers (id) {
+        id -> Integer,
+        challenge_id -> Integer,
+        discord_id -> Bigint,
+        name -> Varchar,
+        claimed_at -> Datetime,
     }
 }
 
@@ -31,11 +106,70 @@ table! {
         htb_id -> Integer,
         name -> Text,
         difficulty -> Text,
-        points -> Text,
-        release_date -> Text,
-        challenge_category -> Integer,
-        working -> Nullable<Text>,
+        points -> Integer,
+        release_date -> Nullable<Datetime>,
+        challenge_category -> Nullable<Integer>,
         machine_avatar -> Nullable<Text>,
+        retired -> Bool,
+        retired_date -> Nullable<Datetime>,
+        os -> Nullable<Text>,
+        announced -> Bool,
+        content_type -> Text,
+        team_id -> Integer,
+    }
+}
+
+table! {
+    htb_flag_captures (id) {
+        id -> Integer,
+        content_id -> Integer,
+        user_id -> Integer,
+        username -> Text,
+        flag_title -> Text,
+        points -> Integer,
+        announced -> Bool,
+        captured_time -> Datetime,
+        team_id -> Integer,
+    }
+}
+
+table! {
+    htb_link_requests (entry_id) {
+        entry_id -> Integer,
+        htb_id -> Integer,
+        discord_id -> Bigint,
+        code -> Text,
+        created_at -> Datetime,
+    }
+}
+
+table! {
+    htb_member_snapshots (entry_id) {
+        entry_id -> Integer,
+        htb_id -> Integer,
+        name -> Text,
+        rank -> Integer,
+        rank_text -> Text,
+        points -> Integer,
+        user_owns -> Integer,
+        root_owns -> Integer,
+        timestamp -> Datetime,
+        team_id -> Integer,
+    }
+}
+
+table! {
+    htb_scheduled_releases (id) {
+        id -> Integer,
+        htb_id -> Integer,
+        name -> Text,
+        os -> Text,
+        difficulty -> Text,
+        avatar -> Text,
+        release_time -> Datetime,
+        countdown_announced -> Bool,
+        reminder_sent -> Bool,
+        team_id -> Integer,
     }
 }
 
@@ -48,6 +182,17 @@ table! {
         solve_type -> Text,
         announced -> Bool,
         solved_time -> Datetime,
+        blood_position -> Nullable<Integer>,
+        team_first -> Bool,
+        team_id -> Integer,
+    }
+}
+
+table! {
+    htb_team_members (id) {
+        id -> Integer,
+        team_id -> Integer,
+        htb_id -> Integer,
     }
 }
 
@@ -57,6 +202,18 @@ table! {
         rank -> Integer,
         points -> Integer,
         timestamp -> Datetime,
+        team_id -> Integer,
+    }
+}
+
+table! {
+    htb_tracked_content (id) {
+        id -> Integer,
+        htb_id -> Integer,
+        content_type -> Text,
+        name -> Text,
+        total_flags -> Integer,
+        team_id -> Integer,
     }
 }
 
@@ -65,6 +222,7 @@ table! {
         entry_id -> Integer,
         htb_id -> Integer,
         discord_id -> Bigint,
+        rank_id -> Nullable<Integer>,
     }
 }
 
@@ -79,11 +237,23 @@ table! {
 }
 
 allow_tables_to_appear_in_same_query!(
+    announcement_routes,
+    announcement_templates,
+    challenge_notes,
+    challenge_workers,
+    challenge_writeups,
     challenges,
     ctfs,
+    htb_challenge_workers,
     htb_challenges,
+    htb_flag_captures,
+    htb_link_requests,
+    htb_member_snapshots,
+    htb_scheduled_releases,
     htb_solves,
+    htb_team_members,
     htb_team_rank,
+    htb_tracked_content,
     htb_user_id_mapping,
     scoreboard,
 );
@@ -0,0 +1,346 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use diesel::prelude::*;
+use diesel::{insert_into, update, QueryDsl, RunQueryDsl};
+use failure::Error;
+
+use crate::ctfs::db::get_challenges_for_ctfid;
+use crate::get_pooled_connection;
+use crate::models::{Challenge, ChallengeNote, ChallengeWriteup, Ctf};
+use crate::notes::get_challenge_notes;
+use crate::schema::challenge_writeups::dsl as writeup_dsl;
+
+/// The biggest markdown file that can be attached as a writeup.
+pub const MAX_WRITEUP_BYTES: usize = 1024 * 1024;
+
+/// A writeup is either a link to one posted elsewhere, or the markdown itself.
+#[derive(Debug, PartialEq)]
+pub enum WriteupBody {
+    Url(String),
+    Markdown(String),
+}
+
+impl WriteupBody {
+    pub fn url(url: &str) -> Result<Self, Error> {
+        let url = url.trim();
+
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            return Err(format_err!(
+                "Writeups need to be a http(s) link or an attached markdown file!"
+            ));
+        }
+
+        Ok(WriteupBody::Url(url.to_string()))
+    }
+
+    pub fn markdown(bytes: Vec<u8>) -> Result<Self, Error> {
+        if bytes.len() > MAX_WRITEUP_BYTES {
+            return Err(format_err!(
+                "Writeups can't be bigger than {}KB!",
+                MAX_WRITEUP_BYTES / 1024
+            ));
+        }
+
+        let markdown = String::from_utf8(bytes)
+            .map_err(|_| format_err!("The attached writeup isn't a text file!"))?;
+
+        if markdown.trim().is_empty() {
+            return Err(format_err!("The attached writeup is empty!"));
+        }
+
+        Ok(WriteupBody::Markdown(markdown))
+    }
+}
+
+/// Stores someone's writeup for a challenge, replacing any they'd already sent.
+pub async fn set_challenge_writeup(
+    challenge_id: i32,
+    discord_id: i64,
+    author: &str,
+    body: &WriteupBody,
+) -> Result<(), Error> {
+    let connection = get_pooled_connection().await?;
+
+    let (url, content) = match body {
+        WriteupBody::Url(url) => (Some(url.as_str()), None),
+        WriteupBody::Markdown(markdown) => (None, Some(markdown.as_str())),
+    };
+
+    let existing = writeup_dsl::challenge_writeups
+        .select(writeup_dsl::id)
+        .filter(writeup_dsl::challenge_id.eq(challenge_id))
+        .filter(writeup_dsl::discord_id.eq(discord_id))
+        .load::<i32>(&connection)?;
+
+    if existing.is_empty() {
+        insert_into(writeup_dsl::challenge_writeups)
+            .values((
+                writeup_dsl::challenge_id.eq(challenge_id),
+                writeup_dsl::discord_id.eq(discord_id),
+                writeup_dsl::author.eq(author),
+                writeup_dsl::url.eq(url),
+                writeup_dsl::content.eq(content),
+            ))
+            .execute(&connection)?;
+    } else {
+        update(writeup_dsl::challenge_writeups.filter(writeup_dsl::id.eq_any(existing)))
+            .set((
+                writeup_dsl::author.eq(author),
+                writeup_dsl::url.eq(url),
+                writeup_dsl::content.eq(content),
+                writeup_dsl::submitted_at.eq(Local::now().naive_local()),
+            ))
+            .execute(&connection)?;
+    }
+
+    Ok(())
+}
+
+/// Every writeup for any of the given challenges, oldest first.
+pub async fn get_challenge_writeups(challenge_ids: &[i32]) -> Result<Vec<ChallengeWriteup>, Error> {
+    let connection = get_pooled_connection().await?;
+
+    let writeups = writeup_dsl::challenge_writeups
+        .filter(writeup_dsl::challenge_id.eq_any(challenge_ids))
+        .order_by(writeup_dsl::submitted_at)
+        .load::<ChallengeWriteup>(&connection)?;
+
+    Ok(writeups)
+}
+
+/// Makes a CTF, category or challenge name safe to use as a folder name.
+pub fn folder_name(name: &str) -> String {
+    let cleaned: String = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let cleaned = cleaned.trim_matches('_');
+
+    if cleaned.is_empty() {
+        "unnamed".to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+/// The CTF's folder, ``<id>-<name>``. The id keeps CTFs whose names come out the same once
+/// they're made safe from sharing, and replacing, each other's export.
+pub fn ctf_folder(root: &Path, ctf: &Ctf) -> PathBuf {
+    root.join(format!("{}-{}", ctf.id, folder_name(&ctf.name)))
+}
+
+/// Where a challenge's writeup goes, ``<id>-<ctf>/<category>/<challenge>``.
+pub fn challenge_folder(root: &Path, ctf: &Ctf, category: &str, challenge: &str) -> PathBuf {
+    ctf_folder(root, ctf)
+        .join(folder_name(category))
+        .join(folder_name(challenge))
+}
+
+/// Where each challenge's writeup goes. Challenges whose names come out the same once they're made
+/// safe, e.g. "Baby RSA" and "Baby_RSA", have their id added so neither overwrites the other.
+pub fn challenge_folders(root: &Path, ctf: &Ctf, challenges: &[Challenge]) -> Vec<PathBuf> {
+    let folders: Vec<PathBuf> = challenges
+        .iter()
+        .map(|challenge| challenge_folder(root, ctf, &challenge.category, &challenge.name))
+        .collect();
+
+    folders
+        .iter()
+        .zip(challenges)
+        .map(|(folder, challenge)| {
+            if folders.iter().filter(|other| *other == folder).count() > 1 {
+                folder.with_file_name(format!("{}-{}", folder_name(&challenge.name), challenge.id))
+            } else {
+                folder.clone()
+            }
+        })
+        .collect()
+}
+
+/// The README for a challenge's folder, with its details, the team's notes and everyone's
+/// writeups.
+pub fn render_readme(
+    challenge: &Challenge,
+    notes: &[ChallengeNote],
+    writeups: &[ChallengeWriteup],
+) -> String {
+    let mut readme = format!(
+        "# {}\n\n**Category:** {}  \n**Points:** {}  \n**Solved by:** {}\n",
+        challenge.name,
+        challenge.category,
+        challenge.points,
+        challenge.solver.as_deref().unwrap_or("Unsolved")
+    );
+
+    if let Some(description) = &challenge.description {
+        readme.push_str(&format!("\n## Description\n\n{}\n", description.trim()));
+    }
+
+    if !notes.is_empty() {
+        readme.push_str("\n## Notes\n\n");
+        for note in notes {
+            readme.push_str(&format!(
+                "- `{}` **{}**: {}\n",
+                note.created_at.format("%Y-%m-%d %H:%M"),
+                note.author,
+                note.content
+            ));
+        }
+    }
+
+    for writeup in writeups {
+        readme.push_str(&format!("\n## Writeup by {}\n\n", writeup.author));
+
+        match (&writeup.content, &writeup.url) {
+            (Some(content), _) => readme.push_str(&format!("{}\n", content.trim())),
+            (None, Some(url)) => readme.push_str(&format!("<{}>\n", url)),
+            (None, None) => {}
+        }
+    }
+
+    readme
+}
+
+/// Writes every challenge in the CTF out as ``<id>-<ctf>/<category>/<challenge>/README.md`` under
+/// the given folder, returning the CTF's folder. Any earlier export of the CTF is replaced, so
+/// challenges that have since been renamed don't leave their old folder behind.
+pub async fn export_ctf_writeups(ctf: &Ctf, root: &Path) -> Result<PathBuf, Error> {
+    let challenges = get_challenges_for_ctfid(ctf.id).await?;
+    let challenge_ids: Vec<i32> = challenges.iter().map(|challenge| challenge.id).collect();
+    let writeups = get_challenge_writeups(&challenge_ids).await?;
+    let folders = challenge_folders(root, ctf, &challenges);
+
+    let export_folder = ctf_folder(root, ctf);
+    if export_folder.exists() {
+        fs::remove_dir_all(&export_folder)?;
+    }
+
+    for (challenge, folder) in challenges.iter().zip(folders) {
+        let notes = get_challenge_notes(challenge.id).await?;
+        let challenge_writeups: Vec<ChallengeWriteup> = writeups
+            .iter()
+            .filter(|writeup| writeup.challenge_id == challenge.id)
+            .cloned()
+            .collect();
+
+        fs::create_dir_all(&folder)?;
+        fs::write(
+            folder.join("README.md"),
+            render_readme(challenge, &notes, &challenge_writeups),
+        )?;
+    }
+
+    Ok(export_folder)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+
+    fn time() -> NaiveDateTime {
+        NaiveDateTime::parse_from_str("2026-10-19 09:05:00", "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn test_writeup_body() {
+        assert_eq!(
+            WriteupBody::url(" https://blog.example/baby-rsa ").unwrap(),
+            WriteupBody::Url("https://blog.example/baby-rsa".to_string())
+        );
+        assert!(WriteupBody::url("javascript:alert(1)").is_err());
+        assert!(WriteupBody::markdown(b"  \n".to_vec()).is_err());
+        assert!(WriteupBody::markdown(vec![0xff, 0xfe]).is_err());
+        assert!(WriteupBody::markdown(vec![b'a'; MAX_WRITEUP_BYTES + 1]).is_err());
+    }
+
+    #[test]
+    fn test_challenge_folder() {
+        assert_eq!(
+            challenge_folder(
+                Path::new("writeups"),
+                &Ctf::sample(4, "DUCTF 2021"),
+                "pwn",
+                "../../etc/passwd"
+            ),
+            PathBuf::from("writeups/4-DUCTF_2021/pwn/etc_passwd")
+        );
+        assert_eq!(folder_name("???"), "unnamed");
+    }
+
+    #[test]
+    fn test_ctf_folder() {
+        // Names that come out the same once they're made safe still get their own folders
+        assert_ne!(
+            ctf_folder(Path::new("writeups"), &Ctf::sample(1, "DUCTF 2021")),
+            ctf_folder(Path::new("writeups"), &Ctf::sample(2, "DUCTF_2021"))
+        );
+    }
+
+    #[test]
+    fn test_challenge_folders() {
+        let challenges = vec![
+            Challenge {
+                id: 1,
+                ..Challenge::sample("Baby RSA", "crypto")
+            },
+            Challenge {
+                id: 2,
+                ..Challenge::sample("Baby_RSA", "crypto")
+            },
+            Challenge {
+                id: 3,
+                ..Challenge::sample("Baby RSA", "pwn")
+            },
+        ];
+
+        assert_eq!(
+            challenge_folders(Path::new("writeups"), &Ctf::sample(4, "DUCTF"), &challenges),
+            vec![
+                PathBuf::from("writeups/4-DUCTF/crypto/Baby_RSA-1"),
+                PathBuf::from("writeups/4-DUCTF/crypto/Baby_RSA-2"),
+                PathBuf::from("writeups/4-DUCTF/pwn/Baby_RSA"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_readme() {
+        let challenge = Challenge {
+            description: Some("e is tiny\n".to_string()),
+            ..Challenge::sample("Baby RSA", "crypto").solved_by("alice")
+        };
+        let notes = vec![ChallengeNote {
+            id: 1,
+            challenge_id: 1,
+            discord_id: None,
+            author: "bob".to_string(),
+            content: "try a cube root".to_string(),
+            created_at: time(),
+        }];
+        let writeups = vec![ChallengeWriteup {
+            id: 1,
+            challenge_id: 1,
+            discord_id: 10,
+            author: "alice".to_string(),
+            url: Some("https://blog.example/baby-rsa".to_string()),
+            content: None,
+            submitted_at: time(),
+        }];
+
+        assert_eq!(
+            render_readme(&challenge, &notes, &writeups),
+            "# Baby RSA\n\n**Category:** crypto  \n**Points:** 100  \n**Solved by:** alice\n\n## Description\n\ne is tiny\n\n## Notes\n\n- `2026-10-19 09:05` **bob**: try a cube root\n\n## Writeup by alice\n\n<https://blog.example/baby-rsa>\n"
+        );
+    }
+}
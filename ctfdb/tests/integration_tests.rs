@@ -1,22 +1,24 @@
 use std::env;
 
-use ctfdb::htb::structs::{HTBAPIConfig, HTBApi};
+use ctfdb::htb::structs::{HTBAPIConfig, HTBApi, HTBAuth};
 
 async fn get_htb_api() -> HTBApi {
     dotenv::dotenv().ok();
 
-    let email = env::var("HTB_EMAIL").expect("No HTB_EMAIL environment variable defined!");
-    let password = env::var("HTB_PASSWORD").expect("No HTB_PASSWORD environment variable defined!");
+    let auth = match env::var("HTB_APP_TOKEN") {
+        Ok(token) => HTBAuth::AppToken(token),
+        Err(_) => HTBAuth::Credentials {
+            email: env::var("HTB_EMAIL").expect("No HTB_EMAIL environment variable defined!"),
+            password: env::var("HTB_PASSWORD")
+                .expect("No HTB_PASSWORD environment variable defined!"),
+        },
+    };
     let team_id = env::var("HTB_TEAM_ID")
         .expect("No HTB_TEAM_ID environment variable defined!")
         .parse::<i32>()
         .expect("HTB_TEAM_ID needs to be an integer!");
 
-    let config = HTBAPIConfig {
-        email,
-        password,
-        team_id,
-    };
+    let config = HTBAPIConfig { auth, team_id };
 
     let htb_api = ctfdb::htb::api::new_htbapi_instance(config)
         .await
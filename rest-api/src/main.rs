@@ -1,13 +1,29 @@
 extern crate env_logger;
 
-use actix_web::{get, middleware, web, App, HttpResponse, HttpServer, Responder};
+use actix_web::{
+    get, http::header, middleware, post, web, App, HttpRequest, HttpResponse, HttpServer, Responder,
+};
 
 use actix_cors::Cors;
-use chrono::Utc;
-use ctfdb::{ctfs::db::{get_active_ctfs, get_challenges_for_ctfid, get_latest_scoreboard_status}, init_migrations};
+use chrono::{NaiveDateTime, Utc};
+use ctfdb::{
+    ctfs::db::{
+        get_active_ctfs, get_challenge_from_id, get_challenge_workers, get_challenges_for_ctfid,
+        get_latest_scoreboard_status,
+    },
+    htb::{
+        db::{
+            get_htb_challenge_workers, get_htb_team_ids, get_rank_history, get_team_leaderboard,
+            search_challenges,
+        },
+        structs::{bucket_rank_history, parse_htb_date, HTBSearchFilter, RankBucket},
+    },
+    init_migrations,
+    notes::{add_challenge_note, clean_note, get_challenge_notes},
+};
 use std::env;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize)]
 pub struct ChallengeForIDResponse {
@@ -16,6 +32,7 @@ pub struct ChallengeForIDResponse {
 
 #[derive(Debug, Serialize)]
 pub struct ChallengeResponse {
+    id: i32,
     title: String,
     category: String,
     status: String,
@@ -27,6 +44,73 @@ pub struct ChallengeResponse {
     solved_time: Option<String>,
 }
 
+#[derive(Debug, Serialize)]
+pub struct ChallengeNotesResponse {
+    data: Vec<ChallengeNoteResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChallengeNoteResponse {
+    id: i32,
+    author: String,
+    /// A string, as JavaScript can't hold a Discord id in a number.
+    discord_id: Option<String>,
+    content: String,
+    created_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewChallengeNote {
+    content: String,
+}
+
+/// Who can post notes, by the token they send. Notes are posted under the name their token is for.
+#[derive(Debug, Clone, Default)]
+pub struct NoteAuthors(Vec<(String, String)>);
+
+impl NoteAuthors {
+    /// Parses ``NOTE_TOKENS``, e.g. ``frontend:some-secret,alice:another-secret``.
+    fn parse(tokens: &str) -> Self {
+        NoteAuthors(
+            tokens
+                .split(',')
+                .filter_map(|entry| {
+                    let (author, token) = entry.split_once(':')?;
+                    let (author, token) = (author.trim(), token.trim());
+
+                    if author.is_empty() || author.chars().count() > 255 || token.is_empty() {
+                        eprintln!("Ignoring a NOTE_TOKENS entry without a valid name and token");
+                        return None;
+                    }
+
+                    Some((author.to_string(), token.to_string()))
+                })
+                .collect(),
+        )
+    }
+
+    /// The author for a request's ``Authorization: Bearer <token>`` header.
+    fn author_for(&self, authorization: Option<&str>) -> Option<&str> {
+        let token = authorization?.strip_prefix("Bearer ")?.trim();
+
+        self.0
+            .iter()
+            .find(|(_, known)| tokens_match(known, token))
+            .map(|(author, _)| author.as_str())
+    }
+}
+
+/// Compares tokens without stopping at the first difference, so how long it takes doesn't give
+/// away how much of a token was right.
+fn tokens_match(known: &str, given: &str) -> bool {
+    known.len() == given.len()
+        && known
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
 #[derive(Serialize)]
 pub struct ActiveCTFResponse {
     data: Vec<CTFResponse>,
@@ -46,6 +130,78 @@ pub struct ScoreboardResponse {
     entry_time: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct HTBSearchQuery {
+    name: Option<String>,
+    status: Option<String>,
+    difficulty: Option<String>,
+    os: Option<String>,
+    team: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HTBChallengeListResponse {
+    data: Vec<HTBChallengeResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HTBChallengeResponse {
+    id: i32,
+    name: String,
+    difficulty: String,
+    points: i32,
+    release_date: Option<String>,
+    content_type: String,
+    machine: bool,
+    os: Option<String>,
+    retired: bool,
+    retired_date: Option<String>,
+    working: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HTBTeamQuery {
+    team: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LeaderboardResponse {
+    data: Vec<LeaderboardEntryResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LeaderboardEntryResponse {
+    id: i32,
+    name: String,
+    rank: i32,
+    rank_text: String,
+    points: i32,
+    user_owns: i32,
+    root_owns: i32,
+    weekly_points_gained: i32,
+    weekly_owns_gained: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RankHistoryQuery {
+    from: Option<String>,
+    to: Option<String>,
+    bucket: Option<String>,
+    team: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RankHistoryResponse {
+    data: Vec<RankHistoryPointResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RankHistoryPointResponse {
+    timestamp: String,
+    rank: i32,
+    points: i32,
+}
+
 #[get("/api/v1/active")]
 async fn get_active_ctfs_route() -> impl Responder {
     return match get_active_ctfs().await {
@@ -117,9 +273,25 @@ async fn get_stats_for_id_route(web::Path(id): web::Path<i32>) -> impl Responder
 async fn get_challenges_for_id_route(web::Path(id): web::Path<i32>) -> impl Responder {
     return match get_challenges_for_ctfid(id).await {
         Ok(challenges) => {
+            let challenge_ids: Vec<i32> = challenges.iter().map(|challenge| challenge.id).collect();
+            let workers = match get_challenge_workers(&challenge_ids).await {
+                Ok(workers) => workers,
+                Err(why) => {
+                    eprintln!("Error when retrieving workers from database... {}", why);
+                    vec![]
+                }
+            };
+
             let mut data = vec![];
 
             for challenge in challenges {
+                let working = working_names(
+                    workers
+                        .iter()
+                        .filter(|worker| worker.challenge_id == challenge.id)
+                        .map(|worker| worker.name.as_str()),
+                );
+
                 // Break down solved time to string for serialisation purposes
                 let solved_time;
                 if challenge.solved {
@@ -131,7 +303,7 @@ async fn get_challenges_for_id_route(web::Path(id): web::Path<i32>) -> impl Resp
                 let challenge_status;
                 if challenge.solved {
                     challenge_status = "DONE".to_string();
-                } else if challenge.working.is_some() {
+                } else if working.is_some() {
                     challenge_status = "INPROGRESS".to_string();
                 } else {
                     challenge_status = "TODO".to_string();
@@ -140,8 +312,9 @@ async fn get_challenges_for_id_route(web::Path(id): web::Path<i32>) -> impl Resp
                 let challenge_priority = get_challenge_priority(challenge.points);
 
                 let challenge_response = ChallengeResponse {
+                    id: challenge.id,
                     category: challenge.category,
-                    working: challenge.working,
+                    working,
                     solver: challenge.solver,
                     points: challenge.points,
                     solved_time,
@@ -164,6 +337,296 @@ async fn get_challenges_for_id_route(web::Path(id): web::Path<i32>) -> impl Resp
     };
 }
 
+#[get("/api/v1/challenges/{id}/notes")]
+async fn get_challenge_notes_route(web::Path(id): web::Path<i32>) -> impl Responder {
+    return match get_challenge_notes(id).await {
+        Ok(notes) => {
+            let data = notes
+                .into_iter()
+                .map(|note| ChallengeNoteResponse {
+                    id: note.id,
+                    author: note.author,
+                    discord_id: note.discord_id.map(|discord_id| discord_id.to_string()),
+                    content: note.content,
+                    created_at: note.created_at.to_string(),
+                })
+                .collect();
+
+            HttpResponse::Ok().json(ChallengeNotesResponse { data })
+        }
+        Err(why) => {
+            eprintln!("Error when retrieving notes from database... {}", why);
+            HttpResponse::InternalServerError().body("Error retrieving notes from database")
+        }
+    };
+}
+
+#[post("/api/v1/challenges/{id}/notes")]
+async fn add_challenge_note_route(
+    request: HttpRequest,
+    authors: web::Data<NoteAuthors>,
+    web::Path(id): web::Path<i32>,
+    web::Json(note): web::Json<NewChallengeNote>,
+) -> impl Responder {
+    let authorization = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+
+    let author = match authors.author_for(authorization) {
+        Some(author) => author,
+        None => return HttpResponse::Unauthorized().body("A valid note token is needed"),
+    };
+
+    if let Err(why) = clean_note(&note.content) {
+        return HttpResponse::BadRequest().body(why.to_string());
+    }
+
+    if get_challenge_from_id(id).await.is_err() {
+        return HttpResponse::NotFound().body(format!("No challenge exists with id {}", id));
+    }
+
+    return match add_challenge_note(id, None, author, &note.content).await {
+        Ok(_) => HttpResponse::Created().finish(),
+        Err(why) => {
+            eprintln!("Error when adding note to database... {}", why);
+            HttpResponse::InternalServerError().body("Error adding note to database")
+        }
+    };
+}
+
+/// Everyone working on a challenge by the name they last used, the API can't ask Discord for
+/// their current one.
+fn working_names<'a>(names: impl Iterator<Item = &'a str>) -> Option<String> {
+    let names: Vec<&str> = names.collect();
+
+    if names.is_empty() {
+        None
+    } else {
+        Some(names.join(", "))
+    }
+}
+
+/// The HTB team set up in the environment, ``HTB_TEAM_ID`` or the team ID of the only team in
+/// ``HTB_TEAMS``.
+fn configured_htb_team() -> Option<i32> {
+    let team_id = env::var("HTB_TEAM_ID").ok().or_else(|| {
+        let teams = env::var("HTB_TEAMS").unwrap_or_default();
+        let mut names = teams
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty());
+
+        match (names.next(), names.next()) {
+            (Some(name), None) => env::var(format!("HTB_{}_TEAM_ID", name.to_uppercase())).ok(),
+            _ => None,
+        }
+    })?;
+
+    team_id.parse::<i32>().ok()
+}
+
+/// Picks the team for a request, the one asked for, then the configured team, then the only team
+/// with data. ``None`` when there's no telling which team is meant.
+fn pick_htb_team(team: Option<i32>, configured: Option<i32>, known: &[i32]) -> Option<i32> {
+    team.or(configured).or(match known {
+        [team_id] => Some(*team_id),
+        _ => None,
+    })
+}
+
+/// The HTB team a request is for, see `pick_htb_team`.
+async fn resolve_htb_team(team: Option<i32>) -> Option<i32> {
+    let configured = configured_htb_team();
+
+    // Only worth looking up the teams with data when there's nothing else to go on
+    let known = if team.is_none() && configured.is_none() {
+        get_htb_team_ids().await.unwrap_or_else(|why| {
+            eprintln!("Error loading HTB teams from database... {}", why);
+            vec![]
+        })
+    } else {
+        vec![]
+    };
+
+    pick_htb_team(team, configured, &known)
+}
+
+#[get("/api/v1/htb/challenges")]
+async fn search_htb_challenges_route(
+    web::Query(query): web::Query<HTBSearchQuery>,
+) -> impl Responder {
+    let team_id = match resolve_htb_team(query.team).await {
+        Some(team_id) => team_id,
+        None => {
+            return HttpResponse::BadRequest()
+                .body("Couldn't tell which HTB team is meant, pass ?team=<team id>")
+        }
+    };
+
+    let filter = HTBSearchFilter {
+        name: query.name,
+        retired: query
+            .status
+            .as_deref()
+            .and_then(HTBSearchFilter::retired_from_status),
+        difficulty: query.difficulty,
+        os: query.os,
+    };
+
+    return match search_challenges(team_id, &filter).await {
+        Ok(challenges) => {
+            let challenge_ids: Vec<i32> = challenges.iter().map(|challenge| challenge.id).collect();
+            let workers = match get_htb_challenge_workers(&challenge_ids).await {
+                Ok(workers) => workers,
+                Err(why) => {
+                    eprintln!("Error when retrieving HTB workers from database... {}", why);
+                    vec![]
+                }
+            };
+
+            let data = challenges
+                .into_iter()
+                .map(|challenge| {
+                    let machine = challenge.is_machine();
+                    let working = working_names(
+                        workers
+                            .iter()
+                            .filter(|worker| worker.challenge_id == challenge.id)
+                            .map(|worker| worker.name.as_str()),
+                    );
+
+                    HTBChallengeResponse {
+                        id: challenge.htb_id,
+                        name: challenge.name,
+                        difficulty: challenge.difficulty,
+                        points: challenge.points,
+                        release_date: challenge.release_date.map(|date| date.to_string()),
+                        machine,
+                        content_type: challenge.content_type.as_str().to_string(),
+                        os: challenge.os,
+                        retired: challenge.retired,
+                        retired_date: challenge.retired_date.map(|date| date.to_string()),
+                        working,
+                    }
+                })
+                .collect();
+
+            HttpResponse::Ok().json(HTBChallengeListResponse { data })
+        }
+        Err(why) => {
+            eprintln!("Error when searching HTB challenges in database... {}", why);
+            HttpResponse::InternalServerError().body("Error searching HTB challenges in database")
+        }
+    };
+}
+
+#[get("/api/v1/htb/leaderboard")]
+async fn get_htb_leaderboard_route(web::Query(query): web::Query<HTBTeamQuery>) -> impl Responder {
+    let team_id = match resolve_htb_team(query.team).await {
+        Some(team_id) => team_id,
+        None => {
+            return HttpResponse::BadRequest()
+                .body("Couldn't tell which HTB team is meant, pass ?team=<team id>")
+        }
+    };
+
+    return match get_team_leaderboard(team_id).await {
+        Ok(leaderboard) => {
+            let data = leaderboard
+                .into_iter()
+                .map(|entry| LeaderboardEntryResponse {
+                    id: entry.htb_id,
+                    name: entry.name,
+                    rank: entry.rank,
+                    rank_text: entry.rank_text,
+                    points: entry.points,
+                    user_owns: entry.user_owns,
+                    root_owns: entry.root_owns,
+                    weekly_points_gained: entry.points_gained,
+                    weekly_owns_gained: entry.owns_gained,
+                })
+                .collect();
+
+            HttpResponse::Ok().json(LeaderboardResponse { data })
+        }
+        Err(why) => {
+            eprintln!(
+                "Error when retrieving HTB leaderboard from database... {}",
+                why
+            );
+            HttpResponse::InternalServerError()
+                .body("Error retrieving HTB leaderboard from database")
+        }
+    };
+}
+
+type RankHistoryRange = (
+    Option<NaiveDateTime>,
+    Option<NaiveDateTime>,
+    Option<RankBucket>,
+);
+
+fn parse_rank_history_query(query: &RankHistoryQuery) -> Result<RankHistoryRange, String> {
+    let parse_date = |date: &Option<String>| match date {
+        Some(date) => parse_htb_date(date)
+            .map(Some)
+            .ok_or_else(|| format!("Unable to parse date '{}'", date)),
+        None => Ok(None),
+    };
+
+    let bucket = match &query.bucket {
+        Some(bucket) => Some(
+            RankBucket::from_name(bucket)
+                .ok_or_else(|| format!("Unknown bucket '{}', use hour, day or week", bucket))?,
+        ),
+        None => None,
+    };
+
+    Ok((parse_date(&query.from)?, parse_date(&query.to)?, bucket))
+}
+
+#[get("/api/v1/htb/rank")]
+async fn get_htb_rank_history_route(
+    web::Query(query): web::Query<RankHistoryQuery>,
+) -> impl Responder {
+    let (from, to, bucket) = match parse_rank_history_query(&query) {
+        Ok(range) => range,
+        Err(why) => return HttpResponse::BadRequest().body(why),
+    };
+
+    let team_id = match resolve_htb_team(query.team).await {
+        Some(team_id) => team_id,
+        None => {
+            return HttpResponse::BadRequest()
+                .body("Couldn't tell which HTB team is meant, pass ?team=<team id>")
+        }
+    };
+
+    return match get_rank_history(team_id, from, to).await {
+        Ok(history) => {
+            let data = bucket_rank_history(&history, bucket)
+                .into_iter()
+                .map(|point| RankHistoryPointResponse {
+                    timestamp: point.timestamp.to_string(),
+                    rank: point.rank,
+                    points: point.points,
+                })
+                .collect();
+
+            HttpResponse::Ok().json(RankHistoryResponse { data })
+        }
+        Err(why) => {
+            eprintln!(
+                "Error when retrieving HTB rank history from database... {}",
+                why
+            );
+            HttpResponse::InternalServerError()
+                .body("Error retrieving HTB rank history from database")
+        }
+    };
+}
+
 fn get_challenge_priority(points: i32) -> String {
     if points < 50 {
         "LOW".to_string()
@@ -189,19 +652,29 @@ async fn main() -> std::io::Result<()> {
 
     let bind_address = env::var("BIND_ADDRESS").unwrap_or_else(|_| "127.0.0.1:8010".to_owned());
 
+    // Notes can't be posted at all without any tokens set up
+    let note_authors = NoteAuthors::parse(&env::var("NOTE_TOKENS").unwrap_or_default());
+
     println!("Booting up rest-api service...");
 
-    HttpServer::new(|| {
+    HttpServer::new(move || {
         let allowed_origin =
             env::var("ALLOWED_ORIGIN").expect("No ALLOWED_ORIGIN environment variable set!");
 
         let cors = Cors::default()
             .allowed_origin(&allowed_origin)
-            .allowed_methods(vec!["GET"]);
+            .allowed_methods(vec!["GET", "POST"])
+            .allowed_headers(vec![header::CONTENT_TYPE, header::AUTHORIZATION]);
         App::new()
+            .data(note_authors.clone())
             .wrap(cors)
             .wrap(middleware::Logger::default())
+            .service(search_htb_challenges_route)
+            .service(get_htb_leaderboard_route)
+            .service(get_htb_rank_history_route)
             .service(get_challenges_for_id_route)
+            .service(get_challenge_notes_route)
+            .service(add_challenge_note_route)
             .service(get_active_ctfs_route)
             .service(get_stats_for_id_route)
     })
@@ -244,4 +717,58 @@ mod tests {
         assert_eq!(get_challenge_priority(260), expected);
         assert_eq!(get_challenge_priority(15000), expected);
     }
+
+    #[test]
+    fn test_parse_rank_history_query() {
+        let query = RankHistoryQuery {
+            from: Some("2021-10-01".to_string()),
+            to: None,
+            bucket: Some("week".to_string()),
+            team: None,
+        };
+        let (from, to, bucket) = parse_rank_history_query(&query).unwrap();
+        assert_eq!(from, parse_htb_date("2021-10-01"));
+        assert!(to.is_none());
+        assert_eq!(bucket, Some(RankBucket::Week));
+
+        let query = RankHistoryQuery {
+            from: Some("yesterday".to_string()),
+            to: None,
+            bucket: None,
+            team: None,
+        };
+        assert!(parse_rank_history_query(&query).is_err());
+
+        let query = RankHistoryQuery {
+            from: None,
+            to: None,
+            bucket: Some("fortnight".to_string()),
+            team: None,
+        };
+        assert!(parse_rank_history_query(&query).is_err());
+    }
+
+    #[test]
+    fn test_pick_htb_team() {
+        assert_eq!(pick_htb_team(Some(1), Some(2), &[3]), Some(1));
+        assert_eq!(pick_htb_team(None, Some(2), &[3, 4]), Some(2));
+        assert_eq!(pick_htb_team(None, None, &[3]), Some(3));
+        assert_eq!(pick_htb_team(None, None, &[3, 4]), None);
+        assert_eq!(pick_htb_team(None, None, &[]), None);
+    }
+
+    #[test]
+    fn test_note_authors() {
+        let authors = NoteAuthors::parse("frontend:some-secret, alice : another ,broken,:nameless");
+
+        assert_eq!(
+            authors.author_for(Some("Bearer some-secret")),
+            Some("frontend")
+        );
+        assert_eq!(authors.author_for(Some("Bearer another")), Some("alice"));
+        assert_eq!(authors.author_for(Some("Bearer some-secre")), None);
+        assert_eq!(authors.author_for(Some("some-secret")), None);
+        assert_eq!(authors.author_for(None), None);
+        assert_eq!(NoteAuthors::default().author_for(Some("Bearer ")), None);
+    }
 }